@@ -30,7 +30,6 @@ fn setup(
         StepModelLoader {
             handle: step_handle_1,
             position: Vec3::new(-30.0, 0.0, 0.0),
-            colour: Color::srgb(0.8, 0.7, 0.6),
             name: "Model 1".to_string(),
         },
         Name::new("STEP Model Loader 1"),
@@ -40,7 +39,6 @@ fn setup(
         StepModelLoader {
             handle: step_handle_2,
             position: Vec3::new(30.0, 0.0, 0.0),
-            colour: Color::srgb(0.6, 0.7, 0.8),
             name: "Model 2".to_string(),
         },
         Name::new("STEP Model Loader 2"),
@@ -106,7 +104,6 @@ fn setup(
 struct StepModelLoader {
     handle: Handle<StepAsset>,
     position: Vec3,
-    colour: Color,
     name: String,
 }
 
@@ -116,8 +113,6 @@ struct RotatingStepModel;
 fn load_step_models(
     _time: Res<Time>,
     step_assets: Res<Assets<StepAsset>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
     mut query: Query<(Entity, &StepModelLoader), Without<RotatingStepModel>>,
     mut commands: Commands,
 ) {
@@ -125,18 +120,9 @@ fn load_step_models(
         if let Some(step_asset) = step_assets.get(&loader.handle) {
             println!("✅ Loaded STEP model: {}", loader.name);
             
-            // Create material for this STEP model
-            let material = materials.add(StandardMaterial {
-                base_color: loader.colour,
-                metallic: 0.1,
-                perceptual_roughness: 0.5,
-                ..default()
-            });
-
-            // Spawn the STEP model with unit scaling and significant spacing
+            // Spawn the STEP model's scene, which brings the file's own colours with it
             commands.spawn((
-                Mesh3d(meshes.add(step_asset.mesh.clone())),
-                MeshMaterial3d(material),
+                SceneRoot(step_asset.scene.clone()),
                 Transform::from_translation(loader.position)
                     .with_scale(Vec3::splat(0.3)), // Much smaller scale to make models unit size
                 GlobalTransform::default(),
@@ -1,24 +1,25 @@
+//! Side-by-side comparison of the triangulation backends on the same STEP file.
+//!
+//! The asset server caches one asset per path, so a single file can't be loaded with four
+//! different settings through it. Instead the file is read once and handed to
+//! `triangulate_step_file` with a different `StepLoaderSettings` per quadrant.
+//!
+//! Run with `--features opencascade,meshopt` to fill in every quadrant.
 use bevy::prelude::*;
-use bevy_step_loader::{StepAsset, StepPlugin};
+use bevy_step_loader::{StepBounds, StepLoaderSettings, TriangulationBackend, simplify_mesh, triangulate_step_file};
+
+const STEP_FILE: &str = "assets/22604_bcab4db9_0001_2.step";
 
 fn main() {
     App::new()
-        .add_plugins((
-            DefaultPlugins.set(ImagePlugin::default_nearest()),
-            StepPlugin,
-        ))
+        .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
         .insert_resource(CameraState::default())
         .insert_resource(ModelPositions::default())
-        .add_systems(Startup, (setup_scene, setup_ui))
-        .add_systems(Update, (check_step_loaded, rotate_models, update_statistics, camera_control_system))
+        .add_systems(Startup, (setup_scene, setup_ui, spawn_models))
+        .add_systems(Update, (rotate_models, update_statistics, camera_control_system))
         .run();
 }
 
-#[derive(Resource)]
-struct StepModel {
-    handle: Handle<StepAsset>,
-}
-
 #[derive(Resource, Default)]
 struct ModelPositions {
     positions: Vec<Vec3>,
@@ -41,29 +42,88 @@ impl Default for CameraState {
     }
 }
 
-#[derive(Component)]
-struct QuadrantLabel;
+/// One of the four configurations being compared.
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+enum Quadrant {
+    Foxtrot,
+    Occt,
+    FoxtrotSimplified,
+    OcctSimplified,
+}
 
-#[derive(Component)]
-struct FoxtrotStatsDisplay;
+impl Quadrant {
+    const ALL: [Quadrant; 4] = [
+        Quadrant::Foxtrot,
+        Quadrant::Occt,
+        Quadrant::FoxtrotSimplified,
+        Quadrant::OcctSimplified,
+    ];
+
+    fn backend(self) -> TriangulationBackend {
+        match self {
+            Quadrant::Foxtrot | Quadrant::FoxtrotSimplified => TriangulationBackend::Foxtrot,
+            Quadrant::Occt | Quadrant::OcctSimplified => TriangulationBackend::Occt,
+        }
+    }
 
-#[derive(Component)]
-struct OpenCascadeStatsDisplay;
+    /// Target ratio for meshopt decimation, if this quadrant is simplified.
+    fn simplify_ratio(self) -> Option<f32> {
+        match self {
+            Quadrant::FoxtrotSimplified => Some(0.5),
+            Quadrant::OcctSimplified => Some(0.3),
+            _ => None,
+        }
+    }
 
-#[derive(Component)]
-struct FoxtrotSimplifiedStatsDisplay;
+    fn label(self) -> &'static str {
+        match self {
+            Quadrant::Foxtrot | Quadrant::FoxtrotSimplified => "Foxtrot",
+            Quadrant::Occt | Quadrant::OcctSimplified => "OCCT",
+        }
+    }
+
+    fn colour(self) -> Color {
+        match self {
+            Quadrant::Foxtrot => Color::srgb(0.9, 0.5, 0.5), // Light red
+            Quadrant::Occt => Color::srgb(0.5, 0.5, 0.9), // Light blue
+            Quadrant::FoxtrotSimplified => Color::srgb(0.5, 0.9, 0.5), // Light green
+            Quadrant::OcctSimplified => Color::srgb(0.9, 0.9, 0.5), // Light yellow
+        }
+    }
+
+    fn position(self, spacing: f32) -> Vec3 {
+        match self {
+            Quadrant::Foxtrot => Vec3::new(-spacing, spacing, 0.0),
+            Quadrant::Occt => Vec3::new(spacing, spacing, 0.0),
+            Quadrant::FoxtrotSimplified => Vec3::new(-spacing, -spacing, 0.0),
+            Quadrant::OcctSimplified => Vec3::new(spacing, -spacing, 0.0),
+        }
+    }
+
+    /// Screen anchor for this quadrant's UI, offset `inset` pixels from the corner.
+    fn node(self, inset: f32) -> Node {
+        let mut node = Node {
+            position_type: PositionType::Absolute,
+            ..default()
+        };
+        match self {
+            Quadrant::Foxtrot => (node.top, node.left) = (px(inset), px(10)),
+            Quadrant::Occt => (node.top, node.right) = (px(inset), px(10)),
+            Quadrant::FoxtrotSimplified => (node.bottom, node.left) = (px(inset), px(10)),
+            Quadrant::OcctSimplified => (node.bottom, node.right) = (px(inset), px(10)),
+        }
+        node
+    }
+}
 
 #[derive(Component)]
-struct OpenCascadeSimplifiedStatsDisplay;
+struct QuadrantLabel;
 
-fn setup_scene(
-    mut commands: Commands,
-    asset_server: Res<AssetServer>,
-) {
-    // Load the STEP file
-    let step_handle = asset_server.load("22604_bcab4db9_0001_2.step");
-    commands.insert_resource(StepModel { handle: step_handle });
+/// Marks the stats text for a quadrant.
+#[derive(Component)]
+struct StatsDisplay(Quadrant);
 
+fn setup_scene(mut commands: Commands) {
     commands.spawn((
         Camera3d::default(),
         Projection::Orthographic(OrthographicProjection {
@@ -106,7 +166,7 @@ fn setup_scene(
     });
 }
 
-fn setup_ui(mut commands: Commands, _asset_server: Res<AssetServer>) {
+fn setup_ui(mut commands: Commands) {
     // UI camera
     commands.spawn((
         Camera2d,
@@ -116,393 +176,122 @@ fn setup_ui(mut commands: Commands, _asset_server: Res<AssetServer>) {
         },
     ));
 
-    // Labels for each quadrant with smaller font size
-    commands.spawn((
-        Text::new("Foxtrot"),
-        TextFont {
-            font_size: 16.0,
-            ..default()
-        },
-        TextColor(Color::srgb(0.9, 0.5, 0.5)),
-        Node {
-            position_type: PositionType::Absolute,
-            top: px(10),
-            left: px(10),
-            ..default()
-        },
-        QuadrantLabel,
-    ));
-
-    commands.spawn((
-        Text::new("OCCT"),
-        TextFont {
-            font_size: 16.0,
-            ..default()
-        },
-        TextColor(Color::srgb(0.5, 0.5, 0.9)),
-        Node {
-            position_type: PositionType::Absolute,
-            top: px(10),
-            right: px(10),
-            ..default()
-        },
-        QuadrantLabel,
-    ));
-
-    commands.spawn((
-        Text::new("Foxtrot"),
-        TextFont {
-            font_size: 16.0,
-            ..default()
-        },
-        TextColor(Color::srgb(0.5, 0.9, 0.5)),
-        Node {
-            position_type: PositionType::Absolute,
-            bottom: px(10),
-            left: px(10),
-            ..default()
-        },
-        QuadrantLabel,
-    ));
-
-    commands.spawn((
-        Text::new("OCCT"),
-        TextFont {
-            font_size: 16.0,
-            ..default()
-        },
-        TextColor(Color::srgb(0.9, 0.9, 0.5)),
-        Node {
-            position_type: PositionType::Absolute,
-            bottom: px(10),
-            right: px(10),
-            ..default()
-        },
-        QuadrantLabel,
-    ));
-
-    commands.spawn((
-        Text::new("verts: 0\ntris: 0\nedges: 0\nbytes: 0"),
-        TextFont {
-            font_size: 14.0,
-            ..default()
-        },
-        TextColor(Color::srgb(0.9, 0.5, 0.5)),
-        Node {
-            position_type: PositionType::Absolute,
-            top: px(50),
-            left: px(10),
-            ..default()
-        },
-        FoxtrotStatsDisplay,
-    ));
-
-    commands.spawn((
-        Text::new("verts: 0\ntris: 0\nedges: 0\nbytes: 0"),
-        TextFont {
-            font_size: 14.0,
-            ..default()
-        },
-        TextColor(Color::srgb(0.5, 0.5, 0.9)),
-        Node {
-            position_type: PositionType::Absolute,
-            top: px(50),
-            right: px(10),
-            ..default()
-        },
-        OpenCascadeStatsDisplay,
-    ));
-
-    commands.spawn((
-        Text::new("verts: 0\ntris: 0\nedges: 0\nbytes: 0\nw/meshopt"),
-        TextFont {
-            font_size: 14.0,
-            ..default()
-        },
-        TextColor(Color::srgb(0.5, 0.9, 0.5)),
-        Node {
-            position_type: PositionType::Absolute,
-            bottom: px(50),
-            left: px(10),
-            ..default()
-        },
-        FoxtrotSimplifiedStatsDisplay,
-    ));
-
-    commands.spawn((
-        Text::new("verts: 0\ntris: 0\nedges: 0\nbytes: 0\nw/meshopt"),
-        TextFont {
-            font_size: 14.0,
-            ..default()
-        },
-        TextColor(Color::srgb(0.9, 0.9, 0.5)),
-        Node {
-            position_type: PositionType::Absolute,
-            bottom: px(50),
-            right: px(10),
-            ..default()
-        },
-        OpenCascadeSimplifiedStatsDisplay,
-    ));
+    for quadrant in Quadrant::ALL {
+        // Labels for each quadrant with smaller font size
+        commands.spawn((
+            Text::new(quadrant.label()),
+            TextFont {
+                font_size: 16.0,
+                ..default()
+            },
+            TextColor(quadrant.colour()),
+            quadrant.node(10.0),
+            QuadrantLabel,
+        ));
+
+        commands.spawn((
+            Text::new("loading..."),
+            TextFont {
+                font_size: 14.0,
+                ..default()
+            },
+            TextColor(quadrant.colour()),
+            quadrant.node(50.0),
+            StatsDisplay(quadrant),
+        ));
+    }
 }
 
-fn check_step_loaded(
+fn spawn_models(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut model_positions: ResMut<ModelPositions>,
-    step_assets: Res<Assets<StepAsset>>,
-    step_model: Option<Res<StepModel>>,
 ) {
-    if let Some(step_model) = step_model {
-        if let Some(step_asset) = step_assets.get(&step_model.handle) {
-            println!("✅ STEP file loaded successfully!");
-            
-            let foxtrot_material = materials.add(StandardMaterial {
-                base_color: Color::srgb(0.9, 0.5, 0.5), // Light red
-                metallic: 0.2,
-                perceptual_roughness: 0.4,
-                ..default()
-            });
-            
-            let occt_material = materials.add(StandardMaterial {
-                base_color: Color::srgb(0.5, 0.5, 0.9), // Light blue
-                metallic: 0.2,
-                perceptual_roughness: 0.4,
-                ..default()
-            });
-            
-            let foxtrot_simplified_material = materials.add(StandardMaterial {
-                base_color: Color::srgb(0.5, 0.9, 0.5), // Light green
-                metallic: 0.2,
-                perceptual_roughness: 0.4,
-                ..default()
-            });
-            
-            let occt_simplified_material = materials.add(StandardMaterial {
-                base_color: Color::srgb(0.9, 0.9, 0.5), // Light yellow
-                metallic: 0.2,
-                perceptual_roughness: 0.4,
-                ..default()
-            });
-
-            // Scale models to unit size space them out..
-            let scale_factor = 4.0;
-            let spacing = 300.0;
-
-            model_positions.positions.clear();
-
-            // Top-left (Foxtrot): -spacing, spacing
-            let foxtrot_pos = Vec3::new(-spacing, spacing, 0.0);
-            model_positions.positions.push(foxtrot_pos);
-            commands.spawn((
-                Mesh3d(meshes.add(step_asset.mesh.clone())),
-                MeshMaterial3d(foxtrot_material.clone()),
-                Transform::from_xyz(foxtrot_pos.x, foxtrot_pos.y, foxtrot_pos.z)
-                    .with_scale(Vec3::splat(scale_factor)),
-                GlobalTransform::default(),
-                RotatingModel,
-                FoxtrotModel,
-                ModelMetadata {
-                    vertices: get_vertex_count(&step_asset.mesh),
-                    triangles: get_triangle_count(&step_asset.mesh),
-                    edges: get_triangle_count(&step_asset.mesh) * 3,
-                    bytes: calculate_mesh_size_bytes(&step_asset.mesh),
-                },
-            ));
-
-            // Top-right (OpenCASCADE): spacing, spacing  
-            let occt_pos = Vec3::new(spacing, spacing, 0.0);
-            model_positions.positions.push(occt_pos);
-            commands.spawn((
-                Mesh3d(meshes.add(step_asset.mesh.clone())),
-                MeshMaterial3d(occt_material.clone()),
-                Transform::from_xyz(occt_pos.x, occt_pos.y, occt_pos.z)
-                    .with_scale(Vec3::splat(scale_factor)),
-                GlobalTransform::default(),
-                RotatingModel,
-                OpenCascadeModel,
-                ModelMetadata {
-                    vertices: get_vertex_count(&step_asset.mesh),
-                    triangles: get_triangle_count(&step_asset.mesh),
-                    edges: get_triangle_count(&step_asset.mesh) * 3,
-                    bytes: calculate_mesh_size_bytes(&step_asset.mesh),
-                },
-            ));
-
-            // Bottom-left (Foxtrot + Simplification): -spacing, -spacing
-            let foxtrot_simplified_pos = Vec3::new(-spacing, -spacing, 0.0);
-            model_positions.positions.push(foxtrot_simplified_pos);
-            #[cfg(feature = "meshopt")]
-            {
-                let mut simplified_asset = step_asset.clone();
-                if simplified_asset.simplify_mesh(0.5, 0.01).is_ok() {
-                    let vertices = get_vertex_count(&simplified_asset.mesh);
-                    let triangles = get_triangle_count(&simplified_asset.mesh);
-                    let edges = triangles * 3; 
-                    
-                    let mesh_bytes = calculate_mesh_size_bytes(&simplified_asset.mesh);
-                    commands.spawn((
-                        Mesh3d(meshes.add(simplified_asset.mesh)),
-                        MeshMaterial3d(foxtrot_simplified_material.clone()),
-                        Transform::from_xyz(foxtrot_simplified_pos.x, foxtrot_simplified_pos.y, foxtrot_simplified_pos.z)
-                            .with_scale(Vec3::splat(scale_factor)),
-                        GlobalTransform::default(),
-                        RotatingModel,
-                        FoxtrotSimplifiedModel,
-                        ModelMetadata { 
-                            vertices, 
-                            triangles, 
-                            edges, 
-                            bytes: mesh_bytes
-                        },
-                    ));
-                } else {
-                    // Fallback to original mesh if simplification fails
-                    let vertices = get_vertex_count(&step_asset.mesh);
-                    let triangles = get_triangle_count(&step_asset.mesh);
-                    let edges = triangles * 3;
-                    
-                    commands.spawn((
-                        Mesh3d(meshes.add(step_asset.mesh.clone())),
-                        MeshMaterial3d(foxtrot_simplified_material.clone()),
-                        Transform::from_xyz(foxtrot_simplified_pos.x, foxtrot_simplified_pos.y, foxtrot_simplified_pos.z)
-                            .with_scale(Vec3::splat(scale_factor)),
-                        GlobalTransform::default(),
-                        RotatingModel,
-                        FoxtrotSimplifiedModel,
-                        ModelMetadata { 
-                            vertices, 
-                            triangles, 
-                            edges, 
-                            bytes: calculate_mesh_size_bytes(&step_asset.mesh) 
-                        },
-                    ));
-                }
-            }
-            #[cfg(not(feature = "meshopt"))]
-            {
-                let vertices = get_vertex_count(&step_asset.mesh);
-                let triangles = get_triangle_count(&step_asset.mesh);
-                let edges = triangles * 3; // Approximation
-                
-                commands.spawn((
-                    Mesh3d(meshes.add(step_asset.mesh.clone())),
-                    MeshMaterial3d(foxtrot_simplified_material.clone()),
-                    Transform::from_xyz(foxtrot_simplified_pos.x, foxtrot_simplified_pos.y, foxtrot_simplified_pos.z)
-                        .with_scale(Vec3::splat(scale_factor)),
-                    GlobalTransform::default(),
-                    RotatingModel,
-                    FoxtrotSimplifiedModel,
-                    ModelMetadata { 
-                        vertices, 
-                        triangles, 
-                        edges, 
-                        bytes: calculate_mesh_size_bytes(&step_asset.mesh) 
-                    },
-                ));
+    let bytes = std::fs::read(STEP_FILE).expect("the example STEP file should be present");
+
+    // Models are scaled to unit size, then up to this, and spaced out
+    let model_size = 400.0;
+    let spacing = 300.0;
+
+    model_positions.positions.clear();
+
+    for quadrant in Quadrant::ALL {
+        let position = quadrant.position(spacing);
+        model_positions.positions.push(position);
+
+        let settings = StepLoaderSettings {
+            backend: quadrant.backend(),
+            ..default()
+        };
+
+        let mut mesh = match triangulate_step_file(&bytes, &settings) {
+            Ok(mesh) => mesh,
+            Err(e) => {
+                // Most likely the backend isn't compiled in, show why in place of the stats
+                println!("❌ {} failed: {}", quadrant.label(), e);
+                commands.spawn((ModelMetadata::Failed(e.to_string()), quadrant));
+                continue;
             }
+        };
 
-            // Bottom-right (OpenCASCADE + Simplification): spacing, -spacing
-            let occt_simplified_pos = Vec3::new(spacing, -spacing, 0.0);
-            model_positions.positions.push(occt_simplified_pos);
-            #[cfg(all(feature = "opencascade", feature = "meshopt"))]
-            {
-                let mut simplified_asset = step_asset.clone();
-                if simplified_asset.simplify_mesh(0.3, 0.01).is_ok() {
-                    let vertices = get_vertex_count(&simplified_asset.mesh);
-                    let triangles = get_triangle_count(&simplified_asset.mesh);
-                    let edges = triangles * 3;
-                    
-                    let mesh_bytes = calculate_mesh_size_bytes(&simplified_asset.mesh);
-                    commands.spawn((
-                        Mesh3d(meshes.add(simplified_asset.mesh)),
-                        MeshMaterial3d(occt_simplified_material.clone()),
-                        Transform::from_xyz(occt_simplified_pos.x, occt_simplified_pos.y, occt_simplified_pos.z)
-                            .with_scale(Vec3::splat(scale_factor)),
-                        GlobalTransform::default(),
-                        RotatingModel,
-                        OpenCascadeSimplifiedModel,
-                        ModelMetadata { 
-                            vertices, 
-                            triangles, 
-                            edges, 
-                            bytes: mesh_bytes
-                        },
-                    ));
-                } else {
-                    // Fallback to original mesh if simplification fails
-                    let vertices = get_vertex_count(&step_asset.mesh);
-                    let triangles = get_triangle_count(&step_asset.mesh);
-                    let edges = triangles * 3;
-                    
-                    commands.spawn((
-                        Mesh3d(meshes.add(step_asset.mesh.clone())),
-                        MeshMaterial3d(occt_simplified_material.clone()),
-                        Transform::from_xyz(occt_simplified_pos.x, occt_simplified_pos.y, occt_simplified_pos.z)
-                            .with_scale(Vec3::splat(scale_factor)),
-                        GlobalTransform::default(),
-                        RotatingModel,
-                        OpenCascadeSimplifiedModel,
-                        ModelMetadata { 
-                            vertices, 
-                            triangles, 
-                            edges, 
-                            bytes: calculate_mesh_size_bytes(&step_asset.mesh) 
-                        },
-                    ));
+        let mut simplified = false;
+        if let Some(ratio) = quadrant.simplify_ratio() {
+            match simplify_mesh(&mut mesh, ratio, 0.01) {
+                Ok(report) => {
+                    println!(
+                        "{}: {} -> {} vertices (error: {})",
+                        quadrant.label(),
+                        report.vertices_before,
+                        report.vertices_after,
+                        report.error
+                    );
+                    simplified = true;
                 }
+                Err(e) => println!("⚠️ {} not simplified: {}", quadrant.label(), e),
             }
-            #[cfg(not(all(feature = "opencascade", feature = "meshopt")))]
-            {
-                let vertices = get_vertex_count(&step_asset.mesh);
-                let triangles = get_triangle_count(&step_asset.mesh);
-                let edges = triangles * 3; // Approximation
-                
-                commands.spawn((
-                    Mesh3d(meshes.add(step_asset.mesh.clone())),
-                    MeshMaterial3d(occt_simplified_material.clone()),
-                    Transform::from_xyz(occt_simplified_pos.x, occt_simplified_pos.y, occt_simplified_pos.z)
-                        .with_scale(Vec3::splat(scale_factor)),
-                    GlobalTransform::default(),
-                    RotatingModel,
-                    OpenCascadeSimplifiedModel,
-                    ModelMetadata { 
-                        vertices, 
-                        triangles, 
-                        edges, 
-                        bytes: calculate_mesh_size_bytes(&step_asset.mesh) 
-                    },
-                ));
-            }
-
-            // Remove the resource to prevent re-execution
-            commands.remove_resource::<StepModel>();
         }
-    }
-}
-
-#[derive(Component)]
-struct RotatingModel;
 
-#[derive(Component)]
-struct FoxtrotModel;
+        let fit = StepBounds::from_mesh(&mesh).normalizing_transform();
+        let metadata = ModelMetadata::Loaded {
+            vertices: get_vertex_count(&mesh),
+            triangles: get_triangle_count(&mesh),
+            edges: get_triangle_count(&mesh) * 3, // Approximation
+            bytes: calculate_mesh_size_bytes(&mesh),
+            simplified,
+        };
 
-#[derive(Component)]
-struct OpenCascadeModel;
+        commands.spawn((
+            Mesh3d(meshes.add(mesh)),
+            MeshMaterial3d(materials.add(StandardMaterial {
+                base_color: quadrant.colour(),
+                metallic: 0.2,
+                perceptual_roughness: 0.4,
+                ..default()
+            })),
+            Transform::from_translation(position).with_scale(Vec3::splat(model_size)) * fit,
+            RotatingModel,
+            quadrant,
+            metadata,
+        ));
+    }
 
-#[derive(Component)]
-struct FoxtrotSimplifiedModel;
+    println!("✅ STEP file triangulated with every backend");
+}
 
 #[derive(Component)]
-struct OpenCascadeSimplifiedModel;
+struct RotatingModel;
 
 #[derive(Component)]
-struct ModelMetadata {
-    vertices: usize,
-    triangles: usize,
-    edges: usize,
-    bytes: usize,
+enum ModelMetadata {
+    Loaded {
+        vertices: usize,
+        triangles: usize,
+        edges: usize,
+        bytes: usize,
+        simplified: bool,
+    },
+    Failed(String),
 }
 
 fn rotate_models(
@@ -515,69 +304,29 @@ fn rotate_models(
 }
 
 fn update_statistics(
-    mut text_param_set: ParamSet<(
-        Query<&mut Text, With<FoxtrotStatsDisplay>>,
-        Query<&mut Text, With<OpenCascadeStatsDisplay>>,
-        Query<&mut Text, With<FoxtrotSimplifiedStatsDisplay>>,
-        Query<&mut Text, With<OpenCascadeSimplifiedStatsDisplay>>,
-    )>,
-    mut metadata_param_set: ParamSet<(
-        Query<&ModelMetadata, (With<FoxtrotModel>, Without<OpenCascadeModel>, Without<FoxtrotSimplifiedModel>, Without<OpenCascadeSimplifiedModel>)>,
-        Query<&ModelMetadata, (With<OpenCascadeModel>, Without<FoxtrotModel>, Without<FoxtrotSimplifiedModel>, Without<OpenCascadeSimplifiedModel>)>,
-        Query<&ModelMetadata, (With<FoxtrotSimplifiedModel>, Without<FoxtrotModel>, Without<OpenCascadeModel>, Without<OpenCascadeSimplifiedModel>)>,
-        Query<&ModelMetadata, (With<OpenCascadeSimplifiedModel>, Without<FoxtrotModel>, Without<OpenCascadeModel>, Without<FoxtrotSimplifiedModel>)>
-    )>
+    mut displays: Query<(&mut Text, &StatsDisplay)>,
+    models: Query<(&ModelMetadata, &Quadrant), Changed<ModelMetadata>>,
 ) {
-    // Update Foxtrot stats
-    if let Ok(mut text) = text_param_set.p0().single_mut() {
-        if let Ok(metadata) = metadata_param_set.p0().single() {
-            **text = format!(
-                "verts: {}\ntris: {}\nedges: {}\nbytes: {}",
-                metadata.vertices, metadata.triangles, metadata.edges, metadata.bytes
-            );
-        }
-    }
-
-    // Update OpenCASCADE stats
-    if let Ok(mut text) = text_param_set.p1().single_mut() {
-        if let Ok(metadata) = metadata_param_set.p1().single() {
-            **text = format!(
-                "verts: {}\ntris: {}\nedges: {}\nbytes: {}",
-                metadata.vertices, metadata.triangles, metadata.edges, metadata.bytes
-            );
-        }
-    }
-
-    // Update Foxtrot + Simplified stats
-    if let Ok(mut text) = text_param_set.p2().single_mut() {
-        if let Ok(metadata) = metadata_param_set.p2().single() {
-            **text = format!(
-                "verts: {}\ntris: {}\nedges: {}\nbytes: {}\nw/meshopt",
-                metadata.vertices, metadata.triangles, metadata.edges, metadata.bytes
-            );
-        }
-    }
-
-    // Update OpenCASCADE + Simplified stats
-    if let Ok(mut text) = text_param_set.p3().single_mut() {
-        if let Ok(metadata) = metadata_param_set.p3().single() {
-            **text = format!(
-                "verts: {}\ntris: {}\nedges: {}\nbytes: {}\nw/meshopt",
-                metadata.vertices, metadata.triangles, metadata.edges, metadata.bytes
-            );
-        }
+    for (metadata, quadrant) in &models {
+        let Some((mut text, _)) = displays.iter_mut().find(|(_, d)| d.0 == *quadrant) else {
+            continue;
+        };
+        **text = match metadata {
+            ModelMetadata::Loaded { vertices, triangles, edges, bytes, simplified } => format!(
+                "verts: {}\ntris: {}\nedges: {}\nbytes: {}{}",
+                vertices,
+                triangles,
+                edges,
+                bytes,
+                if *simplified { "\nw/meshopt" } else { "" }
+            ),
+            ModelMetadata::Failed(e) => format!("unavailable:\n{}", e),
+        };
     }
 }
 
 fn get_vertex_count(mesh: &Mesh) -> usize {
-    if let Some(positions) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
-        match positions {
-            bevy_mesh::VertexAttributeValues::Float32x3(pos) => pos.len(),
-            _ => 0,
-        }
-    } else {
-        0
-    }
+    mesh.count_vertices()
 }
 
 fn get_triangle_count(mesh: &Mesh) -> usize {
@@ -733,15 +482,15 @@ fn camera_control_system(
     }
     
     // Update the camera's orthographic projection based on zoom level
-    if let Ok((mut projection, mut transform)) = query.single_mut() {
-        if let Projection::Orthographic(ref mut ortho) = *projection {
-            // Adjust the scale of the orthographic projection based on zoom
-            ortho.scale = 4.5 / camera_state.zoom; // Invert zoom so that scroll in = zoom in
-            
-            // Apply translation based on camera state
-            transform.translation.x = camera_state.translation.x;
-            transform.translation.y = camera_state.translation.y;
-        }
+    if let Ok((mut projection, mut transform)) = query.single_mut()
+        && let Projection::Orthographic(ref mut ortho) = *projection
+    {
+        // Adjust the scale of the orthographic projection based on zoom
+        ortho.scale = 4.5 / camera_state.zoom; // Invert zoom so that scroll in = zoom in
+        
+        // Apply translation based on camera state
+        transform.translation.x = camera_state.translation.x;
+        transform.translation.y = camera_state.translation.y;
     }
     
     // Check if zoom or translation changed (after they have been processed)
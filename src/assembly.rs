@@ -0,0 +1,283 @@
+//! The product structure of a STEP file: which products exist, how assemblies place their
+//! components, and which solids belong to which product.
+//!
+//! The chain we follow is the AP203/AP214 one:
+//!
+//! ```text
+//! PRODUCT <- PRODUCT_DEFINITION_FORMATION <- PRODUCT_DEFINITION
+//! PRODUCT_DEFINITION <- PRODUCT_DEFINITION_SHAPE <- SHAPE_DEFINITION_REPRESENTATION -> SHAPE_REPRESENTATION
+//! PRODUCT_DEFINITION (parent) <- NEXT_ASSEMBLY_USAGE_OCCURRENCE -> PRODUCT_DEFINITION (child)
+//! NEXT_ASSEMBLY_USAGE_OCCURRENCE <- PRODUCT_DEFINITION_SHAPE <- CONTEXT_DEPENDENT_SHAPE_REPRESENTATION
+//!     -> (REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION -> ITEM_DEFINED_TRANSFORMATION)
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use bevy_math::DAffine3;
+
+use crate::brep::{Brep, placement};
+use crate::part21::StepData;
+
+/// Deeper than any real assembly, this only guards against cycles in broken files.
+const MAX_DEPTH: usize = 64;
+
+/// One placed occurrence of a product. A product used twice in an assembly gives two nodes.
+#[derive(Debug, Clone)]
+pub(crate) struct Node {
+    pub name: String,
+    /// The `PRODUCT_DEFINITION` this is an occurrence of, `None` for the node holding solids
+    /// no product claims.
+    pub definition: Option<u64>,
+    pub parent: Option<usize>,
+    /// Placement relative to the parent node, in file units.
+    pub local: DAffine3,
+    /// Placement relative to the file's origin, in file units.
+    pub world: DAffine3,
+    /// Solids in this product's own shape, not its components'.
+    pub solids: Vec<u64>,
+}
+
+/// The assembly tree, flattened so that parents always come before their children.
+#[derive(Debug, Default)]
+pub(crate) struct Assembly {
+    pub nodes: Vec<Node>,
+}
+
+impl Assembly {
+    pub(crate) fn read(data: &StepData, brep: &Brep) -> Assembly {
+        let reader = Reader::new(data, brep);
+        let mut assembly = Assembly::default();
+
+        let children: HashSet<u64> = reader.usages.values().flatten().map(|u| u.child).collect();
+        let mut roots: Vec<u64> = data
+            .all("PRODUCT_DEFINITION")
+            .map(|(id, _)| id)
+            .filter(|id| !children.contains(id))
+            .collect();
+        roots.sort_unstable();
+
+        for root in roots {
+            reader.add_node(&mut assembly, root, None, DAffine3::IDENTITY, 0);
+        }
+
+        // Solids no product claims (files with no product structure, or a broken one) still
+        // need a home, put them at the origin under the first root
+        let placed: HashSet<u64> = assembly.nodes.iter().flat_map(|n| n.solids.iter().copied()).collect();
+        let mut orphans: Vec<u64> = brep.solids.keys().filter(|id| !placed.contains(id)).copied().collect();
+        orphans.sort_unstable();
+        if !orphans.is_empty() {
+            match assembly.nodes.iter_mut().find(|n| n.parent.is_none()) {
+                Some(root) => root.solids.extend(orphans),
+                None => assembly.nodes.push(Node {
+                    name: String::new(),
+                    definition: None,
+                    parent: None,
+                    local: DAffine3::IDENTITY,
+                    world: DAffine3::IDENTITY,
+                    solids: orphans,
+                }),
+            }
+        }
+
+        assembly
+    }
+}
+
+/// A `NEXT_ASSEMBLY_USAGE_OCCURRENCE` and the placement of its child in the parent.
+#[derive(Debug, Clone, Copy)]
+struct Usage {
+    child: u64,
+    local: DAffine3,
+}
+
+struct Reader<'a> {
+    data: &'a StepData,
+    brep: &'a Brep,
+    /// Product definition -> shape representations defining it.
+    shapes: HashMap<u64, Vec<u64>>,
+    /// Representation -> representations related to it without a transform.
+    related: HashMap<u64, Vec<u64>>,
+    /// Parent product definition -> its components.
+    usages: HashMap<u64, Vec<Usage>>,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a StepData, brep: &'a Brep) -> Reader<'a> {
+        // PRODUCT_DEFINITION_SHAPE -> what it defines (a product definition or an assembly usage)
+        let definitions: HashMap<u64, u64> = data
+            .all("PRODUCT_DEFINITION_SHAPE")
+            .filter_map(|(id, r)| Some((id, r.param(2).as_id()?)))
+            .collect();
+
+        let mut shapes: HashMap<u64, Vec<u64>> = HashMap::new();
+        for (_, sdr) in data.all("SHAPE_DEFINITION_REPRESENTATION") {
+            if let (Some(pd), Some(rep)) = (
+                sdr.param(0).as_id().and_then(|pds| definitions.get(&pds)),
+                sdr.param(1).as_id(),
+            ) {
+                shapes.entry(*pd).or_default().push(rep);
+            }
+        }
+
+        let mut related: HashMap<u64, Vec<u64>> = HashMap::new();
+        for (id, rr) in data.all("REPRESENTATION_RELATIONSHIP") {
+            if data.get(id).is_some_and(|i| i.is("REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION")) {
+                continue;
+            }
+            if let (Some(a), Some(b)) = (rr.param(2).as_id(), rr.param(3).as_id()) {
+                related.entry(a).or_default().push(b);
+                related.entry(b).or_default().push(a);
+            }
+        }
+        // The simple form, SHAPE_REPRESENTATION_RELATIONSHIP('name','desc',#a,#b)
+        for (_, srr) in data.all("SHAPE_REPRESENTATION_RELATIONSHIP") {
+            if let (Some(a), Some(b)) = (srr.param(2).as_id(), srr.param(3).as_id()) {
+                related.entry(a).or_default().push(b);
+                related.entry(b).or_default().push(a);
+            }
+        }
+
+        // Assembly usage -> the representation relationship that places it
+        let mut placements: HashMap<u64, u64> = HashMap::new();
+        for (_, cdsr) in data.all("CONTEXT_DEPENDENT_SHAPE_REPRESENTATION") {
+            if let (Some(rel), Some(usage)) = (
+                cdsr.param(0).as_id(),
+                cdsr.param(1).as_id().and_then(|pds| definitions.get(&pds)),
+            ) {
+                placements.insert(*usage, rel);
+            }
+        }
+
+        let mut reader = Reader {
+            data,
+            brep,
+            shapes,
+            related,
+            usages: HashMap::new(),
+        };
+
+        let mut usages: HashMap<u64, Vec<Usage>> = HashMap::new();
+        let mut nauos: Vec<_> = data.all("NEXT_ASSEMBLY_USAGE_OCCURRENCE").collect();
+        nauos.sort_unstable_by_key(|(id, _)| *id);
+        for (id, nauo) in nauos {
+            let (Some(parent), Some(child)) = (nauo.param(3).as_id(), nauo.param(4).as_id()) else {
+                continue;
+            };
+            let local = placements
+                .get(&id)
+                .and_then(|rel| reader.relationship_transform(*rel, child))
+                .unwrap_or(DAffine3::IDENTITY);
+            usages.entry(parent).or_default().push(Usage { child, local });
+        }
+        reader.usages = usages;
+        reader
+    }
+
+    fn add_node(&self, assembly: &mut Assembly, pd: u64, parent: Option<usize>, local: DAffine3, depth: usize) {
+        if depth > MAX_DEPTH {
+            return;
+        }
+        let solids = self.solids_of(pd);
+        let components = self.usages.get(&pd).map(Vec::as_slice).unwrap_or_default();
+        // Product definitions with nothing to show, drawings and the like
+        if parent.is_none() && solids.is_empty() && components.is_empty() {
+            return;
+        }
+
+        let world = match parent {
+            Some(p) => assembly.nodes[p].world * local,
+            None => local,
+        };
+        let index = assembly.nodes.len();
+        assembly.nodes.push(Node {
+            name: self.product_name(pd),
+            definition: Some(pd),
+            parent,
+            local,
+            world,
+            solids,
+        });
+
+        for usage in components {
+            self.add_node(assembly, usage.child, Some(index), usage.local, depth + 1);
+        }
+    }
+
+    /// The solids in a product definition's own shape representations, following plain
+    /// (untransformed) representation relationships but not into another product's shape.
+    fn solids_of(&self, pd: u64) -> Vec<u64> {
+        let owned_elsewhere: HashSet<u64> = self
+            .shapes
+            .iter()
+            .filter(|(other, _)| **other != pd)
+            .flat_map(|(_, reps)| reps.iter().copied())
+            .collect();
+
+        let mut seen = HashSet::new();
+        let mut todo: Vec<u64> = self.shapes.get(&pd).cloned().unwrap_or_default();
+        let mut solids = Vec::new();
+        while let Some(rep) = todo.pop() {
+            if !seen.insert(rep) {
+                continue;
+            }
+            if let Some(items) = self.data.get(rep).and_then(|i| i.records.iter().find(|r| r.keyword.ends_with("REPRESENTATION"))) {
+                solids.extend(items.param(1).refs().filter(|item| self.brep.solids.contains_key(item)));
+            }
+            for next in self.related.get(&rep).into_iter().flatten() {
+                if !owned_elsewhere.contains(next) {
+                    todo.push(*next);
+                }
+            }
+        }
+        solids.sort_unstable();
+        solids.dedup();
+        solids
+    }
+
+    fn product_name(&self, pd: u64) -> String {
+        let product = self
+            .data
+            .get(pd)
+            .and_then(|i| i.records.first())
+            .and_then(|r| r.param(2).as_id())
+            .and_then(|formation| self.data.get(formation)?.records.first()?.param(2).as_id())
+            .and_then(|product| self.data.record(product, "PRODUCT"));
+        match product {
+            Some(p) => {
+                let name = p.param(1).as_str().unwrap_or_default();
+                let id = p.param(0).as_str().unwrap_or_default();
+                if name.is_empty() { id } else { name }.to_string()
+            }
+            None => String::new(),
+        }
+    }
+
+    /// The transform placing `child` in its parent, from a representation relationship with
+    /// an `ITEM_DEFINED_TRANSFORMATION`.
+    fn relationship_transform(&self, rel: u64, child: u64) -> Option<DAffine3> {
+        let inst = self.data.get(rel)?;
+        let rr = inst.record("REPRESENTATION_RELATIONSHIP");
+        let rrwt = inst.record("REPRESENTATION_RELATIONSHIP_WITH_TRANSFORMATION")?;
+        // Complex instances give the transformation its own record, the simple form has it last
+        let idt = self.data.record(rrwt.params.last()?.as_id()?, "ITEM_DEFINED_TRANSFORMATION")?;
+        let item_1 = placement(self.data, idt.param(2).as_id()?)?;
+        let item_2 = placement(self.data, idt.param(3).as_id()?)?;
+
+        // rep_1 should be the child's shape, but exporters don't all agree, so check
+        let rep_1 = rr.unwrap_or(rrwt).param(2).as_id();
+        let child_is_rep_2 = match (rep_1, self.shapes.get(&child)) {
+            (Some(rep_1), Some(child_reps)) => !self.connected(child_reps, rep_1),
+            _ => false,
+        };
+        Some(if child_is_rep_2 {
+            item_1 * item_2.inverse()
+        } else {
+            item_2 * item_1.inverse()
+        })
+    }
+
+    /// Whether `rep` is one of `reps` or linked to one of them without a transform.
+    fn connected(&self, reps: &[u64], rep: u64) -> bool {
+        reps.contains(&rep) || reps.iter().any(|r| self.related.get(r).is_some_and(|rel| rel.contains(&rep)))
+    }
+}
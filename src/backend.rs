@@ -0,0 +1,591 @@
+use bevy_asset::RenderAssetUsages;
+use bevy_platform::time::Instant;
+use bevy_math::{DAffine3, DVec3, Mat3, Vec3};
+use bevy_mesh::{Indices, Mesh};
+use wgpu_types::PrimitiveTopology;
+
+#[cfg(feature = "meshopt")]
+use crate::MeshoptPasses;
+use crate::assembly::Assembly;
+use crate::brep::Brep;
+use crate::{FoxtrotStats, NormalMode, StepLoadReport, StepLoaderError, StepLoaderSettings, TriangulationBackend};
+
+/// Triangulate the STEP file data into a Bevy Mesh, using the backend picked in `settings`.
+///
+/// This is what [`StepLoader`](crate::StepLoader) runs, exposed so you can triangulate bytes
+/// you already have in hand, or compare backends on the same file.
+///
+/// The `opencascade` backend builds OpenCASCADE through its wrapper crate, which needs CMake and
+/// a C++ toolchain, but it is considerably more robust than Foxtrot and handles a wider range of
+/// real-world STEP files and their quirks.
+pub fn triangulate_step_file(step_data: &[u8], settings: &StepLoaderSettings) -> Result<Mesh, StepLoaderError> {
+    let (mut raw, _) = triangulate(step_data, settings)?;
+    // The tolerances, surface normals and UVs all need the faces under the triangles, which
+    // means reading the rest of the file ourselves
+    let data = crate::part21::parse(step_data).unwrap_or_default();
+    let brep = Brep::read(&data);
+    let assembly = Assembly::read(&data, &brep);
+    let mut hits = crate::segment::classify(&raw, &brep, &assembly);
+    crate::refine::refine(&mut raw, &mut hits, &brep, &assembly, settings);
+    if settings.normals == NormalMode::Surface {
+        crate::normals::surface_normals(&mut raw, &hits, &brep, &assembly);
+    }
+    if settings.uv_scale.is_some() {
+        crate::uv::surface_uvs(&mut raw, &hits, &brep, &assembly);
+    }
+    let length_unit = crate::units::length_unit(&data);
+    build_mesh(raw, &crate::units::rescaled(settings, length_unit)?)
+}
+
+/// Triangles straight out of a backend, in file units and before any of the settings that
+/// shape the final mesh have been applied.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct RawMesh {
+    pub positions: Vec<[f32; 3]>,
+    pub indices: Vec<u32>,
+    /// Linear RGBA per vertex, empty when the file has no colours.
+    pub colors: Vec<[f32; 4]>,
+    /// Unit normals per vertex, empty when there are none. The backends take theirs from the
+    /// surfaces they tessellate, [`normals::surface_normals`](crate::normals::surface_normals)
+    /// replaces them with our own where it can.
+    pub normals: Vec<[f32; 3]>,
+    /// Texture coordinates per vertex, in file units along the surface, empty when there are
+    /// none. OCCT gives the raw surface parameters of its faces, which [`uv::surface_uvs`](crate::uv::surface_uvs)
+    /// turns into lengths.
+    pub uvs: Vec<[f32; 2]>,
+    /// Per vertex indices into [`StepAsset::faces`](crate::StepAsset::faces), empty when the
+    /// faces aren't known, see [`picking`](crate::picking).
+    pub faces: Vec<u32>,
+}
+
+impl RawMesh {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// A new mesh holding just `triangles`, with only the vertices they use, moved by `transform`.
+    pub(crate) fn subset(&self, triangles: impl IntoIterator<Item = usize>, transform: &DAffine3) -> RawMesh {
+        let mut remap = vec![u32::MAX; self.positions.len()];
+        let mut out = RawMesh::default();
+        for t in triangles {
+            for &v in &self.indices[t * 3..t * 3 + 3] {
+                let slot = &mut remap[v as usize];
+                if *slot == u32::MAX {
+                    *slot = out.positions.len() as u32;
+                    let p = DVec3::from(self.positions[v as usize].map(f64::from));
+                    out.positions.push(transform.transform_point3(p).as_vec3().to_array());
+                    if let Some(color) = self.colors.get(v as usize) {
+                        out.colors.push(*color);
+                    }
+                    if let Some(normal) = self.normals.get(v as usize) {
+                        let n = transform.transform_vector3(DVec3::from(normal.map(f64::from)));
+                        out.normals.push(n.normalize_or_zero().as_vec3().to_array());
+                    }
+                    if let Some(uv) = self.uvs.get(v as usize) {
+                        out.uvs.push(*uv);
+                    }
+                    if let Some(face) = self.faces.get(v as usize) {
+                        out.faces.push(*face);
+                    }
+                }
+                out.indices.push(*slot);
+            }
+        }
+        out
+    }
+
+    /// Adds a copy of vertex `v`, with all its attributes, and returns its index.
+    pub(crate) fn duplicate_vertex(&mut self, v: u32) -> u32 {
+        let i = v as usize;
+        self.positions.push(self.positions[i]);
+        if let Some(color) = self.colors.get(i).copied() {
+            self.colors.push(color);
+        }
+        if let Some(normal) = self.normals.get(i).copied() {
+            self.normals.push(normal);
+        }
+        if let Some(uv) = self.uvs.get(i).copied() {
+            self.uvs.push(uv);
+        }
+        if let Some(face) = self.faces.get(i).copied() {
+            self.faces.push(face);
+        }
+        (self.positions.len() - 1) as u32
+    }
+
+    /// Adds a vertex at `position` between `a` and `b`, with their attributes averaged, and
+    /// returns its index.
+    pub(crate) fn midpoint_vertex(&mut self, a: u32, b: u32, position: [f32; 3]) -> u32 {
+        let (a, b) = (a as usize, b as usize);
+        let mid = |x: &[f32], y: &[f32], out: &mut [f32]| {
+            for ((o, x), y) in out.iter_mut().zip(x).zip(y) {
+                *o = (x + y) / 2.0;
+            }
+        };
+        self.positions.push(position);
+        if let (Some(x), Some(y)) = (self.colors.get(a), self.colors.get(b)) {
+            let mut color = [0.0; 4];
+            mid(x, y, &mut color);
+            self.colors.push(color);
+        }
+        if let (Some(x), Some(y)) = (self.normals.get(a), self.normals.get(b)) {
+            let mut normal = [0.0; 3];
+            mid(x, y, &mut normal);
+            self.normals.push(Vec3::from(normal).normalize_or_zero().to_array());
+        }
+        if let (Some(x), Some(y)) = (self.uvs.get(a), self.uvs.get(b)) {
+            let mut uv = [0.0; 2];
+            mid(x, y, &mut uv);
+            self.uvs.push(uv);
+        }
+        if let Some(face) = self.faces.get(a).copied() {
+            self.faces.push(face);
+        }
+        (self.positions.len() - 1) as u32
+    }
+}
+
+/// Runs the backend picked in `settings`, and reports which one actually produced the triangles.
+pub(crate) fn triangulate(
+    step_data: &[u8],
+    settings: &StepLoaderSettings,
+) -> Result<(RawMesh, StepLoadReport), StepLoaderError> {
+    match settings.backend {
+        TriangulationBackend::Foxtrot => triangulate_with_foxtrot(step_data),
+        TriangulationBackend::Occt => triangulate_with_occt(step_data),
+        TriangulationBackend::FoxtrotThenOcct => {
+            let foxtrot = triangulate_with_foxtrot(step_data);
+            match foxtrot {
+                Ok((mesh, report)) if !mesh.is_empty() => Ok((mesh, report)),
+                // Without OCCT compiled in there's nothing to retry with, so hand back what Foxtrot gave us
+                _ if !cfg!(feature = "opencascade") => foxtrot,
+                _ => triangulate_with_occt(step_data).map(|(mesh, mut report)| {
+                    report.fell_back = true;
+                    (mesh, report)
+                }),
+            }
+        }
+    }
+}
+
+#[cfg(not(feature = "opencascade"))]
+fn triangulate_with_occt(_step_data: &[u8]) -> Result<(RawMesh, StepLoadReport), StepLoaderError> {
+    Err(StepLoaderError::BackendUnavailable(TriangulationBackend::Occt))
+}
+
+#[cfg(feature = "opencascade")]
+fn triangulate_with_occt(step_data: &[u8]) -> Result<(RawMesh, StepLoadReport), StepLoaderError> {
+    use opencascade::primitives::Shape;
+    use opencascade::mesh::Mesher;
+
+    let mut report = StepLoadReport::new(TriangulationBackend::Occt);
+    let start = Instant::now();
+
+    // OCCT can only read from a path, so give each load a file of its own. It's removed
+    // when `temp_file` drops, on the error paths too
+    let temp_file = TempStepFile::new(step_data)?;
+    let shape_to_mesh = Shape::read_step(&temp_file.path)
+        .map_err(|e| StepLoaderError::OcctError(format!("OCCT failed to read STEP file: {:?}", e)))?;
+    drop(temp_file);
+    report.parse_time = start.elapsed();
+
+    let start = Instant::now();
+    // opencascade 0.2 meshes at a fixed linear deflection of 0.01, the tolerances are met by
+    // refining afterwards
+    let occt_mesh = Mesher::new(&shape_to_mesh).mesh();
+    report.tessellate_time = start.elapsed();
+
+    let vertices: Vec<[f32; 3]> = occt_mesh
+        .vertices
+        .iter()
+        .map(|v| [v.x as f32, v.y as f32, v.z as f32])
+        .collect();
+
+    let indices: Vec<u32> = occt_mesh.indices.iter().map(|&i| i as u32).collect();
+
+    // OCCT's normals come from the surfaces, but only when it had them for every vertex
+    let normals: Vec<[f32; 3]> = if occt_mesh.normals.len() == vertices.len() {
+        occt_mesh.normals.iter().map(|n| [n.x as f32, n.y as f32, n.z as f32]).collect()
+    } else {
+        Vec::new()
+    };
+
+    // Likewise the UVs, which are each face's own surface parameters
+    let uvs: Vec<[f32; 2]> = if occt_mesh.uvs.len() == vertices.len() {
+        occt_mesh.uvs.iter().map(|uv| [uv.x as f32, uv.y as f32]).collect()
+    } else {
+        Vec::new()
+    };
+
+    let mesh = RawMesh {
+        positions: vertices,
+        indices,
+        colors: Vec::new(),
+        normals,
+        uvs,
+        faces: Vec::new(),
+    };
+    Ok((mesh, report))
+}
+
+/// A STEP file in the temp directory that's deleted on drop.
+#[cfg(feature = "opencascade")]
+struct TempStepFile {
+    path: std::path::PathBuf,
+}
+
+#[cfg(feature = "opencascade")]
+impl TempStepFile {
+    fn new(contents: &[u8]) -> std::io::Result<TempStepFile> {
+        use std::io::Write;
+        use std::sync::atomic::{AtomicU64, Ordering};
+
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.subsec_nanos());
+
+        // The counter keeps loads in this process apart, the pid and time keep processes apart,
+        // and `create_new` makes sure we never share a file with anything else regardless
+        loop {
+            let name = format!(
+                "bevy_step_loader-{}-{}-{}.step",
+                std::process::id(),
+                COUNTER.fetch_add(1, Ordering::Relaxed),
+                nanos
+            );
+            let path = std::env::temp_dir().join(name);
+            match std::fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let temp = TempStepFile { path };
+                    file.write_all(contents)?;
+                    return Ok(temp);
+                }
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[cfg(feature = "opencascade")]
+impl Drop for TempStepFile {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+fn triangulate_with_foxtrot(step_data: &[u8]) -> Result<(RawMesh, StepLoadReport), StepLoaderError> {
+    use step::step_file::StepFile;
+    use triangulate::triangulate::triangulate4 as triangulate;
+
+    // Foxtrot panics on some malformed input, turn that into an error so callers (and the fallback) can handle it
+    let ((triangulated_mesh, stats), parse_time, tessellate_time) = std::panic::catch_unwind(|| {
+        let start = Instant::now();
+        let flat = StepFile::strip_flatten(step_data);
+        let step = StepFile::parse(&flat);
+        let parse_time = start.elapsed();
+
+        let start = Instant::now();
+        (triangulate(&step), parse_time, start.elapsed())
+    })
+    .map_err(|_| StepLoaderError::BackendPanicked(TriangulationBackend::Foxtrot))?;
+
+    let mut report = StepLoadReport::new(TriangulationBackend::Foxtrot);
+    report.parse_time = parse_time;
+    report.tessellate_time = tessellate_time;
+    report.foxtrot = Some(FoxtrotStats {
+        shells: stats.num_shells,
+        faces: stats.num_faces,
+        errors: stats.num_errors,
+        panics: stats.num_panics,
+    });
+
+    let vertices: Vec<[f32; 3]> = triangulated_mesh
+        .verts
+        .iter()
+        .map(|v| [v.pos.x as f32, v.pos.y as f32, v.pos.z as f32])
+        .collect();
+
+    let normals: Vec<[f32; 3]> = triangulated_mesh
+        .verts
+        .iter()
+        .map(|v| [v.norm.x as f32, v.norm.y as f32, v.norm.z as f32])
+        .collect();
+
+    let indices: Vec<u32> = triangulated_mesh
+        .triangles
+        .iter()
+        .flat_map(|t| [t.verts.x, t.verts.y, t.verts.z])
+        .collect();
+
+    let mesh = RawMesh {
+        positions: vertices,
+        indices,
+        colors: Vec::new(),
+        normals,
+        uvs: Vec::new(),
+        faces: Vec::new(),
+    };
+    Ok((mesh, report))
+}
+
+/// Turn raw backend output into a Bevy Mesh, applying the backend-independent parts of `settings`.
+pub(crate) fn build_mesh(raw: RawMesh, settings: &StepLoaderSettings) -> Result<Mesh, StepLoaderError> {
+    let mut mesh = build_indexed_mesh(raw, settings)?;
+    add_normals(&mut mesh, settings)?;
+    Ok(mesh)
+}
+
+/// The first half of [`build_mesh`], everything up to the normals while the mesh is still
+/// indexed, for anything that wants to work on the triangles before they're finished.
+pub(crate) fn build_indexed_mesh(mut raw: RawMesh, settings: &StepLoaderSettings) -> Result<Mesh, StepLoaderError> {
+    let rotation = settings.up_axis.basis().as_mat3();
+    let basis = rotation * settings.scale;
+    if basis != Mat3::IDENTITY {
+        for v in &mut raw.positions {
+            *v = (basis * Vec3::from(*v)).to_array();
+        }
+    }
+    // The other modes compute their own once the mesh is built
+    if settings.normals != NormalMode::Surface || raw.normals.len() != raw.positions.len() {
+        raw.normals.clear();
+    }
+    if rotation != Mat3::IDENTITY {
+        for n in &mut raw.normals {
+            *n = (rotation * Vec3::from(*n)).to_array();
+        }
+    }
+    // UVs are lengths along the surface, OCCT's parameters included once `surface_uvs` has
+    // scaled them, so they scale with the positions
+    match settings.uv_scale {
+        Some(uv_scale) if raw.uvs.len() == raw.positions.len() => {
+            let uv_scale = uv_scale * settings.scale;
+            for uv in &mut raw.uvs {
+                *uv = uv.map(|c| c * uv_scale);
+            }
+        }
+        _ => raw.uvs.clear(),
+    }
+    if raw.faces.len() != raw.positions.len() {
+        raw.faces.clear();
+    }
+    if settings.up_axis.is_mirrored() {
+        for triangle in raw.indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+    }
+
+    // Optimise while the mesh is still indexed, flat shading un-indexes it
+    #[cfg(feature = "meshopt")]
+    if settings.meshopt {
+        optimize_buffers(&mut raw, &settings.meshopt_passes)?;
+    }
+
+    let RawMesh {
+        positions,
+        indices,
+        colors,
+        normals,
+        uvs,
+        faces,
+    } = raw;
+    let mut bevy_mesh = Mesh::new(
+        PrimitiveTopology::TriangleList,
+        RenderAssetUsages::all(), // Using the asset API directly
+    );
+    bevy_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    if !colors.is_empty() {
+        bevy_mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+    }
+    if !normals.is_empty() {
+        bevy_mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    }
+    if !uvs.is_empty() {
+        bevy_mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, uvs);
+    }
+    if !faces.is_empty() {
+        bevy_mesh.insert_attribute(crate::ATTRIBUTE_STEP_FACE, faces);
+    }
+    bevy_mesh.insert_indices(Indices::U32(indices));
+
+    Ok(bevy_mesh)
+}
+
+/// The second half of [`build_mesh`].
+pub(crate) fn add_normals(mesh: &mut Mesh, settings: &StepLoaderSettings) -> Result<(), StepLoaderError> {
+    // Compute normals for proper lighting
+    match settings.normals {
+        // Surface normals come in with the mesh, unless the backend had none to give
+        NormalMode::Surface if mesh.contains_attribute(Mesh::ATTRIBUTE_NORMAL) => {}
+        NormalMode::Surface | NormalMode::Smooth => mesh.compute_normals(),
+        NormalMode::Flat => {
+            mesh.duplicate_vertices();
+            mesh.compute_flat_normals();
+        }
+        NormalMode::CreaseAngle(degrees) => crate::compute_crease_normals(mesh, degrees)?,
+    }
+    // Tangents follow the normals, so they're only finished now
+    if mesh.contains_attribute(Mesh::ATTRIBUTE_UV_0) {
+        mesh.generate_tangents()
+            .map_err(|_| StepLoaderError::InvalidMesh("couldn't generate tangents"))?;
+    }
+
+    #[cfg(feature = "meshopt")]
+    if let (true, Some(bits)) = (settings.meshopt, settings.meshopt_passes.normal_bits)
+        && let Some(bevy_mesh::VertexAttributeValues::Float32x3(normals)) = mesh.attribute_mut(Mesh::ATTRIBUTE_NORMAL)
+    {
+        quantize_normals(normals, bits);
+    }
+    Ok(())
+}
+
+/// Runs the meshopt passes picked in `passes` over a mesh's buffers.
+#[cfg(feature = "meshopt")]
+fn optimize_buffers(raw: &mut RawMesh, passes: &MeshoptPasses) -> Result<(), StepLoaderError> {
+    if raw.indices.is_empty() || raw.positions.is_empty() {
+        return Ok(());
+    }
+
+    // Before welding, so vertices that only differ by noise below the precision kept merge
+    if let Some(bits) = passes.position_bits {
+        quantize_positions(&mut raw.positions, bits);
+    }
+
+    if passes.weld {
+        // Weld on every attribute, not just position, so faces of different colours keep their
+        // own vertices and so do the sides of a sharp edge, a UV seam or a face boundary
+        let keys: Vec<[f32; 13]> = (0..raw.positions.len())
+            .map(|i| {
+                let p = raw.positions[i];
+                let c = raw.colors.get(i).copied().unwrap_or_default();
+                let n = raw.normals.get(i).copied().unwrap_or_default();
+                let uv = raw.uvs.get(i).copied().unwrap_or_default();
+                // Compared bitwise, so the bits of the index do as well as any float
+                let face = f32::from_bits(raw.faces.get(i).copied().unwrap_or_default());
+                [p[0], p[1], p[2], c[0], c[1], c[2], c[3], n[0], n[1], n[2], uv[0], uv[1], face]
+            })
+            .collect();
+        let (count, remap) = meshopt::generate_vertex_remap(&keys, Some(&raw.indices));
+        remap_buffers(raw, count, &remap);
+    }
+
+    if passes.vertex_cache {
+        meshopt::optimize_vertex_cache_in_place(&mut raw.indices, raw.positions.len());
+    }
+
+    if let Some(threshold) = passes.overdraw {
+        let adapter =
+            meshopt::VertexDataAdapter::new(bytemuck::cast_slice(&raw.positions), std::mem::size_of::<[f32; 3]>(), 0)
+                .map_err(|_| StepLoaderError::InvalidMesh("positions can't be read by meshopt"))?;
+        meshopt::optimize_overdraw_in_place(&mut raw.indices, &adapter, threshold);
+    }
+
+    // Last, as it follows the final triangle order
+    if passes.vertex_fetch {
+        let remap = meshopt::optimize_vertex_fetch_remap(&raw.indices, raw.positions.len());
+        let count = remap.iter().filter(|&&r| r != u32::MAX).count();
+        remap_buffers(raw, count, &remap);
+    }
+
+    Ok(())
+}
+
+#[cfg(feature = "meshopt")]
+fn remap_buffers(raw: &mut RawMesh, count: usize, remap: &[u32]) {
+    raw.positions = meshopt::remap_vertex_buffer(&raw.positions, count, remap);
+    if !raw.colors.is_empty() {
+        raw.colors = meshopt::remap_vertex_buffer(&raw.colors, count, remap);
+    }
+    if !raw.normals.is_empty() {
+        raw.normals = meshopt::remap_vertex_buffer(&raw.normals, count, remap);
+    }
+    if !raw.uvs.is_empty() {
+        raw.uvs = meshopt::remap_vertex_buffer(&raw.uvs, count, remap);
+    }
+    if !raw.faces.is_empty() {
+        raw.faces = meshopt::remap_vertex_buffer(&raw.faces, count, remap);
+    }
+    raw.indices = meshopt::remap_index_buffer(Some(&raw.indices), count, remap);
+}
+
+/// Rounds positions to what `bits` bits per axis can hold across the mesh's bounds.
+#[cfg(feature = "meshopt")]
+fn quantize_positions(positions: &mut [[f32; 3]], bits: u8) {
+    let (min, max) = positions.iter().fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), p| {
+        (min.min(Vec3::from(*p)), max.max(Vec3::from(*p)))
+    });
+    // One scale for every axis, so the rounding doesn't stretch the model
+    let extent = (max - min).max_element();
+    if extent <= 0.0 {
+        return;
+    }
+    let steps = ((1u64 << bits.clamp(1, 24)) - 1) as f32;
+    for p in positions {
+        let q = ((Vec3::from(*p) - min) / extent * steps).round();
+        *p = (q / steps * extent + min).to_array();
+    }
+}
+
+/// Rounds normals to signed normalised `bits`-bit components, and renormalises them.
+#[cfg(feature = "meshopt")]
+fn quantize_normals(normals: &mut [[f32; 3]], bits: u8) {
+    let steps = ((1u64 << (bits.clamp(2, 24) - 1)) - 1) as f32;
+    for n in normals {
+        let q = (Vec3::from(*n) * steps).round() / steps;
+        *n = q.normalize_or_zero().to_array();
+    }
+}
+
+#[cfg(all(test, feature = "meshopt"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn welding_keeps_faces_apart() {
+        // A square on face 0 as two triangles with corners of their own, and a triangle on
+        // face 1 against its edge along x = 1
+        let square = [
+            [0.0, 0.0, 0.0],
+            [1.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 0.0, 0.0],
+            [1.0, 1.0, 0.0],
+            [0.0, 1.0, 0.0],
+        ];
+        let side = [[1.0, 0.0, 0.0], [1.0, 0.0, -1.0], [1.0, 1.0, 0.0]];
+        let corners: Vec<([f32; 3], u32)> = square.iter().map(|p| (*p, 0)).chain(side.iter().map(|p| (*p, 1))).collect();
+        let passes = MeshoptPasses {
+            weld: true,
+            vertex_cache: false,
+            ..Default::default()
+        };
+
+        let mut raw = RawMesh {
+            positions: corners.iter().map(|(p, _)| *p).collect(),
+            indices: (0..9).collect(),
+            faces: corners.iter().map(|(_, f)| *f).collect(),
+            ..Default::default()
+        };
+        optimize_buffers(&mut raw, &passes).unwrap();
+        // The square's two shared corners merge, the triangle's corners on the same edge don't
+        assert_eq!(raw.positions.len(), 7);
+        assert_eq!(raw.faces.iter().filter(|&&f| f == 0).count(), 4);
+        assert_eq!(raw.faces.iter().filter(|&&f| f == 1).count(), 3);
+        let welded: Vec<([f32; 3], u32)> = raw
+            .indices
+            .iter()
+            .map(|&i| (raw.positions[i as usize], raw.faces[i as usize]))
+            .collect();
+        assert_eq!(welded, corners);
+
+        // Without faces to tell them apart, every corner at the same place is one vertex
+        let mut untagged = RawMesh {
+            positions: corners.iter().map(|(p, _)| *p).collect(),
+            indices: (0..9).collect(),
+            ..Default::default()
+        };
+        optimize_buffers(&mut untagged, &passes).unwrap();
+        assert_eq!(untagged.positions.len(), 5);
+    }
+}
@@ -0,0 +1,98 @@
+//! Converts a STEP file to glTF, for loading with Bevy's own glTF loader from then on.
+//!
+//! ```text
+//! cargo run --features gltf --bin step2gltf -- part.step part.glb [--z-up]
+//! ```
+//!
+//! Writes binary glTF unless the output ends in `.gltf`. The model is converted to metres,
+//! glTF's unit, taking files that don't declare one to be in millimetres, and `--z-up` turns
+//! a Z-up file the right way up.
+
+use std::path::Path;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use bevy_app::{App, TaskPoolPlugin};
+use bevy_asset::{AssetApp, AssetPlugin, AssetServer, Assets, LoadState};
+use bevy_mesh::Mesh;
+use bevy_pbr::StandardMaterial;
+use bevy_scene::Scene;
+use bevy_step_loader::{LengthUnit, StepAsset, StepLoaderSettings, StepPlugin, UpAxis};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let z_up = args.iter().any(|a| a == "--z-up");
+    let mut paths = args.iter().filter(|a| !a.starts_with("--"));
+    let (Some(input), Some(output)) = (paths.next(), paths.next()) else {
+        eprintln!("usage: step2gltf <input.step> <output.glb|output.gltf> [--z-up]");
+        return ExitCode::FAILURE;
+    };
+
+    // The asset server reads from the input's directory
+    let input = match Path::new(input).canonicalize() {
+        Ok(input) => input,
+        Err(e) => {
+            eprintln!("{}: {}", input, e);
+            return ExitCode::FAILURE;
+        }
+    };
+    let (Some(directory), Some(file)) = (input.parent(), input.file_name()) else {
+        eprintln!("{}: not a file", input.display());
+        return ExitCode::FAILURE;
+    };
+
+    let mut app = App::new();
+    app.add_plugins((
+        TaskPoolPlugin::default(),
+        AssetPlugin {
+            file_path: directory.to_string_lossy().into_owned(),
+            ..Default::default()
+        },
+        StepPlugin,
+    ))
+    .init_asset::<Mesh>()
+    .init_asset::<StandardMaterial>()
+    .init_asset::<Scene>();
+    app.finish();
+    app.cleanup();
+
+    let handle = app.world().resource::<AssetServer>().load_with_settings::<StepAsset, StepLoaderSettings>(
+        file.to_string_lossy().into_owned(),
+        move |s: &mut StepLoaderSettings| {
+            s.target_unit = Some(LengthUnit::Metre);
+            s.assumed_unit = Some(LengthUnit::Millimetre);
+            if z_up {
+                s.up_axis = UpAxis::ZUp;
+            }
+        },
+    );
+    loop {
+        app.update();
+        match app.world().resource::<AssetServer>().load_state(&handle) {
+            LoadState::Loaded => break,
+            LoadState::Failed(e) => {
+                eprintln!("{}: {}", input.display(), e);
+                return ExitCode::FAILURE;
+            }
+            _ => std::thread::sleep(Duration::from_millis(10)),
+        }
+    }
+
+    let world = app.world();
+    let Some(step) = world.resource::<Assets<StepAsset>>().get(&handle) else {
+        eprintln!("{}: loaded but missing", input.display());
+        return ExitCode::FAILURE;
+    };
+    let meshes = world.resource::<Assets<Mesh>>();
+    let materials = world.resource::<Assets<StandardMaterial>>();
+    match step.export_gltf(output, meshes, materials) {
+        Ok(()) => {
+            println!("{} -> {} ({} parts)", input.display(), output, step.parts.len());
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("{}: {}", output, e);
+            ExitCode::FAILURE
+        }
+    }
+}
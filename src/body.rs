@@ -0,0 +1,136 @@
+//! One labeled mesh per solid, so a single body can be loaded on its own.
+
+use std::collections::HashMap;
+
+use bevy_asset::LoadContext;
+use bevy_platform::time::Instant;
+
+use crate::assembly::Assembly;
+use crate::backend::{RawMesh, build_mesh};
+use crate::brep::Brep;
+use crate::collider;
+use crate::segment::FaceHit;
+use crate::{
+    MassProperties, StepAssetLabel, StepBody, StepBounds, StepLoadReport, StepLoaderError, StepLoaderSettings,
+};
+
+/// Adds a mesh for every solid that got triangles, in the frame of the product that owns it.
+///
+/// A solid placed several times by the assembly gives one mesh, taken from its first
+/// occurrence, the other occurrences are the same triangles moved somewhere else. The time
+/// their collision shapes take goes into `report`.
+pub(crate) fn build(
+    load_context: &mut LoadContext<'_>,
+    raw: &RawMesh,
+    brep: &Brep,
+    assembly: &Assembly,
+    hits: &[Option<FaceHit>],
+    settings: &StepLoaderSettings,
+    report: &mut StepLoadReport,
+) -> Result<Vec<StepBody>, StepLoaderError> {
+    // Solid -> (the node of its first occurrence, that occurrence's triangles)
+    let mut occurrences: HashMap<u64, (usize, Vec<usize>)> = HashMap::new();
+    for (t, hit) in hits.iter().enumerate() {
+        let Some(hit) = hit else { continue };
+        let (node, triangles) = occurrences.entry(hit.solid).or_insert((hit.node, Vec::new()));
+        if hit.node < *node {
+            *node = hit.node;
+            triangles.clear();
+        }
+        if hit.node == *node {
+            triangles.push(t);
+        }
+    }
+
+    let mut ids: Vec<u64> = occurrences.keys().copied().collect();
+    ids.sort_unstable();
+
+    let names: Vec<(u64, &str)> = ids.iter().map(|id| (*id, brep.solids[id].name.as_str())).collect();
+    let labels = body_labels(&names);
+
+    let mut bodies = Vec::with_capacity(ids.len());
+    for (id, label) in ids.into_iter().zip(labels) {
+        let name = brep.solids[&id].name.clone();
+        let (node, triangles) = &occurrences[&id];
+        let part = raw.subset(triangles.iter().copied(), &assembly.nodes[*node].world.inverse());
+        let mesh = build_mesh(part, settings)?;
+        let density = settings.densities.get(&label).copied().unwrap_or(settings.density);
+        let mass = MassProperties::from_mesh(&mesh, density)?;
+        let bounds = StepBounds::from_mesh(&mesh);
+        let start = Instant::now();
+        let colliders = collider::build(&mesh, &brep.solids[&id], brep, settings)?;
+        report.collider_time += start.elapsed();
+        let mesh = load_context.add_labeled_asset(StepAssetLabel::Body(label.clone()).to_string(), mesh);
+        bodies.push(StepBody {
+            name,
+            id,
+            label,
+            mesh,
+            bounds,
+            mass,
+            colliders,
+        });
+    }
+    Ok(bodies)
+}
+
+/// The labels of the solids `names`, by id and name, in the same order: each body's own name
+/// where that's enough to find it, otherwise the name and entity id. Names that are empty,
+/// reserved, or that come out the same as another body's label get the id.
+fn body_labels(names: &[(u64, &str)]) -> Vec<String> {
+    let mut labels: Vec<String> = names
+        .iter()
+        .map(|&(id, name)| {
+            // `#` would start another label and `/` reads as a path, so keep them out
+            let name: String = name
+                .chars()
+                .map(|c| if c == '#' || c == '/' || c == '\\' { '_' } else { c })
+                .collect();
+            match name.as_str() {
+                "" => format!("Body-{}", id),
+                name if StepAssetLabel::is_reserved(name) => format!("{}-{}", name, id),
+                _ => name,
+            }
+        })
+        .collect();
+
+    // Adding the id can make a label another body already has, `Body1` with id 5 and a body
+    // named `Body1-5`, so go round until every label is used once. The ids differ, so each
+    // round leaves fewer labels the same
+    loop {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        for label in &labels {
+            *counts.entry(label.as_str()).or_default() += 1;
+        }
+        let repeated: Vec<usize> = (0..labels.len()).filter(|&i| counts[labels[i].as_str()] > 1).collect();
+        if repeated.is_empty() {
+            return labels;
+        }
+        for i in repeated {
+            labels[i] = format!("{}-{}", labels[i], names[i].0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_are_unique() {
+        let labels = body_labels(&[
+            (1, "Bracket"),
+            (2, "a#b"),
+            (3, "a/b"),
+            (4, ""),
+            (5, "Body1"),
+            (6, "Scene"),
+            (9, "Body1"),
+            (12, "Body1-5"),
+        ]);
+        assert_eq!(
+            labels,
+            ["Bracket", "a_b-2", "a_b-3", "Body-4", "Body1-5-5", "Scene-6", "Body1-9", "Body1-5-12"]
+        );
+    }
+}
@@ -0,0 +1,265 @@
+//! Bounding volumes of the loaded meshes, for laying models out without walking their
+//! vertices again.
+
+use bevy_camera::primitives::Aabb;
+use bevy_math::bounding::BoundingSphere;
+use bevy_math::{DMat3, DVec3, Quat, Vec3};
+use bevy_mesh::{Mesh, VertexAttributeValues};
+use bevy_transform::components::Transform;
+
+/// Plenty for a 3x3 matrix to converge, Jacobi roughly doubles its digits each sweep.
+const JACOBI_ROTATIONS: usize = 32;
+
+/// A box along axes of its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrientedBox {
+    pub center: Vec3,
+    pub half_extents: Vec3,
+    /// Takes the box's axes to the mesh's.
+    pub rotation: Quat,
+}
+
+impl OrientedBox {
+    pub fn volume(&self) -> f32 {
+        8.0 * self.half_extents.x * self.half_extents.y * self.half_extents.z
+    }
+
+    /// The box's corners, in the mesh's frame.
+    pub fn corners(&self) -> [Vec3; 8] {
+        [0, 1, 2, 3, 4, 5, 6, 7].map(|i| {
+            let sign = Vec3::new(
+                if i & 1 == 0 { -1.0 } else { 1.0 },
+                if i & 2 == 0 { -1.0 } else { 1.0 },
+                if i & 4 == 0 { -1.0 } else { 1.0 },
+            );
+            self.center + self.rotation * (sign * self.half_extents)
+        })
+    }
+}
+
+/// The bounding volumes of a mesh, in its own frame and units.
+#[derive(Debug, Clone, Copy)]
+pub struct StepBounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+    /// A box fitted along the principal axes of the vertices, or the AABB when that's
+    /// tighter.
+    pub oriented: OrientedBox,
+}
+
+impl StepBounds {
+    /// The bounds of `mesh`'s positions. A mesh with none gets bounds of no size at the origin.
+    pub fn from_mesh(mesh: &Mesh) -> StepBounds {
+        match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => {
+                let points: Vec<Vec3> = positions.iter().copied().map(Vec3::from).collect();
+                StepBounds::from_points(&points)
+            }
+            _ => StepBounds::from_points(&[]),
+        }
+    }
+
+    pub fn from_points(points: &[Vec3]) -> StepBounds {
+        let (min, max) = match points {
+            [] => (Vec3::ZERO, Vec3::ZERO),
+            _ => points
+                .iter()
+                .fold((Vec3::INFINITY, Vec3::NEG_INFINITY), |(min, max), p| (min.min(*p), max.max(*p))),
+        };
+        let aabb = Aabb::from_min_max(min, max);
+        let along_axes = OrientedBox {
+            center: (min + max) / 2.0,
+            half_extents: (max - min) / 2.0,
+            rotation: Quat::IDENTITY,
+        };
+        let oriented = match principal_box(points) {
+            Some(fitted) if fitted.volume() < along_axes.volume() => fitted,
+            _ => along_axes,
+        };
+        StepBounds {
+            aabb,
+            sphere: bounding_sphere(points, (min + max) / 2.0),
+            oriented,
+        }
+    }
+
+    /// The size of the AABB's longest side.
+    pub fn size(&self) -> f32 {
+        2.0 * self.aabb.half_extents.max_element()
+    }
+
+    /// A transform putting the AABB's centre at the origin and scaling its longest side to 1,
+    /// to show models of any size alike.
+    pub fn normalizing_transform(&self) -> Transform {
+        let scale = match self.size() {
+            size if size > 0.0 => 1.0 / size,
+            _ => 1.0,
+        };
+        Transform {
+            translation: -Vec3::from(self.aabb.center) * scale,
+            scale: Vec3::splat(scale),
+            ..Transform::IDENTITY
+        }
+    }
+}
+
+/// Ritter's sphere, or the one about the AABB's centre when that happens to be smaller.
+fn bounding_sphere(points: &[Vec3], aabb_center: Vec3) -> BoundingSphere {
+    let Some(&first) = points.first() else {
+        return BoundingSphere::new(Vec3::ZERO, 0.0);
+    };
+    let farthest_from = |from: Vec3| {
+        points
+            .iter()
+            .copied()
+            .max_by(|a, b| a.distance_squared(from).total_cmp(&b.distance_squared(from)))
+            .unwrap_or(from)
+    };
+    let a = farthest_from(first);
+    let b = farthest_from(a);
+    let mut center = (a + b) / 2.0;
+    let mut radius = a.distance(b) / 2.0;
+    for p in points {
+        let distance = p.distance(center);
+        if distance > radius {
+            // Grow just enough to take in `p`, keeping the far side where it is
+            let grown = (radius + distance) / 2.0;
+            center += (*p - center) * ((grown - radius) / distance);
+            radius = grown;
+        }
+    }
+
+    let around_aabb = points.iter().map(|p| p.distance(aabb_center)).fold(0.0, f32::max);
+    if around_aabb < radius {
+        BoundingSphere::new(aabb_center, around_aabb)
+    } else {
+        BoundingSphere::new(center, radius)
+    }
+}
+
+/// The box along the principal axes of `points`, `None` without enough of them to have any.
+fn principal_box(points: &[Vec3]) -> Option<OrientedBox> {
+    if points.len() < 3 {
+        return None;
+    }
+    let points: Vec<DVec3> = points.iter().map(|p| p.as_dvec3()).collect();
+    let mean = points.iter().sum::<DVec3>() / points.len() as f64;
+    let covariance = points.iter().fold(DMat3::ZERO, |sum, p| {
+        let d = *p - mean;
+        sum + DMat3::from_cols(d * d.x, d * d.y, d * d.z)
+    });
+
+    let mut axes = principal_axes(covariance);
+    if axes.determinant() < 0.0 {
+        axes.z_axis = -axes.z_axis;
+    }
+    let (min, max) = points.iter().fold((DVec3::INFINITY, DVec3::NEG_INFINITY), |(min, max), p| {
+        let local = axes.transpose() * *p;
+        (min.min(local), max.max(local))
+    });
+    Some(OrientedBox {
+        center: (axes * ((min + max) / 2.0)).as_vec3(),
+        half_extents: ((max - min) / 2.0).as_vec3(),
+        rotation: Quat::from_mat3(&axes.as_mat3()).normalize(),
+    })
+}
+
+/// The eigenvectors of the symmetric matrix `m`, as the columns of a rotation, by Jacobi's
+/// method.
+pub(crate) fn principal_axes(m: DMat3) -> DMat3 {
+    // Column major, `a[column][row]`
+    let mut a = m.to_cols_array_2d();
+    let mut v = DMat3::IDENTITY.to_cols_array_2d();
+    let scale = a[0][0].abs() + a[1][1].abs() + a[2][2].abs();
+    for _ in 0..JACOBI_ROTATIONS {
+        // Zero the largest off-diagonal element
+        let Some((p, q)) = [(0, 1), (0, 2), (1, 2)]
+            .into_iter()
+            .max_by(|&(i, j), &(k, l)| a[i][j].abs().total_cmp(&a[k][l].abs()))
+        else {
+            break;
+        };
+        if a[p][q].abs() <= 1e-12 * scale {
+            break;
+        }
+        let theta = (a[q][q] - a[p][p]) / (2.0 * a[p][q]);
+        let t = theta.signum() / (theta.abs() + (theta * theta + 1.0).sqrt());
+        let c = 1.0 / (t * t + 1.0).sqrt();
+        let s = t * c;
+
+        // A J, then J^T (A J), and V J
+        let rotate = |m: &mut [[f64; 3]; 3]| {
+            let (column_p, column_q) = (m[p], m[q]);
+            m[p] = [0, 1, 2].map(|k| c * column_p[k] - s * column_q[k]);
+            m[q] = [0, 1, 2].map(|k| s * column_p[k] + c * column_q[k]);
+        };
+        rotate(&mut a);
+        for column in &mut a {
+            let (pk, qk) = (column[p], column[q]);
+            column[p] = c * pk - s * qk;
+            column[q] = s * pk + c * qk;
+        }
+        rotate(&mut v);
+    }
+    DMat3::from_cols_array_2d(&v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn oriented_box_of_a_rotated_box() {
+        let rotation = Quat::from_euler(bevy_math::EulerRot::YXZ, 0.7, -0.4, 1.1);
+        let center = Vec3::new(10.0, -5.0, 2.0);
+        let half_extents = Vec3::new(3.0, 2.0, 1.0);
+        let corners = OrientedBox {
+            center,
+            half_extents,
+            rotation,
+        }
+        .corners();
+
+        let bounds = StepBounds::from_points(&corners);
+        let oriented = bounds.oriented;
+        assert!((oriented.volume() - 48.0).abs() < 1e-3);
+        assert!(oriented.center.distance(center) < 1e-4);
+        let mut found = oriented.half_extents.to_array();
+        found.sort_by(f32::total_cmp);
+        assert!(Vec3::from(found).abs_diff_eq(Vec3::new(1.0, 2.0, 3.0), 1e-4));
+        // Its corners are the box's, in some order
+        for corner in oriented.corners() {
+            assert!(corners.iter().any(|c| c.distance(corner) < 1e-4));
+        }
+        assert!(oriented.volume() < 8.0 * bounds.aabb.half_extents.x * bounds.aabb.half_extents.y * bounds.aabb.half_extents.z);
+    }
+
+    #[test]
+    fn principal_axes_are_eigenvectors() {
+        let m = DMat3::from_cols(
+            DVec3::new(4.0, 1.0, -2.0),
+            DVec3::new(1.0, 3.0, 0.5),
+            DVec3::new(-2.0, 0.5, 5.0),
+        );
+        let axes = principal_axes(m);
+        assert!((axes.transpose() * axes).abs_diff_eq(DMat3::IDENTITY, 1e-12));
+        for axis in [axes.x_axis, axes.y_axis, axes.z_axis] {
+            let image = m * axis;
+            assert!(image.reject_from_normalized(axis).length() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn empty_and_flat() {
+        let empty = StepBounds::from_points(&[]);
+        assert_eq!(empty.size(), 0.0);
+        assert_eq!(empty.oriented.volume(), 0.0);
+        assert_eq!(empty.normalizing_transform(), Transform::IDENTITY);
+
+        let square = [Vec3::ZERO, Vec3::X, Vec3::Y, Vec3::new(1.0, 1.0, 0.0)];
+        let bounds = StepBounds::from_points(&square);
+        assert_eq!(bounds.oriented.volume(), 0.0);
+        assert_eq!(bounds.size(), 1.0);
+        assert!(Vec3::from(bounds.sphere.center).distance(Vec3::new(0.5, 0.5, 0.0)) < 1e-6);
+    }
+}
@@ -0,0 +1,608 @@
+//! The B-rep side of a STEP file: solids, their faces, and enough of each face's geometry to
+//! tell which face a patch of triangles came from.
+
+use std::collections::HashMap;
+
+use bevy_math::{DAffine3, DMat3, DVec2, DVec3};
+
+use crate::part21::{Record, StepData, Value};
+use crate::{StepLoadReport, StepLoaderError};
+
+/// Keywords of the representation items we treat as solids.
+pub(crate) const SOLID_KEYWORDS: [&str; 4] = [
+    "MANIFOLD_SOLID_BREP",
+    "BREP_WITH_VOIDS",
+    "FACETED_BREP",
+    "SHELL_BASED_SURFACE_MODEL",
+];
+
+/// Oriented shells wrapping oriented shells, deeper than this is a cycle.
+const MAX_ORIENTATIONS: usize = 4;
+
+/// Representation items that go along with geometry without being any, placements and
+/// references to other shapes.
+const IGNORED_ITEMS: [&str; 3] = ["AXIS2_PLACEMENT_3D", "MAPPED_ITEM", "CARTESIAN_POINT"];
+
+/// An axis aligned box in file units.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Bounds {
+    pub min: DVec3,
+    pub max: DVec3,
+}
+
+impl Bounds {
+    pub(crate) const EMPTY: Bounds = Bounds {
+        min: DVec3::INFINITY,
+        max: DVec3::NEG_INFINITY,
+    };
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.min.cmpgt(self.max).any()
+    }
+
+    pub(crate) fn extend(&mut self, p: DVec3) {
+        self.min = self.min.min(p);
+        self.max = self.max.max(p);
+    }
+
+    pub(crate) fn union(&mut self, other: &Bounds) {
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    pub(crate) fn padded(&self, pad: f64) -> Bounds {
+        Bounds {
+            min: self.min - pad,
+            max: self.max + pad,
+        }
+    }
+
+    pub(crate) fn diagonal(&self) -> f64 {
+        if self.is_empty() { 0.0 } else { (self.max - self.min).length() }
+    }
+
+    pub(crate) fn overlaps(&self, other: &Bounds) -> bool {
+        self.min.cmple(other.max).all() && self.max.cmpge(other.min).all()
+    }
+
+    /// How far `p` lies outside the box, zero if it's inside.
+    pub(crate) fn outside_distance(&self, p: DVec3) -> f64 {
+        (self.min - p).max(p - self.max).max(DVec3::ZERO).length()
+    }
+
+    /// The bounds of this box after an affine transform.
+    pub(crate) fn transformed(&self, transform: &DAffine3) -> Bounds {
+        let mut out = Bounds::EMPTY;
+        if self.is_empty() {
+            return out;
+        }
+        for i in 0..8 {
+            let corner = DVec3::new(
+                if i & 1 == 0 { self.min.x } else { self.max.x },
+                if i & 2 == 0 { self.min.y } else { self.max.y },
+                if i & 4 == 0 { self.min.z } else { self.max.z },
+            );
+            out.extend(transform.transform_point3(corner));
+        }
+        out
+    }
+}
+
+/// The underlying geometry of a face, for the surface types we know how to evaluate.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum SurfaceKind {
+    Plane,
+    Cylinder { radius: f64 },
+    Cone { radius: f64, semi_angle: f64 },
+    Sphere { radius: f64 },
+    Torus { major_radius: f64, minor_radius: f64 },
+    /// B-splines, swept surfaces, offsets... anything we only know by the face's bounds.
+    Other,
+}
+
+/// A surface and its placement. Every analytic surface is defined in a local frame with its
+/// axis along +Z, so the maths below happens in that frame.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Surface {
+    pub kind: SurfaceKind,
+    /// Local frame into file space.
+    pub frame: DAffine3,
+    /// File space into the local frame.
+    pub inverse: DAffine3,
+}
+
+impl Surface {
+    fn new(kind: SurfaceKind, frame: DAffine3) -> Surface {
+        Surface {
+            kind,
+            frame,
+            inverse: frame.inverse(),
+        }
+    }
+
+    pub(crate) const OTHER: Surface = Surface {
+        kind: SurfaceKind::Other,
+        frame: DAffine3::IDENTITY,
+        inverse: DAffine3::IDENTITY,
+    };
+
+    /// Distance from `p` to the (untrimmed) surface, `None` for [`SurfaceKind::Other`].
+    pub(crate) fn distance(&self, p: DVec3) -> Option<f64> {
+        let q = self.inverse.transform_point3(p);
+        Some(match self.kind {
+            SurfaceKind::Plane => q.z.abs(),
+            SurfaceKind::Cylinder { radius } => (q.truncate().length() - radius).abs(),
+            SurfaceKind::Cone { radius, semi_angle } => {
+                let expected = radius + q.z * semi_angle.tan();
+                (q.truncate().length() - expected).abs() * semi_angle.cos()
+            }
+            SurfaceKind::Sphere { radius } => (q.length() - radius).abs(),
+            SurfaceKind::Torus { major_radius, minor_radius } => {
+                let ring = q.truncate().length() - major_radius;
+                ((ring * ring + q.z * q.z).sqrt() - minor_radius).abs()
+            }
+            SurfaceKind::Other => return None,
+        })
+    }
+
+    /// The unit normal of the (untrimmed) surface at the point nearest `p`, in file space.
+    /// It points away from the axis, or along +Z for planes, regardless of which way the face
+    /// using the surface faces. `None` for [`SurfaceKind::Other`] and points on the axis.
+    pub(crate) fn normal(&self, p: DVec3) -> Option<DVec3> {
+        let q = self.inverse.transform_point3(p);
+        let radial = DVec3::new(q.x, q.y, 0.0).try_normalize();
+        let local = match self.kind {
+            SurfaceKind::Plane => DVec3::Z,
+            SurfaceKind::Cylinder { .. } => radial?,
+            SurfaceKind::Cone { semi_angle, .. } => radial? * semi_angle.cos() - DVec3::Z * semi_angle.sin(),
+            SurfaceKind::Sphere { .. } => q.try_normalize()?,
+            SurfaceKind::Torus { major_radius, .. } => (q - radial? * major_radius).try_normalize()?,
+            SurfaceKind::Other => return None,
+        };
+        (self.frame.matrix3 * local).try_normalize()
+    }
+
+    /// The point of the (untrimmed) surface nearest `p`, in file space. `None` for
+    /// [`SurfaceKind::Other`] and points on the axis.
+    pub(crate) fn project(&self, p: DVec3) -> Option<DVec3> {
+        let q = self.inverse.transform_point3(p);
+        let radial = DVec3::new(q.x, q.y, 0.0).try_normalize();
+        let local = match self.kind {
+            SurfaceKind::Plane => DVec3::new(q.x, q.y, 0.0),
+            SurfaceKind::Cylinder { radius } => radial? * radius + DVec3::Z * q.z,
+            SurfaceKind::Cone { radius, semi_angle } => {
+                // Nearest point on the line the cone sweeps, in the plane through the axis
+                let (sin, cos) = semi_angle.sin_cos();
+                let along = (q.truncate().length() - radius) * sin + q.z * cos;
+                radial? * (radius + along * sin) + DVec3::Z * (along * cos)
+            }
+            SurfaceKind::Sphere { radius } => q.try_normalize()? * radius,
+            SurfaceKind::Torus { major_radius, minor_radius } => {
+                let ring = radial? * major_radius;
+                ring + (q - ring).try_normalize()? * minor_radius
+            }
+            SurfaceKind::Other => return None,
+        };
+        Some(self.frame.transform_point3(local))
+    }
+
+    /// The surface's (u, v) parameters at the point nearest `p`, scaled so that both measure
+    /// length along the surface and textures don't stretch. Angles are measured at the
+    /// surface's reference radius. Also returns each parameter's period, zero if it doesn't wrap.
+    /// `None` for [`SurfaceKind::Other`].
+    pub(crate) fn parameters(&self, p: DVec3) -> Option<(DVec2, DVec2)> {
+        use std::f64::consts::TAU;
+
+        let q = self.inverse.transform_point3(p);
+        let angle = q.y.atan2(q.x);
+        let rho = q.truncate().length();
+        Some(match self.kind {
+            SurfaceKind::Plane => (q.truncate(), DVec2::ZERO),
+            SurfaceKind::Cylinder { radius } => (DVec2::new(angle * radius, q.z), DVec2::new(TAU * radius, 0.0)),
+            SurfaceKind::Cone { radius, semi_angle } => {
+                // Cones may start at their apex, fall back to a unit radius there
+                let radius = if radius > 0.0 { radius } else { 1.0 };
+                (DVec2::new(angle * radius, q.z / semi_angle.cos()), DVec2::new(TAU * radius, 0.0))
+            }
+            SurfaceKind::Sphere { radius } => (
+                DVec2::new(angle * radius, q.z.atan2(rho) * radius),
+                DVec2::new(TAU * radius, 0.0),
+            ),
+            SurfaceKind::Torus { major_radius, minor_radius } => (
+                DVec2::new(angle * major_radius, q.z.atan2(rho - major_radius) * minor_radius),
+                DVec2::new(TAU * major_radius, TAU * minor_radius),
+            ),
+            SurfaceKind::Other => return None,
+        })
+    }
+
+    /// Bounds of the whole closed surface, for the ones that are finite.
+    fn bounds(&self) -> Option<Bounds> {
+        let extent = match self.kind {
+            SurfaceKind::Sphere { radius } => DVec3::splat(radius),
+            SurfaceKind::Torus { major_radius, minor_radius } => DVec3::new(
+                major_radius + minor_radius,
+                major_radius + minor_radius,
+                minor_radius,
+            ),
+            _ => return None,
+        };
+        Some(Bounds { min: -extent, max: extent }.transformed(&self.frame))
+    }
+}
+
+/// An `ADVANCED_FACE` (or `FACE_SURFACE`).
+#[derive(Debug, Clone)]
+pub(crate) struct Face {
+    pub surface: Surface,
+    /// The surface's keyword, for reporting.
+    pub surface_type: String,
+    /// Bounds of the face's edges, widened to the whole surface for spheres and tori
+    /// whose edges don't reach their extremes.
+    pub bounds: Bounds,
+}
+
+/// A solid (or surface model), as the faces of all its shells.
+#[derive(Debug, Clone)]
+pub(crate) struct Solid {
+    /// The representation item's name, `Body1` in `MANIFOLD_SOLID_BREP('Body1',#12)`.
+    pub name: String,
+    pub faces: Vec<u64>,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct Brep {
+    pub solids: HashMap<u64, Solid>,
+    pub faces: HashMap<u64, Face>,
+}
+
+impl Brep {
+    pub(crate) fn read(data: &StepData) -> Brep {
+        let mut brep = Brep::default();
+        for keyword in SOLID_KEYWORDS {
+            for (id, record) in data.all(keyword) {
+                let name = record.param(0).as_str().unwrap_or_default().to_string();
+                let mut faces = Vec::new();
+                for shell in solid_shells(data, keyword, record) {
+                    let Some(shell) = data
+                        .record(shell, "CLOSED_SHELL")
+                        .or_else(|| data.record(shell, "OPEN_SHELL"))
+                    else {
+                        continue;
+                    };
+                    for face_id in shell.param(1).refs() {
+                        if let Some(face) = read_face(data, face_id) {
+                            brep.faces.insert(face_id, face);
+                            faces.push(face_id);
+                        }
+                    }
+                }
+                brep.solids.insert(id, Solid { name, faces });
+            }
+        }
+        brep
+    }
+}
+
+/// The shells of a solid, `record` being its record of type `keyword` (one of
+/// [`SOLID_KEYWORDS`]): the outer shell, and for a `BREP_WITH_VOIDS` the voids, whose
+/// `ORIENTED_CLOSED_SHELL`s are resolved to the shells they turn inside out.
+pub(crate) fn solid_shells(data: &StepData, keyword: &str, record: &Record) -> Vec<u64> {
+    let shells: Vec<u64> = match keyword {
+        "SHELL_BASED_SURFACE_MODEL" => record.param(1).refs().collect(),
+        "BREP_WITH_VOIDS" => std::iter::once(record.param(1).as_id())
+            .flatten()
+            .chain(record.param(2).refs())
+            .collect(),
+        _ => record.param(1).as_id().into_iter().collect(),
+    };
+    shells
+        .into_iter()
+        .filter_map(|mut shell| {
+            // An oriented shell could name another, but never more than a couple deep outside
+            // of a broken file
+            for _ in 0..MAX_ORIENTATIONS {
+                match data
+                    .record(shell, "ORIENTED_CLOSED_SHELL")
+                    .or_else(|| data.record(shell, "ORIENTED_OPEN_SHELL"))
+                {
+                    Some(oriented) => shell = oriented.param(2).as_id()?,
+                    None => return Some(shell),
+                }
+            }
+            None
+        })
+        .collect()
+}
+
+/// Why a backend might have produced nothing for this file, from its `report` with the
+/// failed faces filled in.
+pub(crate) fn explain_empty(data: &StepData, brep: &Brep, report: &StepLoadReport) -> StepLoaderError {
+    let backend = report.backend;
+    // Every face came out empty, so blame the surface type that failed most. Surfaces we can't
+    // evaluate (B-splines, offsets...) go first, those are the ones the backends choke on
+    let culprit = report.failed_faces.iter().min_by_key(|f| {
+        let analytic = brep.faces.get(&f.entity.id).is_some_and(|face| face.surface.kind != SurfaceKind::Other);
+        let failures = report.failed_surfaces.get(&f.surface).copied().unwrap_or(0);
+        (analytic, std::cmp::Reverse(failures), f.entity.id)
+    });
+    if let Some(face) = culprit {
+        return StepLoaderError::TessellationFailed {
+            backend,
+            entity: Some(face.entity),
+            surface: Some(face.surface.clone()).filter(|s| !s.is_empty()),
+        };
+    }
+    // Solids without a face we could read, the solid is as close as we can get
+    if let Some(solid) = brep.solids.keys().min() {
+        return StepLoaderError::TessellationFailed {
+            backend,
+            entity: Some(data.entity(*solid)),
+            surface: None,
+        };
+    }
+
+    // No solids at all, so see what the shape is made of instead
+    let mut unsupported: Vec<(u64, &str)> = data
+        .instances
+        .values()
+        .flat_map(|i| &i.records)
+        .filter(|r| r.keyword.ends_with("SHAPE_REPRESENTATION"))
+        .flat_map(|r| r.param(1).refs())
+        .filter_map(|item| Some((item, data.get(item)?.records.first()?.keyword.as_str())))
+        .filter(|(_, keyword)| !IGNORED_ITEMS.contains(keyword))
+        .collect();
+    unsupported.sort_unstable();
+    match unsupported.first() {
+        Some((item, keyword)) => StepLoaderError::UnsupportedEntity {
+            entity: data.entity(*item),
+            keyword: keyword.to_string(),
+        },
+        None => StepLoaderError::EmptyResult { backend },
+    }
+}
+
+fn read_face(data: &StepData, id: u64) -> Option<Face> {
+    let record = data
+        .record(id, "ADVANCED_FACE")
+        .or_else(|| data.record(id, "FACE_SURFACE"))?;
+
+    let surface_id = record.param(2).as_id();
+    let surface = surface_id.map_or(Surface::OTHER, |s| read_surface(data, s));
+    let surface_type = surface_id.map(|s| surface_type(data, s)).unwrap_or_default();
+
+    let mut bounds = Bounds::EMPTY;
+    for bound in record.param(1).refs() {
+        let Some(loop_id) = data
+            .record(bound, "FACE_OUTER_BOUND")
+            .or_else(|| data.record(bound, "FACE_BOUND"))
+            .and_then(|b| b.param(1).as_id())
+        else {
+            continue;
+        };
+        loop_bounds(data, loop_id, &mut bounds);
+    }
+    if let Some(surface_bounds) = surface.bounds() {
+        bounds.union(&surface_bounds);
+    }
+
+    Some(Face {
+        surface,
+        surface_type,
+        bounds,
+    })
+}
+
+/// The keyword naming a surface. Complex instances (rational B-splines, mostly) spread it
+/// over several records, the longest `*SURFACE*` one is the most specific.
+pub(crate) fn surface_type(data: &StepData, id: u64) -> String {
+    let Some(inst) = data.get(id) else { return String::new() };
+    match inst.records.as_slice() {
+        [record] => record.keyword.clone(),
+        records => records
+            .iter()
+            .map(|r| r.keyword.as_str())
+            .filter(|k| k.contains("SURFACE"))
+            .max_by_key(|k| k.len())
+            .unwrap_or_default()
+            .to_string(),
+    }
+}
+
+/// The `EDGE_CURVE`s around a face, loop by loop in the order the file lists them.
+pub(crate) fn face_edges(data: &StepData, face_id: u64) -> Vec<u64> {
+    let Some(face) = data
+        .record(face_id, "ADVANCED_FACE")
+        .or_else(|| data.record(face_id, "FACE_SURFACE"))
+    else {
+        return Vec::new();
+    };
+    face.param(1)
+        .refs()
+        .filter_map(|bound| {
+            data.record(bound, "FACE_OUTER_BOUND")
+                .or_else(|| data.record(bound, "FACE_BOUND"))
+                .and_then(|b| b.param(1).as_id())
+        })
+        .filter_map(|loop_id| data.record(loop_id, "EDGE_LOOP"))
+        .flat_map(|edge_loop| edge_loop.param(1).refs())
+        .filter_map(|oriented| data.record(oriented, "ORIENTED_EDGE")?.param(3).as_id())
+        .filter(|edge| data.record(*edge, "EDGE_CURVE").is_some())
+        .collect()
+}
+
+fn loop_bounds(data: &StepData, loop_id: u64, bounds: &mut Bounds) {
+    let Some(inst) = data.get(loop_id) else { return };
+    if let Some(edge_loop) = inst.record("EDGE_LOOP") {
+        for oriented in edge_loop.param(1).refs() {
+            let Some(edge) = data
+                .record(oriented, "ORIENTED_EDGE")
+                .and_then(|o| o.param(3).as_id())
+                .and_then(|e| data.record(e, "EDGE_CURVE"))
+            else {
+                continue;
+            };
+            for vertex in [edge.param(1), edge.param(2)] {
+                if let Some(p) = vertex.as_id().and_then(|v| vertex_point(data, v)) {
+                    bounds.extend(p);
+                }
+            }
+            if let Some(curve) = edge.param(3).as_id() {
+                curve_bounds(data, curve, bounds);
+            }
+        }
+    } else if let Some(vertex_loop) = inst.record("VERTEX_LOOP") {
+        if let Some(p) = vertex_loop.param(1).as_id().and_then(|v| vertex_point(data, v)) {
+            bounds.extend(p);
+        }
+    } else if let Some(poly_loop) = inst.record("POLY_LOOP") {
+        for p in poly_loop.param(1).refs().filter_map(|p| point(data, p)) {
+            bounds.extend(p);
+        }
+    }
+}
+
+/// Widens `bounds` to cover an edge's curve, conservatively: circles contribute the whole
+/// circle and B-splines their control points.
+fn curve_bounds(data: &StepData, id: u64, bounds: &mut Bounds) {
+    let Some(inst) = data.get(id) else { return };
+    if let Some(circle) = inst.record("CIRCLE") {
+        if let (Some(frame), Some(r)) = (circle.param(1).as_id().and_then(|p| placement(data, p)), circle.param(2).as_f64()) {
+            bounds.union(&ellipse_bounds(&frame, r, r));
+        }
+    } else if let Some(ellipse) = inst.record("ELLIPSE") {
+        if let (Some(frame), Some(a), Some(b)) = (
+            ellipse.param(1).as_id().and_then(|p| placement(data, p)),
+            ellipse.param(2).as_f64(),
+            ellipse.param(3).as_f64(),
+        ) {
+            bounds.union(&ellipse_bounds(&frame, a, b));
+        }
+    } else if let Some(curve) = inst
+        .record("SURFACE_CURVE")
+        .or_else(|| inst.record("SEAM_CURVE"))
+        .or_else(|| inst.record("TRIMMED_CURVE"))
+    {
+        if let Some(basis) = curve.param(1).as_id() {
+            curve_bounds(data, basis, bounds);
+        }
+    } else {
+        // B-spline curves and polylines, whose points lie in the first list of references
+        for p in control_points(data, id) {
+            bounds.extend(p);
+        }
+    }
+}
+
+fn ellipse_bounds(frame: &DAffine3, a: f64, b: f64) -> Bounds {
+    let x = frame.matrix3.x_axis * a;
+    let y = frame.matrix3.y_axis * b;
+    let extent = (x * x + y * y).powf(0.5);
+    Bounds {
+        min: frame.translation - extent,
+        max: frame.translation + extent,
+    }
+}
+
+/// The points in the first list of `CARTESIAN_POINT` references found in an instance, which
+/// for B-spline curves is the control polygon. Complex instances split the attributes over
+/// several records, so rather than counting parameters this looks for the list itself.
+pub(crate) fn control_points(data: &StepData, id: u64) -> Vec<DVec3> {
+    let Some(inst) = data.get(id) else { return Vec::new() };
+    for record in &inst.records {
+        for param in &record.params {
+            let points: Vec<DVec3> = param.refs().filter_map(|p| point(data, p)).collect();
+            if !points.is_empty() {
+                return points;
+            }
+        }
+    }
+    Vec::new()
+}
+
+fn read_surface(data: &StepData, id: u64) -> Surface {
+    let Some(record) = data.get(id).and_then(|inst| inst.records.first()) else {
+        return Surface::OTHER;
+    };
+    let frame = record.param(1).as_id().and_then(|p| placement(data, p));
+    let real = |index: usize| record.param(index).as_f64();
+
+    let kind = match record.keyword.as_str() {
+        "PLANE" => Some(SurfaceKind::Plane),
+        "CYLINDRICAL_SURFACE" => real(2).map(|radius| SurfaceKind::Cylinder { radius }),
+        "CONICAL_SURFACE" => real(2)
+            .zip(real(3))
+            .map(|(radius, semi_angle)| SurfaceKind::Cone { radius, semi_angle }),
+        "SPHERICAL_SURFACE" => real(2).map(|radius| SurfaceKind::Sphere { radius }),
+        "TOROIDAL_SURFACE" => real(2)
+            .zip(real(3))
+            .map(|(major_radius, minor_radius)| SurfaceKind::Torus { major_radius, minor_radius }),
+        _ => None,
+    };
+    match (kind, frame) {
+        (Some(kind), Some(frame)) => Surface::new(kind, frame),
+        _ => Surface::OTHER,
+    }
+}
+
+pub(crate) fn point(data: &StepData, id: u64) -> Option<DVec3> {
+    vec3(data.record(id, "CARTESIAN_POINT")?.param(1))
+}
+
+pub(crate) fn direction(data: &StepData, id: u64) -> Option<DVec3> {
+    vec3(data.record(id, "DIRECTION")?.param(1))?.try_normalize()
+}
+
+pub(crate) fn vertex_point(data: &StepData, id: u64) -> Option<DVec3> {
+    point(data, data.record(id, "VERTEX_POINT")?.param(1).as_id()?)
+}
+
+fn vec3(value: &Value) -> Option<DVec3> {
+    let coords = value.as_list()?;
+    let c = |i: usize| coords.get(i).and_then(Value::as_f64);
+    // 2D points are allowed, treat them as lying on z = 0
+    Some(DVec3::new(c(0)?, c(1)?, c(2).unwrap_or(0.0)))
+}
+
+/// An `AXIS2_PLACEMENT_3D` as the transform from its local frame into its parent's.
+pub(crate) fn placement(data: &StepData, id: u64) -> Option<DAffine3> {
+    let record = data.record(id, "AXIS2_PLACEMENT_3D")?;
+    let origin = point(data, record.param(1).as_id()?)?;
+    let z = record.param(2).as_id().and_then(|d| direction(data, d)).unwrap_or(DVec3::Z);
+    let x_hint = record.param(3).as_id().and_then(|d| direction(data, d)).unwrap_or_else(|| z.any_orthonormal_vector());
+    // Project the reference direction so the frame stays orthonormal even when it's sloppy
+    let x = (x_hint - z * x_hint.dot(z)).try_normalize().unwrap_or_else(|| z.any_orthonormal_vector());
+    let y = z.cross(x);
+    Some(DAffine3::from_mat3_translation(DMat3::from_cols(x, y, z), origin))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part21::parse;
+
+    #[test]
+    fn voids_resolve_to_the_shells_they_orient() {
+        let data = parse(
+            b"ISO-10303-21;
+HEADER;
+ENDSEC;
+DATA;
+#1=CLOSED_SHELL('outer',());
+#2=CLOSED_SHELL('inner',());
+#3=ORIENTED_CLOSED_SHELL('',*,#2,.F.);
+#4=ORIENTED_CLOSED_SHELL('',*,#4,.F.);
+#5=BREP_WITH_VOIDS('hollow',#1,(#3,#4));
+#6=MANIFOLD_SOLID_BREP('solid',#1);
+ENDSEC;
+END-ISO-10303-21;
+",
+        )
+        .unwrap();
+        let hollow = data.record(5, "BREP_WITH_VOIDS").unwrap();
+        // The void that orients itself is dropped rather than followed forever
+        assert_eq!(solid_shells(&data, "BREP_WITH_VOIDS", hollow), [1, 2]);
+        let solid = data.record(6, "MANIFOLD_SOLID_BREP").unwrap();
+        assert_eq!(solid_shells(&data, "MANIFOLD_SOLID_BREP", solid), [1]);
+    }
+}
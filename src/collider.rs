@@ -0,0 +1,931 @@
+//! Collision shapes for the solids, in a form any physics crate can turn into its own.
+//!
+//! Every solid gets its convex hull and a convex decomposition, found by splitting the solid
+//! in two wherever that shrinks the volume the pieces' hulls cover the most, V-HACD style.
+//! Solids made of nothing but planes and cylinders that line up as a box or a cylinder also
+//! get that primitive, taken from the B-rep rather than the triangles.
+
+use bevy_math::{Quat, Vec2, Vec3};
+
+#[cfg(feature = "colliders")]
+use bevy_math::{DAffine3, DVec3};
+#[cfg(feature = "colliders")]
+use bevy_mesh::{Mesh, VertexAttributeValues};
+
+use crate::brep::{Brep, Solid};
+use crate::{StepLoaderError, StepLoaderSettings};
+
+/// A collision shape, in the frame and units of the body's mesh.
+#[derive(Debug, Clone, PartialEq)]
+pub enum StepCollider {
+    Cuboid {
+        center: Vec3,
+        rotation: Quat,
+        half_extents: Vec3,
+    },
+    /// A cylinder along its local Y, as Bevy's and most physics crates' are.
+    Cylinder {
+        center: Vec3,
+        rotation: Quat,
+        radius: f32,
+        half_height: f32,
+    },
+    /// A rectangle with its normal along its local Y, for surface models of a single planar
+    /// face.
+    Plane {
+        center: Vec3,
+        rotation: Quat,
+        half_size: Vec2,
+    },
+    /// The points' convex hull, with its triangles wound outwards. `indices` is empty when the
+    /// points are flat and have no volume.
+    ConvexHull { points: Vec<Vec3>, indices: Vec<[u32; 3]> },
+    /// Several shapes making up one, e.g. the pieces of a convex decomposition.
+    Compound(Vec<StepCollider>),
+}
+
+/// The collision shapes of one solid, see [`StepBody::colliders`](crate::StepBody::colliders).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepColliders {
+    /// The box, cylinder or plane the solid is, when it's one of those.
+    pub primitive: Option<StepCollider>,
+    /// The solid's convex hull, a [`StepCollider::ConvexHull`].
+    pub hull: StepCollider,
+    /// Convex hulls of pieces of the solid, a [`StepCollider::Compound`]. For a convex solid
+    /// that's the one hull.
+    pub decomposition: StepCollider,
+}
+
+impl StepColliders {
+    /// The closest fit: the primitive if there is one, otherwise the decomposition.
+    pub fn best(&self) -> &StepCollider {
+        self.primitive.as_ref().unwrap_or(&self.decomposition)
+    }
+}
+
+/// The collision shapes of `solid`, whose mesh is `mesh`, when the settings ask for them.
+#[cfg(feature = "colliders")]
+pub(crate) fn build(
+    mesh: &Mesh,
+    solid: &Solid,
+    brep: &Brep,
+    settings: &StepLoaderSettings,
+) -> Result<Option<StepColliders>, StepLoaderError> {
+    let Some(collider_settings) = &settings.colliders else {
+        return Ok(None);
+    };
+    let triangles = triangles(mesh)?;
+    let points: Vec<DVec3> = triangles.iter().flatten().copied().collect();
+
+    // The mesh is the B-rep turned into Bevy's axes and scaled
+    let to_mesh = DAffine3::from_mat3(settings.up_axis.basis() * f64::from(settings.scale));
+    let primitive = if collider_settings.primitives {
+        fit::primitive(solid, brep, &to_mesh, &points)
+    } else {
+        None
+    };
+    let pieces = decompose::decompose(triangles, collider_settings.max_pieces, f64::from(collider_settings.min_gain));
+
+    Ok(Some(StepColliders {
+        primitive,
+        hull: hull::collider(&points),
+        decomposition: StepCollider::Compound(pieces.iter().map(|piece| hull::collider(piece)).collect()),
+    }))
+}
+
+#[cfg(not(feature = "colliders"))]
+pub(crate) fn build(
+    _mesh: &bevy_mesh::Mesh,
+    _solid: &Solid,
+    _brep: &Brep,
+    settings: &StepLoaderSettings,
+) -> Result<Option<StepColliders>, StepLoaderError> {
+    match settings.colliders {
+        Some(_) => Err(StepLoaderError::FeatureDisabled("colliders")),
+        None => Ok(None),
+    }
+}
+
+/// The mesh's triangles, as corner positions.
+#[cfg(feature = "colliders")]
+fn triangles(mesh: &Mesh) -> Result<Vec<[DVec3; 3]>, StepLoaderError> {
+    let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => positions,
+        Some(_) => return Err(StepLoaderError::InvalidMesh("expected Float32x3 positions")),
+        None => return Err(StepLoaderError::InvalidMesh("no position attribute")),
+    };
+    let indices: Vec<usize> = match mesh.indices() {
+        Some(indices) => indices.iter().collect(),
+        None => (0..positions.len()).collect(),
+    };
+    Ok(indices
+        .chunks_exact(3)
+        .map(|t| [0, 1, 2].map(|i| DVec3::from(positions[t[i]].map(f64::from))))
+        .collect())
+}
+
+/// Boxes, cylinders and planes from the B-rep's surfaces.
+#[cfg(feature = "colliders")]
+mod fit {
+    use bevy_math::{DAffine3, DMat3, DVec3, Quat, Vec2};
+
+    use super::StepCollider;
+    use crate::brep::{Brep, Solid, SurfaceKind};
+
+    /// Directions closer than this (in `1 - |cos|`) are taken to be the same.
+    const PARALLEL: f64 = 1e-6;
+    /// Distances within this share of the solid's size are taken to be equal.
+    const COINCIDENT: f64 = 1e-4;
+
+    /// An analytic face, moved into the mesh's frame.
+    enum Face {
+        Plane { normal: DVec3, offset: f64 },
+        Cylinder { axis: DVec3, origin: DVec3, radius: f64 },
+    }
+
+    pub(super) fn primitive(solid: &Solid, brep: &Brep, to_mesh: &DAffine3, points: &[DVec3]) -> Option<StepCollider> {
+        let scale = to_mesh.matrix3.x_axis.length();
+        let mut faces = Vec::with_capacity(solid.faces.len());
+        for id in &solid.faces {
+            let surface = &brep.faces.get(id)?.surface;
+            let origin = to_mesh.transform_point3(surface.frame.translation);
+            let axis = (to_mesh.matrix3 * surface.frame.matrix3.z_axis).try_normalize()?;
+            faces.push(match surface.kind {
+                SurfaceKind::Plane => Face::Plane {
+                    normal: axis,
+                    offset: axis.dot(origin),
+                },
+                SurfaceKind::Cylinder { radius } => Face::Cylinder {
+                    axis,
+                    origin,
+                    radius: radius * scale,
+                },
+                _ => return None,
+            });
+        }
+
+        if points.is_empty() {
+            return None;
+        }
+        let (min, max) = points
+            .iter()
+            .fold((DVec3::INFINITY, DVec3::NEG_INFINITY), |(min, max), p| (min.min(*p), max.max(*p)));
+        let tolerance = (max - min).length() * COINCIDENT;
+        if tolerance <= 0.0 {
+            return None;
+        }
+
+        match faces.as_slice() {
+            [Face::Plane { normal, .. }] => Some(plane(*normal, points)),
+            _ if faces.iter().any(|f| matches!(f, Face::Cylinder { .. })) => cylinder(&faces, points, tolerance),
+            _ => cuboid(&faces, points, tolerance),
+        }
+    }
+
+    fn parallel(a: DVec3, b: DVec3) -> bool {
+        1.0 - a.dot(b).abs() < PARALLEL
+    }
+
+    /// The extent of `points` along `axis`.
+    fn span(points: &[DVec3], axis: DVec3) -> (f64, f64) {
+        points
+            .iter()
+            .map(|p| p.dot(axis))
+            .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), d| (min.min(d), max.max(d)))
+    }
+
+    /// Whether the plane `offset` along `axis` is one of the ends of `span`.
+    fn at_end(offset: f64, normal: DVec3, axis: DVec3, (min, max): (f64, f64), tolerance: f64) -> bool {
+        // The plane's offset is along its own normal, which may point either way along `axis`
+        let offset = offset * normal.dot(axis).signum();
+        (offset - min).abs() < tolerance || (offset - max).abs() < tolerance
+    }
+
+    /// The rotation taking local `x`, `y` and `z` to the given orthonormal axes, flipping `z`
+    /// if they're left-handed.
+    fn rotation(x: DVec3, y: DVec3, z: DVec3) -> Quat {
+        let z = if x.cross(y).dot(z) < 0.0 { -z } else { z };
+        Quat::from_mat3(&DMat3::from_cols(x, y, z).as_mat3()).normalize()
+    }
+
+    fn plane(normal: DVec3, points: &[DVec3]) -> StepCollider {
+        let (u, _) = normal.any_orthonormal_pair();
+        let w = u.cross(normal);
+        let (u_min, u_max) = span(points, u);
+        let (n_min, n_max) = span(points, normal);
+        let (w_min, w_max) = span(points, w);
+        let center = u * (u_min + u_max) / 2.0 + normal * (n_min + n_max) / 2.0 + w * (w_min + w_max) / 2.0;
+        StepCollider::Plane {
+            center: center.as_vec3(),
+            rotation: rotation(u, normal, w),
+            half_size: Vec2::new(((u_max - u_min) / 2.0) as f32, ((w_max - w_min) / 2.0) as f32),
+        }
+    }
+
+    /// A box: planes facing three perpendicular ways, each on one of the box's sides.
+    fn cuboid(faces: &[Face], points: &[DVec3], tolerance: f64) -> Option<StepCollider> {
+        let mut axes: Vec<DVec3> = Vec::new();
+        for face in faces {
+            let Face::Plane { normal, .. } = face else { return None };
+            if !axes.iter().any(|a| parallel(*a, *normal)) {
+                axes.push(*normal);
+            }
+        }
+        let &[x, y, z] = axes.as_slice() else { return None };
+        if x.dot(y).abs() > PARALLEL || x.dot(z).abs() > PARALLEL || y.dot(z).abs() > PARALLEL {
+            return None;
+        }
+
+        let spans = [x, y, z].map(|axis| span(points, axis));
+        for face in faces {
+            let Face::Plane { normal, offset } = face else { return None };
+            let (axis, span) = [x, y, z].into_iter().zip(spans).find(|(a, _)| parallel(*a, *normal))?;
+            // A plane inside the box makes an L, a step or a slot, not a box
+            if !at_end(*offset, *normal, axis, span, tolerance) {
+                return None;
+            }
+        }
+
+        let center = [x, y, z]
+            .into_iter()
+            .zip(spans)
+            .map(|(axis, (min, max))| axis * (min + max) / 2.0)
+            .sum::<DVec3>();
+        Some(StepCollider::Cuboid {
+            center: center.as_vec3(),
+            rotation: rotation(x, y, z),
+            half_extents: DVec3::from(spans.map(|(min, max)| (max - min) / 2.0)).as_vec3(),
+        })
+    }
+
+    /// A cylinder: cylindrical faces of one axis and radius, capped by planes across the axis.
+    fn cylinder(faces: &[Face], points: &[DVec3], tolerance: f64) -> Option<StepCollider> {
+        let (axis, origin, radius) = faces.iter().find_map(|f| match f {
+            Face::Cylinder { axis, origin, radius } => Some((*axis, *origin, *radius)),
+            _ => None,
+        })?;
+        let span = span(points, axis);
+
+        let mut caps = 0;
+        for face in faces {
+            match face {
+                Face::Cylinder {
+                    axis: other_axis,
+                    origin: other_origin,
+                    radius: other_radius,
+                } => {
+                    let offset = *other_origin - origin;
+                    let off_axis = (offset - axis * offset.dot(axis)).length();
+                    if !parallel(axis, *other_axis) || off_axis > tolerance || (radius - other_radius).abs() > tolerance {
+                        return None;
+                    }
+                }
+                Face::Plane { normal, offset } => {
+                    if !parallel(axis, *normal) || !at_end(*offset, *normal, axis, span, tolerance) {
+                        return None;
+                    }
+                    caps += 1;
+                }
+            }
+        }
+        // Without both caps it's a tube, or not closed at all
+        if caps < 2 {
+            return None;
+        }
+
+        let (min, max) = span;
+        let center = origin - axis * origin.dot(axis) + axis * (min + max) / 2.0;
+        let (x, z) = axis.any_orthonormal_pair();
+        Some(StepCollider::Cylinder {
+            center: center.as_vec3(),
+            rotation: rotation(x, axis, z),
+            radius: radius as f32,
+            half_height: ((max - min) / 2.0) as f32,
+        })
+    }
+}
+
+/// Convex hulls, by quickhull.
+#[cfg(feature = "colliders")]
+mod hull {
+    use std::collections::{HashMap, HashSet};
+
+    use bevy_math::{DVec3, Vec3};
+
+    use super::StepCollider;
+
+    /// Points closer to a face than this share of the points' size count as on it.
+    const FLAT: f64 = 1e-7;
+    /// More points than this left after dropping the ones inside are thinned out on a grid.
+    const MAX_POINTS: usize = 1024;
+    /// Cells along each side of that grid.
+    const GRID: f64 = 12.0;
+
+    struct Face {
+        corners: [usize; 3],
+        normal: DVec3,
+        offset: f64,
+        /// Points above this face that no other face has claimed yet.
+        outside: Vec<usize>,
+    }
+
+    impl Face {
+        /// The face through `corners`, wound so `inside` is below it.
+        fn new(points: &[DVec3], [a, b, c]: [usize; 3], inside: DVec3) -> Face {
+            let mut corners = [a, b, c];
+            let mut normal = (points[b] - points[a]).cross(points[c] - points[a]).normalize_or_zero();
+            if normal.dot(inside - points[a]) > 0.0 {
+                corners = [a, c, b];
+                normal = -normal;
+            }
+            Face {
+                corners,
+                normal,
+                offset: normal.dot(points[a]),
+                outside: Vec::new(),
+            }
+        }
+
+        fn distance(&self, p: DVec3) -> f64 {
+            self.normal.dot(p) - self.offset
+        }
+    }
+
+    /// The hull's triangles, wound outwards, as indices into `points`. `None` when the points
+    /// are flat, or too few to have a hull.
+    ///
+    /// Curved solids tessellate to thousands of points on their hull, so past [`MAX_POINTS`]
+    /// the hull is of one point per grid cell, the one furthest out, and can sit up to a cell
+    /// inside the exact one.
+    pub(super) fn convex_hull(points: &[DVec3]) -> Option<Vec<[usize; 3]>> {
+        let kept = candidates(points);
+        let subset: Vec<DVec3> = kept.iter().map(|&i| points[i]).collect();
+        let triangles = quickhull(&subset)?;
+        Some(triangles.into_iter().map(|t| t.map(|i| kept[i])).collect())
+    }
+
+    /// The points that might be on the hull: the ones outside the hull of the extreme points
+    /// along 13 axes, thinned out on a grid if there are still too many.
+    fn candidates(points: &[DVec3]) -> Vec<usize> {
+        let all: Vec<usize> = (0..points.len()).collect();
+        if points.len() <= MAX_POINTS {
+            return all;
+        }
+
+        // Anything inside the hull of the extremes is inside the hull of everything
+        let mut extremes: Vec<usize> = [-1.0, 0.0, 1.0]
+            .into_iter()
+            .flat_map(|x| [-1.0, 0.0, 1.0].map(|y| DVec3::new(x, y, 1.0)))
+            .chain([DVec3::X, DVec3::Y, DVec3::new(1.0, 1.0, 0.0), DVec3::new(1.0, -1.0, 0.0)])
+            .flat_map(|axis| {
+                let along = |&i: &usize| points[i].dot(axis);
+                [
+                    all.iter().copied().min_by(|a, b| along(a).total_cmp(&along(b))),
+                    all.iter().copied().max_by(|a, b| along(a).total_cmp(&along(b))),
+                ]
+            })
+            .flatten()
+            .collect();
+        extremes.sort_unstable();
+        extremes.dedup();
+        let corners: Vec<DVec3> = extremes.iter().map(|&i| points[i]).collect();
+        let mut kept: Vec<usize> = match quickhull(&corners) {
+            Some(triangles) => {
+                let planes: Vec<(DVec3, f64)> = triangles
+                    .iter()
+                    .map(|&[a, b, c]| {
+                        let normal = (corners[b] - corners[a]).cross(corners[c] - corners[a]).normalize_or_zero();
+                        (normal, normal.dot(corners[a]))
+                    })
+                    .collect();
+                all.into_iter()
+                    .filter(|&i| planes.iter().any(|(normal, offset)| normal.dot(points[i]) >= *offset))
+                    .chain(extremes)
+                    .collect()
+            }
+            None => all,
+        };
+        kept.sort_unstable();
+        kept.dedup();
+        if kept.len() <= MAX_POINTS {
+            return kept;
+        }
+
+        // Keep the point of each cell furthest from the middle, which is the one nearest the hull
+        let (min, max) = kept
+            .iter()
+            .fold((DVec3::INFINITY, DVec3::NEG_INFINITY), |(min, max), &i| (min.min(points[i]), max.max(points[i])));
+        let middle = (min + max) / 2.0;
+        let cell = ((max - min) / GRID).max(DVec3::splat(f64::MIN_POSITIVE));
+        let mut cells: HashMap<[i64; 3], usize> = HashMap::new();
+        for i in kept {
+            let key = ((points[i] - min) / cell).floor().as_i64vec3().to_array();
+            let best = cells.entry(key).or_insert(i);
+            if points[i].distance_squared(middle) > points[*best].distance_squared(middle) {
+                *best = i;
+            }
+        }
+        let mut kept: Vec<usize> = cells.into_values().collect();
+        kept.sort_unstable();
+        kept
+    }
+
+    /// [`convex_hull`] of every one of `points`.
+    fn quickhull(points: &[DVec3]) -> Option<Vec<[usize; 3]>> {
+        if points.len() < 4 {
+            return None;
+        }
+        let (min, max) = points
+            .iter()
+            .fold((DVec3::INFINITY, DVec3::NEG_INFINITY), |(min, max), p| (min.min(*p), max.max(*p)));
+        let epsilon = (max - min).length() * FLAT;
+        if epsilon <= 0.0 {
+            return None;
+        }
+
+        // Start from the biggest tetrahedron that's quick to find
+        let farthest = |from: &dyn Fn(DVec3) -> f64| {
+            (0..points.len()).max_by(|&a, &b| from(points[a]).total_cmp(&from(points[b])))
+        };
+        let extremes: Vec<usize> = (0..3)
+            .flat_map(|axis| {
+                [
+                    farthest(&|p: DVec3| -p[axis]).unwrap_or(0),
+                    farthest(&|p: DVec3| p[axis]).unwrap_or(0),
+                ]
+            })
+            .collect();
+        let (a, b) = extremes
+            .iter()
+            .flat_map(|&a| extremes.iter().map(move |&b| (a, b)))
+            .max_by(|x, y| points[x.0].distance(points[x.1]).total_cmp(&points[y.0].distance(points[y.1])))?;
+        let line = (points[b] - points[a]).normalize_or_zero();
+        let c = farthest(&|p: DVec3| (p - points[a]).reject_from_normalized(line).length())?;
+        if (points[c] - points[a]).reject_from_normalized(line).length() < epsilon {
+            return None;
+        }
+        let plane = line.cross(points[c] - points[a]).normalize_or_zero();
+        let d = farthest(&|p: DVec3| (p - points[a]).dot(plane).abs())?;
+        if (points[d] - points[a]).dot(plane).abs() < epsilon {
+            return None;
+        }
+
+        let inside = (points[a] + points[b] + points[c] + points[d]) / 4.0;
+        let mut faces: Vec<Face> = [[a, b, c], [a, b, d], [a, c, d], [b, c, d]]
+            .into_iter()
+            .map(|corners| Face::new(points, corners, inside))
+            .collect();
+        assign(points, &mut faces, 0..points.len(), epsilon);
+
+        while let Some(next) = faces.iter().position(|f| !f.outside.is_empty()) {
+            let eye = faces[next]
+                .outside
+                .iter()
+                .copied()
+                .max_by(|&p, &q| faces[next].distance(points[p]).total_cmp(&faces[next].distance(points[q])))?;
+            let eye_point = points[eye];
+
+            let (visible, kept): (Vec<Face>, Vec<Face>) =
+                faces.into_iter().partition(|f| f.distance(eye_point) > epsilon);
+            faces = kept;
+
+            // The horizon is the edges of the visible faces that only one of them has
+            let edges: HashSet<(usize, usize)> = visible
+                .iter()
+                .flat_map(|f| {
+                    let [a, b, c] = f.corners;
+                    [(a, b), (b, c), (c, a)]
+                })
+                .collect();
+            let first_new = faces.len();
+            for &(a, b) in &edges {
+                if !edges.contains(&(b, a)) {
+                    faces.push(Face::new(points, [a, b, eye], inside));
+                }
+            }
+
+            let orphans: Vec<usize> = visible.into_iter().flat_map(|f| f.outside).filter(|&p| p != eye).collect();
+            assign(points, &mut faces[first_new..], orphans, epsilon);
+        }
+
+        Some(faces.into_iter().map(|f| f.corners).collect())
+    }
+
+    /// Gives each point to the first face it lies above, dropping the ones inside.
+    fn assign(points: &[DVec3], faces: &mut [Face], candidates: impl IntoIterator<Item = usize>, epsilon: f64) {
+        for p in candidates {
+            if let Some(face) = faces.iter_mut().find(|f| f.distance(points[p]) > epsilon) {
+                face.outside.push(p);
+            }
+        }
+    }
+
+    /// The volume of the hull of `points`, zero if they're flat.
+    pub(super) fn volume(points: &[DVec3]) -> f64 {
+        let Some(triangles) = convex_hull(points) else { return 0.0 };
+        let origin = points[triangles[0][0]];
+        triangles
+            .iter()
+            .map(|&[a, b, c]| (points[a] - origin).dot((points[b] - origin).cross(points[c] - origin)) / 6.0)
+            .sum()
+    }
+
+    /// A [`StepCollider::ConvexHull`] of `points`, with just the points the hull uses.
+    pub(super) fn collider(points: &[DVec3]) -> StepCollider {
+        let Some(triangles) = convex_hull(points) else {
+            return StepCollider::ConvexHull {
+                points: points.iter().map(|p| p.as_vec3()).collect(),
+                indices: Vec::new(),
+            };
+        };
+        let mut remap: HashMap<usize, u32> = HashMap::new();
+        let mut used: Vec<Vec3> = Vec::new();
+        let indices = triangles
+            .iter()
+            .map(|triangle| {
+                triangle.map(|v| {
+                    *remap.entry(v).or_insert_with(|| {
+                        used.push(points[v].as_vec3());
+                        used.len() as u32 - 1
+                    })
+                })
+            })
+            .collect();
+        StepCollider::ConvexHull { points: used, indices }
+    }
+}
+
+/// Splitting a solid into pieces that are each close to convex.
+#[cfg(feature = "colliders")]
+mod decompose {
+    use std::collections::HashSet;
+
+    use bevy_math::{DMat3, DVec3};
+
+    use super::hull;
+    use crate::bounds::principal_axes;
+
+    /// Where along each axis a piece may be cut, as shares of its extent.
+    const CUTS: [f64; 3] = [0.25, 0.5, 0.75];
+
+    struct Piece {
+        triangles: Vec<[DVec3; 3]>,
+        points: Vec<DVec3>,
+        volume: f64,
+        /// Whether splitting it was tried and didn't help.
+        done: bool,
+    }
+
+    impl Piece {
+        fn new(triangles: Vec<[DVec3; 3]>) -> Piece {
+            // Neighbouring triangles share corners, the hull only needs each once
+            let mut seen = HashSet::new();
+            let points: Vec<DVec3> = triangles
+                .iter()
+                .flatten()
+                .copied()
+                .filter(|p| seen.insert(p.to_array().map(f64::to_bits)))
+                .collect();
+            Piece {
+                volume: hull::volume(&points),
+                triangles,
+                points,
+                done: false,
+            }
+        }
+    }
+
+    /// The point sets of convex pieces covering `triangles`, at most `max_pieces` of them.
+    pub(super) fn decompose(triangles: Vec<[DVec3; 3]>, max_pieces: usize, min_gain: f64) -> Vec<Vec<DVec3>> {
+        let mut pieces = vec![Piece::new(triangles)];
+        while pieces.len() < max_pieces {
+            // Work on the biggest piece that might still improve
+            let Some(index) = pieces
+                .iter()
+                .enumerate()
+                .filter(|(_, p)| !p.done)
+                .max_by(|(_, a), (_, b)| a.volume.total_cmp(&b.volume))
+                .map(|(i, _)| i)
+            else {
+                break;
+            };
+            let volume = pieces[index].volume;
+            match best_split(&pieces[index]) {
+                Some((below, above)) if volume - below.volume - above.volume >= min_gain * volume => {
+                    pieces.swap_remove(index);
+                    pieces.push(below);
+                    pieces.push(above);
+                }
+                _ => pieces[index].done = true,
+            }
+        }
+        pieces.into_iter().map(|p| p.points).collect()
+    }
+
+    /// The split across one of the piece's principal axes leaving the least hull volume.
+    fn best_split(piece: &Piece) -> Option<(Piece, Piece)> {
+        if piece.points.len() < 8 {
+            return None;
+        }
+        let mean = piece.points.iter().sum::<DVec3>() / piece.points.len() as f64;
+        let covariance = piece.points.iter().fold(DMat3::ZERO, |sum, p| {
+            let d = *p - mean;
+            sum + DMat3::from_cols(d * d.x, d * d.y, d * d.z)
+        });
+        let axes = principal_axes(covariance);
+
+        let mut best: Option<(Piece, Piece)> = None;
+        for axis in [axes.x_axis, axes.y_axis, axes.z_axis] {
+            let (min, max) = piece
+                .points
+                .iter()
+                .map(|p| p.dot(axis))
+                .fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), d| (min.min(d), max.max(d)));
+            for cut in CUTS {
+                let (below, above) = split(&piece.triangles, axis, min + (max - min) * cut);
+                if below.is_empty() || above.is_empty() {
+                    continue;
+                }
+                let (below, above) = (Piece::new(below), Piece::new(above));
+                let better = best
+                    .as_ref()
+                    .is_none_or(|(b, a)| below.volume + above.volume < b.volume + a.volume);
+                if better {
+                    best = Some((below, above));
+                }
+            }
+        }
+        best
+    }
+
+    /// The triangles on each side of the plane `offset` along `normal`, cut where they cross it.
+    fn split(triangles: &[[DVec3; 3]], normal: DVec3, offset: f64) -> (Vec<[DVec3; 3]>, Vec<[DVec3; 3]>) {
+        let mut below = Vec::new();
+        let mut above = Vec::new();
+        for triangle in triangles {
+            let distances = triangle.map(|p| p.dot(normal) - offset);
+            if distances.iter().all(|&d| d <= 0.0) {
+                below.push(*triangle);
+            } else if distances.iter().all(|&d| d >= 0.0) {
+                above.push(*triangle);
+            } else {
+                clip(triangle, distances, 1.0, &mut below);
+                clip(triangle, distances, -1.0, &mut above);
+            }
+        }
+        (below, above)
+    }
+
+    /// The part of `triangle` where `side * distance <= 0`, as triangles.
+    fn clip(triangle: &[DVec3; 3], distances: [f64; 3], side: f64, out: &mut Vec<[DVec3; 3]>) {
+        let mut polygon: Vec<DVec3> = Vec::with_capacity(4);
+        for i in 0..3 {
+            let j = (i + 1) % 3;
+            let (di, dj) = (distances[i] * side, distances[j] * side);
+            if di <= 0.0 {
+                polygon.push(triangle[i]);
+            }
+            if (di < 0.0 && dj > 0.0) || (di > 0.0 && dj < 0.0) {
+                polygon.push(triangle[i].lerp(triangle[j], di / (di - dj)));
+            }
+        }
+        for k in 1..polygon.len().saturating_sub(1) {
+            out.push([polygon[0], polygon[k], polygon[k + 1]]);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "colliders"))]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use bevy_math::{DAffine3, DQuat, DVec3, Vec3};
+
+    use super::StepCollider;
+    use super::decompose::decompose;
+    use super::fit::primitive;
+    use super::hull::{convex_hull, volume};
+    use crate::UpAxis;
+    use crate::brep::{Bounds, Brep, Face, Solid, Surface, SurfaceKind};
+
+    /// A B-rep of one solid, from each face's surface kind, axis and a point on its axis.
+    fn one_solid(faces: &[(SurfaceKind, DVec3, DVec3)]) -> (Brep, Solid) {
+        let faces: HashMap<u64, Face> = faces
+            .iter()
+            .zip(1..)
+            .map(|((kind, axis, origin), id)| {
+                let frame = DAffine3::from_rotation_translation(DQuat::from_rotation_arc(DVec3::Z, *axis), *origin);
+                let surface = Surface {
+                    kind: kind.clone(),
+                    frame,
+                    inverse: frame.inverse(),
+                };
+                let face = Face {
+                    surface,
+                    surface_type: String::new(),
+                    bounds: Bounds::EMPTY,
+                };
+                (id, face)
+            })
+            .collect();
+        let mut ids: Vec<u64> = faces.keys().copied().collect();
+        ids.sort_unstable();
+        let solid = Solid {
+            name: "solid".to_string(),
+            faces: ids,
+        };
+        (Brep { solids: HashMap::new(), faces }, solid)
+    }
+
+    /// Every edge of the hull is walked once each way, so it's closed and consistently wound.
+    fn assert_closed(triangles: &[[usize; 3]]) {
+        let edges: HashSet<(usize, usize)> =
+            triangles.iter().flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)]).collect();
+        assert_eq!(edges.len(), triangles.len() * 3);
+        assert!(edges.iter().all(|&(a, b)| edges.contains(&(b, a))));
+    }
+
+    #[test]
+    fn hull_of_a_point_cloud() {
+        // A unit cube's corners around points inside it
+        let mut points: Vec<DVec3> = (0..8)
+            .map(|i| DVec3::new((i & 1) as f64, (i >> 1 & 1) as f64, (i >> 2 & 1) as f64))
+            .collect();
+        points.extend((1..100).map(|i| DVec3::new(i as f64 * 0.37 % 1.0, i as f64 * 0.61 % 1.0, i as f64 * 0.83 % 1.0)));
+
+        let triangles = convex_hull(&points).unwrap();
+        assert_closed(&triangles);
+        assert!(triangles.iter().flatten().all(|&v| v < 8));
+        assert!((volume(&points) - 1.0).abs() < 1e-9);
+
+        // Every point is on or below every face
+        for &[a, b, c] in &triangles {
+            let normal = (points[b] - points[a]).cross(points[c] - points[a]).normalize();
+            assert!(points.iter().all(|p| normal.dot(*p - points[a]) < 1e-9));
+        }
+    }
+
+    #[test]
+    fn dense_clouds_are_thinned_first() {
+        // A unit cube's surface sampled far more finely than the hull needs
+        let steps = 40;
+        let mut points = Vec::new();
+        for i in 0..=steps {
+            for j in 0..=steps {
+                let (u, v) = (i as f64 / steps as f64, j as f64 / steps as f64);
+                for w in [0.0, 1.0] {
+                    points.extend([DVec3::new(u, v, w), DVec3::new(u, w, v), DVec3::new(w, u, v)]);
+                }
+            }
+        }
+        let triangles = convex_hull(&points).unwrap();
+        assert_closed(&triangles);
+        assert!((volume(&points) - 1.0).abs() < 1e-9);
+
+        // On a sphere every point is on the hull, so the grid has to cap them
+        let n = 5000;
+        let sphere: Vec<DVec3> = (0..n)
+            .map(|i| {
+                let z = 1.0 - 2.0 * (i as f64 + 0.5) / n as f64;
+                let angle = i as f64 * std::f64::consts::PI * (3.0 - 5f64.sqrt());
+                let r = (1.0 - z * z).sqrt();
+                DVec3::new(r * angle.cos(), r * angle.sin(), z)
+            })
+            .collect();
+        let triangles = convex_hull(&sphere).unwrap();
+        assert_closed(&triangles);
+        let used: HashSet<usize> = triangles.iter().flatten().copied().collect();
+        assert!(used.len() <= 1024);
+        let ball = 4.0 / 3.0 * std::f64::consts::PI;
+        assert!((volume(&sphere) - ball).abs() < 0.05 * ball);
+    }
+
+    #[test]
+    fn fits_a_box() {
+        // 2 by 4 by 6 around (1, 2, 3)
+        let center = DVec3::new(1.0, 2.0, 3.0);
+        let half = DVec3::new(1.0, 2.0, 3.0);
+        let sides: Vec<(SurfaceKind, DVec3, DVec3)> = [DVec3::X, DVec3::NEG_X, DVec3::Y, DVec3::NEG_Y, DVec3::Z, DVec3::NEG_Z]
+            .into_iter()
+            .map(|normal| (SurfaceKind::Plane, normal, center + normal * half.dot(normal.abs())))
+            .collect();
+        let corners: Vec<DVec3> = (0..8)
+            .map(|i| center + half * DVec3::new([-1.0, 1.0][i & 1], [-1.0, 1.0][i >> 1 & 1], [-1.0, 1.0][i >> 2 & 1]))
+            .collect();
+
+        let (brep, solid) = one_solid(&sides);
+        let Some(StepCollider::Cuboid {
+            center: fitted,
+            rotation,
+            half_extents,
+        }) = primitive(&solid, &brep, &DAffine3::IDENTITY, &corners)
+        else {
+            panic!("not a box");
+        };
+        assert!(fitted.abs_diff_eq(center.as_vec3(), 1e-5));
+        assert!(half_extents.abs_diff_eq(half.as_vec3(), 1e-5));
+        assert!((rotation * Vec3::X).abs_diff_eq(Vec3::X, 1e-5));
+
+        // A plane through the middle makes a step or a slot, not a box
+        let mut stepped = sides.clone();
+        stepped.push((SurfaceKind::Plane, DVec3::X, center));
+        let (brep, solid) = one_solid(&stepped);
+        assert_eq!(primitive(&solid, &brep, &DAffine3::IDENTITY, &corners), None);
+    }
+
+    #[test]
+    fn fits_a_cylinder() {
+        // Radius 1 along the file's Z from 0 to 2, in a Z-up file loaded at twice the size
+        let to_mesh = DAffine3::from_mat3(UpAxis::ZUp.basis() * 2.0);
+        let faces = [
+            (SurfaceKind::Cylinder { radius: 1.0 }, DVec3::Z, DVec3::ZERO),
+            (SurfaceKind::Plane, DVec3::NEG_Z, DVec3::ZERO),
+            (SurfaceKind::Plane, DVec3::Z, DVec3::new(0.0, 0.0, 2.0)),
+        ];
+        let points: Vec<DVec3> = (0..32)
+            .flat_map(|i| {
+                let angle = i as f64 * std::f64::consts::TAU / 32.0;
+                [0.0, 2.0].map(|z| to_mesh.transform_point3(DVec3::new(angle.cos(), angle.sin(), z)))
+            })
+            .collect();
+
+        let (brep, solid) = one_solid(&faces);
+        let Some(StepCollider::Cylinder {
+            center,
+            rotation,
+            radius,
+            half_height,
+        }) = primitive(&solid, &brep, &to_mesh, &points)
+        else {
+            panic!("not a cylinder");
+        };
+        // The file's Z is the mesh's Y, which is the cylinder's own axis too
+        assert!(center.abs_diff_eq(Vec3::new(0.0, 2.0, 0.0), 1e-5));
+        assert!((rotation * Vec3::Y).abs().abs_diff_eq(Vec3::Y, 1e-5));
+        assert!((radius - 2.0).abs() < 1e-5);
+        assert!((half_height - 2.0).abs() < 1e-5);
+
+        // Without a cap it's a tube
+        let (brep, solid) = one_solid(&faces[..2]);
+        assert_eq!(primitive(&solid, &brep, &to_mesh, &points), None);
+    }
+
+    #[test]
+    fn fits_a_plane() {
+        let (brep, solid) = one_solid(&[(SurfaceKind::Plane, DVec3::Z, DVec3::ZERO)]);
+        let square = [DVec3::ZERO, DVec3::X, DVec3::new(1.0, 1.0, 0.0), DVec3::Y];
+        let Some(StepCollider::Plane { center, rotation, half_size }) =
+            primitive(&solid, &brep, &DAffine3::IDENTITY, &square)
+        else {
+            panic!("not a plane");
+        };
+        assert!(center.abs_diff_eq(Vec3::new(0.5, 0.5, 0.0), 1e-5));
+        assert!((rotation * Vec3::Y).abs_diff_eq(Vec3::Z, 1e-5));
+        assert!(half_size.abs_diff_eq(bevy_math::Vec2::splat(0.5), 1e-5));
+    }
+
+    /// An L, the unit squares at (0, 0), (1, 0) and (0, 1) one deep, as triangles.
+    fn l_shape() -> Vec<[DVec3; 3]> {
+        let outline = [(0.0, 0.0), (2.0, 0.0), (2.0, 1.0), (1.0, 1.0), (1.0, 2.0), (0.0, 2.0)];
+        let at = |i: usize, z: f64| DVec3::new(outline[i % 6].0, outline[i % 6].1, z);
+        let mut triangles = Vec::new();
+        for i in 0..6 {
+            triangles.push([at(i, 0.0), at(i + 1, 0.0), at(i + 1, 1.0)]);
+            triangles.push([at(i, 0.0), at(i + 1, 1.0), at(i, 1.0)]);
+        }
+        // Both ends fan out from the inside corner, which sees the whole outline
+        for z in [0.0, 1.0] {
+            for i in [4, 5, 0, 1] {
+                triangles.push([at(3, z), at(i, z), at(i + 1, z)]);
+            }
+        }
+        triangles
+    }
+
+    #[test]
+    fn decomposes_an_l_into_two_convex_pieces() {
+        let pieces = decompose(l_shape(), 16, 0.05);
+        assert_eq!(pieces.len(), 2);
+        // Cut along the diagonal into two convex halves, which together are the L exactly
+        for piece in &pieces {
+            assert!((volume(piece) - 1.5).abs() < 1e-9);
+        }
+        let points: HashSet<[u64; 3]> = pieces.iter().flatten().map(|p| p.to_array().map(f64::to_bits)).collect();
+        assert!(points.contains(&DVec3::new(1.0, 1.0, 0.0).to_array().map(f64::to_bits)));
+
+        // Limited to one piece, or asked for more gain than a split gives, it's the hull
+        for pieces in [decompose(l_shape(), 1, 0.05), decompose(l_shape(), 16, 0.5)] {
+            assert_eq!(pieces.len(), 1);
+            assert!((volume(&pieces[0]) - 3.5).abs() < 1e-9);
+        }
+    }
+
+    #[test]
+    fn convex_solids_stay_whole() {
+        let cube: Vec<[DVec3; 3]> = l_shape()
+            .into_iter()
+            .map(|t| t.map(|p| p.min(DVec3::new(1.0, 1.0, 1.0))))
+            .collect();
+        assert_eq!(decompose(cube, 16, 0.01).len(), 1);
+    }
+}
@@ -0,0 +1,244 @@
+//! The B-rep's edges as lines, for CAD style outlines.
+//!
+//! ```text
+//! ADVANCED_FACE -> FACE_BOUND -> EDGE_LOOP -> ORIENTED_EDGE -> EDGE_CURVE -> LINE | CIRCLE | ELLIPSE
+//!     | B_SPLINE_CURVE_WITH_KNOTS | POLYLINE | SURFACE_CURVE | TRIMMED_CURVE
+//! ```
+//!
+//! Each edge is drawn once, from its start vertex to its end vertex along its curve. Seams,
+//! where a closed surface meets itself, aren't edges anyone drew, so they're left out.
+
+use std::collections::{HashMap, HashSet};
+use std::f64::consts::TAU;
+
+use bevy_asset::RenderAssetUsages;
+use bevy_math::{DAffine3, DVec3, DVec4};
+use bevy_mesh::{Indices, Mesh};
+use wgpu_types::PrimitiveTopology;
+
+use crate::StepLoaderSettings;
+use crate::assembly::Assembly;
+use crate::brep::{Brep, face_edges, placement, point, vertex_point};
+use crate::part21::StepData;
+
+/// Most segments any one curve is split into.
+const MAX_SEGMENTS: usize = 512;
+
+/// Angle (radians) between segments of curved edges when no angular tolerance is set.
+const DEFAULT_STEP: f64 = 0.5;
+
+/// How deep we follow curves defined on other curves.
+const MAX_DEPTH: usize = 8;
+
+/// Every edge of every placed solid as a `LineList` mesh, in the same frame and units as
+/// [`StepAsset::mesh`](crate::StepAsset::mesh).
+pub(crate) fn build(data: &StepData, brep: &Brep, assembly: &Assembly, settings: &StepLoaderSettings) -> Mesh {
+    let basis = settings.up_axis.basis() * settings.scale as f64;
+    let mut polylines: HashMap<u64, Option<Vec<DVec3>>> = HashMap::new();
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    for node in &assembly.nodes {
+        let transform = DAffine3::from_mat3(basis) * node.world;
+        for solid in node.solids.iter().filter_map(|s| brep.solids.get(s)) {
+            let mut seen = HashSet::new();
+            for edge in solid.faces.iter().flat_map(|face| face_edges(data, *face)) {
+                if !seen.insert(edge) {
+                    continue;
+                }
+                let polyline = polylines.entry(edge).or_insert_with(|| tessellate(data, edge, settings));
+                let Some(polyline) = polyline else { continue };
+
+                let first = positions.len() as u32;
+                positions.extend(polyline.iter().map(|p| transform.transform_point3(*p).as_vec3().to_array()));
+                for i in 1..polyline.len() as u32 {
+                    indices.extend([first + i - 1, first + i]);
+                }
+            }
+        }
+    }
+
+    let mut mesh = Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::all());
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+    mesh.insert_indices(Indices::U32(indices));
+    mesh
+}
+
+/// An `EDGE_CURVE` as points in file space, `None` if it shouldn't be drawn.
+fn tessellate(data: &StepData, edge_id: u64, settings: &StepLoaderSettings) -> Option<Vec<DVec3>> {
+    let edge = data.record(edge_id, "EDGE_CURVE")?;
+    let start = vertex_point(data, edge.param(1).as_id()?)?;
+    let end = vertex_point(data, edge.param(2).as_id()?)?;
+    let same_sense = edge.param(4).as_enum() != Some("F");
+    let Some(curve) = edge.param(3).as_id() else {
+        return Some(vec![start, end]);
+    };
+    let edge = Edge { start, end, same_sense };
+    curve_points(data, curve, &edge, settings, 0)
+}
+
+/// Where an edge starts and ends on its curve.
+struct Edge {
+    start: DVec3,
+    end: DVec3,
+    /// Whether the edge runs the same way as the curve's parameter.
+    same_sense: bool,
+}
+
+fn curve_points(data: &StepData, id: u64, edge: &Edge, settings: &StepLoaderSettings, depth: usize) -> Option<Vec<DVec3>> {
+    let straight = Some(vec![edge.start, edge.end]);
+    let Some(inst) = data.get(id) else { return straight };
+    if depth > MAX_DEPTH {
+        return straight;
+    }
+
+    if inst.is("SEAM_CURVE") {
+        None
+    } else if let Some(curve) = inst.record("SURFACE_CURVE").or_else(|| inst.record("TRIMMED_CURVE")) {
+        match curve.param(1).as_id() {
+            Some(basis) => curve_points(data, basis, edge, settings, depth + 1),
+            None => straight,
+        }
+    } else if let Some(circle) = inst.record("CIRCLE") {
+        let frame = circle.param(1).as_id().and_then(|p| placement(data, p));
+        let r = circle.param(2).as_f64();
+        match (frame, r) {
+            (Some(frame), Some(r)) => Some(arc(&frame, r, r, edge, settings)),
+            _ => straight,
+        }
+    } else if let Some(ellipse) = inst.record("ELLIPSE") {
+        let frame = ellipse.param(1).as_id().and_then(|p| placement(data, p));
+        match (frame, ellipse.param(2).as_f64(), ellipse.param(3).as_f64()) {
+            (Some(frame), Some(a), Some(b)) => Some(arc(&frame, a, b, edge, settings)),
+            _ => straight,
+        }
+    } else if let Some(polyline) = inst.record("POLYLINE") {
+        let points: Vec<DVec3> = polyline.param(1).refs().filter_map(|p| point(data, p)).collect();
+        Some(trimmed(points, edge))
+    } else if let Some(points) = bspline_points(data, id) {
+        Some(trimmed(points, edge))
+    } else {
+        // Lines, and any curve we don't know, go straight from vertex to vertex
+        straight
+    }
+}
+
+/// The part of an ellipse (a circle when `a == b`) that the edge covers.
+fn arc(frame: &DAffine3, a: f64, b: f64, edge: &Edge, settings: &StepLoaderSettings) -> Vec<DVec3> {
+    let inverse = frame.inverse();
+    let angle = |p: DVec3| {
+        let q = inverse.transform_point3(p);
+        (q.y / b).atan2(q.x / a)
+    };
+    let (from, to) = if edge.same_sense {
+        (angle(edge.start), angle(edge.end))
+    } else {
+        (angle(edge.end), angle(edge.start))
+    };
+    let mut sweep = (to - from).rem_euclid(TAU);
+    // Closed edges start and end on the same vertex and go all the way round
+    if sweep < 1e-9 {
+        sweep = TAU;
+    }
+
+    // Keep within both tolerances, the chord one only means anything below the radius
+    let radius = a.max(b);
+    let mut step = settings.angular_tolerance.unwrap_or(DEFAULT_STEP).max(1e-3);
+    if let Some(chord) = settings.chord_tolerance.filter(|&chord| chord > 0.0 && chord < radius) {
+        step = step.min(2.0 * (1.0 - chord / radius).acos());
+    }
+    let segments = ((sweep / step).ceil() as usize).clamp(1, MAX_SEGMENTS);
+
+    (0..=segments)
+        .map(|i| {
+            let t = from + sweep * i as f64 / segments as f64;
+            frame.transform_point3(DVec3::new(a * t.cos(), b * t.sin(), 0.0))
+        })
+        .collect()
+}
+
+/// The stretch of `points` between the samples nearest the edge's ends, with the ends
+/// themselves put back exactly.
+fn trimmed(points: Vec<DVec3>, edge: &Edge) -> Vec<DVec3> {
+    if points.len() < 2 {
+        return vec![edge.start, edge.end];
+    }
+    let nearest = |target: DVec3| {
+        (0..points.len())
+            .min_by(|&i, &j| points[i].distance_squared(target).total_cmp(&points[j].distance_squared(target)))
+            .unwrap_or_default()
+    };
+    let (first, last) = (nearest(edge.start), nearest(edge.end));
+    let inner = match first.cmp(&last) {
+        // A closed curve, the edge is all of it
+        std::cmp::Ordering::Equal => &points[1..points.len() - 1],
+        std::cmp::Ordering::Less => &points[first + 1..last],
+        std::cmp::Ordering::Greater => &points[last + 1..first],
+    };
+    let mut out = Vec::with_capacity(inner.len() + 2);
+    out.push(edge.start);
+    if first <= last {
+        out.extend_from_slice(inner);
+    } else {
+        out.extend(inner.iter().rev());
+    }
+    out.push(edge.end);
+    out
+}
+
+/// Samples along a `B_SPLINE_CURVE_WITH_KNOTS`, rational or not, over its whole range.
+fn bspline_points(data: &StepData, id: u64) -> Option<Vec<DVec3>> {
+    let inst = data.get(id)?;
+    // A simple instance has everything in one record after the name, a complex one (rational
+    // curves) splits it between records without names
+    let (degree, points, multiplicities, knots, weights) = match inst.record("B_SPLINE_CURVE_WITH_KNOTS") {
+        Some(record) if inst.records.len() == 1 => (record.param(1), record.param(2), record.param(6), record.param(7), None),
+        Some(record) => {
+            let curve = inst.record("B_SPLINE_CURVE")?;
+            let weights = inst.record("RATIONAL_B_SPLINE_CURVE").map(|r| r.param(0));
+            (curve.param(0), curve.param(1), record.param(0), record.param(1), weights)
+        }
+        None => return None,
+    };
+
+    let degree = degree.as_f64()? as usize;
+    let points: Vec<DVec3> = points.refs().map(|p| point(data, p)).collect::<Option<_>>()?;
+    let weights: Vec<f64> = match weights.and_then(|w| w.as_list()) {
+        Some(weights) => weights.iter().map(|w| w.as_f64()).collect::<Option<_>>()?,
+        None => vec![1.0; points.len()],
+    };
+    let mut expanded = Vec::new();
+    for (m, k) in multiplicities.as_list()?.iter().zip(knots.as_list()?) {
+        let (m, k) = (m.as_f64()? as usize, k.as_f64()?);
+        expanded.extend(std::iter::repeat_n(k, m));
+    }
+    if degree == 0 || weights.len() != points.len() || expanded.len() != points.len() + degree + 1 {
+        return None;
+    }
+
+    let homogeneous: Vec<DVec4> = points.iter().zip(&weights).map(|(p, w)| (*p * *w).extend(*w)).collect();
+    let (t0, t1) = (expanded[degree], expanded[points.len()]);
+    let samples = (points.len() * 8).clamp(16, MAX_SEGMENTS);
+    Some(
+        (0..=samples)
+            .map(|i| de_boor(degree, &expanded, &homogeneous, t0 + (t1 - t0) * i as f64 / samples as f64))
+            .collect(),
+    )
+}
+
+/// Evaluates a B-spline at `t` with de Boor's algorithm, in homogeneous coordinates so
+/// rational curves come out right.
+fn de_boor(degree: usize, knots: &[f64], points: &[DVec4], t: f64) -> DVec3 {
+    let n = points.len();
+    let span = (degree..n).rev().find(|&i| knots[i] <= t).unwrap_or(degree);
+    let mut d: Vec<DVec4> = (0..=degree).map(|j| points[j + span - degree]).collect();
+    for r in 1..=degree {
+        for j in (r..=degree).rev() {
+            let i = j + span - degree;
+            let denominator = knots[i + degree + 1 - r] - knots[i];
+            let alpha = if denominator.abs() < 1e-12 { 0.0 } else { (t - knots[i]) / denominator };
+            d[j] = d[j - 1] * (1.0 - alpha) + d[j] * alpha;
+        }
+    }
+    d[degree].truncate() / d[degree].w
+}
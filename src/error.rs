@@ -0,0 +1,156 @@
+use crate::TriangulationBackend;
+
+/// A position in the STEP file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourceLocation {
+    /// 1-based line number.
+    pub line: usize,
+    /// 1-based column, in bytes.
+    pub column: usize,
+    /// Bytes from the start of the file.
+    pub offset: usize,
+}
+
+impl SourceLocation {
+    /// The location of byte `offset`, given where each line starts.
+    pub(crate) fn new(line_starts: &[usize], offset: usize) -> SourceLocation {
+        let line = line_starts.partition_point(|start| *start <= offset).max(1);
+        SourceLocation {
+            line,
+            column: offset - line_starts.get(line - 1).copied().unwrap_or(0) + 1,
+            offset,
+        }
+    }
+}
+
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// An entity instance in the file, `#42`, and where it's defined.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EntityRef {
+    pub id: u64,
+    /// `None` when the instance isn't in the file at all, a dangling reference.
+    pub location: Option<SourceLocation>,
+}
+
+impl std::fmt::Display for EntityRef {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.location {
+            Some(location) => write!(f, "#{} ({})", self.id, location),
+            None => write!(f, "#{}", self.id),
+        }
+    }
+}
+
+/// Where and why reading the file's structure stopped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SyntaxError {
+    pub location: SourceLocation,
+    pub message: String,
+}
+
+impl std::fmt::Display for SyntaxError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.location)
+    }
+}
+
+impl std::error::Error for SyntaxError {}
+
+#[derive(Debug)]
+pub enum StepLoaderError {
+    IoError(std::io::Error),
+    /// The file isn't valid ISO 10303-21.
+    Syntax(SyntaxError),
+    #[cfg(feature = "opencascade")]
+    OcctError(String),
+    BackendUnavailable(TriangulationBackend),
+    /// The backend panicked, which Foxtrot does on some malformed input.
+    BackendPanicked(TriangulationBackend),
+    /// The backend couldn't tessellate the geometry, `entity` and `surface` say what it was
+    /// working on when that's known.
+    TessellationFailed {
+        backend: TriangulationBackend,
+        entity: Option<EntityRef>,
+        surface: Option<String>,
+    },
+    /// The file's shape is made of something neither backend understands, e.g. the
+    /// tessellated geometry of AP242 (`TRIANGULATED_FACE_SET`).
+    UnsupportedEntity { entity: EntityRef, keyword: String },
+    /// The backend ran but produced no triangles, and there's no better explanation.
+    EmptyResult { backend: TriangulationBackend },
+    /// A unit conversion was asked for, the file doesn't declare its length unit and
+    /// [`StepLoaderSettings::assumed_unit`](crate::StepLoaderSettings::assumed_unit) is `None`.
+    UnitMissing,
+    /// A mesh passed in doesn't have what the operation needs, e.g. positions or indices.
+    InvalidMesh(&'static str),
+    /// The operation needs a cargo feature that isn't enabled.
+    FeatureDisabled(&'static str),
+}
+
+impl std::fmt::Display for StepLoaderError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StepLoaderError::IoError(e) => write!(f, "IO error: {}", e),
+            StepLoaderError::Syntax(e) => write!(f, "Syntax error: {}", e),
+            #[cfg(feature = "opencascade")]
+            StepLoaderError::OcctError(e) => write!(f, "OpenCASCADE error: {}", e),
+            StepLoaderError::BackendUnavailable(b) => {
+                write!(f, "Triangulation backend {:?} is not enabled in this build", b)
+            }
+            StepLoaderError::BackendPanicked(b) => write!(f, "Triangulation backend {:?} panicked", b),
+            StepLoaderError::TessellationFailed { backend, entity, surface } => {
+                write!(f, "{:?} failed to tessellate", backend)?;
+                if let Some(surface) = surface {
+                    write!(f, " a {}", surface)?;
+                }
+                if let Some(entity) = entity {
+                    write!(f, " at {}", entity)?;
+                }
+                Ok(())
+            }
+            StepLoaderError::UnsupportedEntity { entity, keyword } => {
+                write!(f, "Unsupported entity {} at {}", keyword, entity)
+            }
+            StepLoaderError::EmptyResult { backend } => write!(f, "{:?} produced no triangles", backend),
+            StepLoaderError::UnitMissing => write!(f, "The file doesn't declare a length unit"),
+            StepLoaderError::InvalidMesh(e) => write!(f, "Invalid mesh: {}", e),
+            StepLoaderError::FeatureDisabled(feature) => {
+                write!(f, "This needs the '{}' feature to be enabled", feature)
+            }
+        }
+    }
+}
+
+impl std::error::Error for StepLoaderError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            StepLoaderError::IoError(e) => Some(e),
+            StepLoaderError::Syntax(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for StepLoaderError {
+    fn from(error: std::io::Error) -> Self {
+        StepLoaderError::IoError(error)
+    }
+}
+
+impl From<SyntaxError> for StepLoaderError {
+    fn from(error: SyntaxError) -> Self {
+        StepLoaderError::Syntax(error)
+    }
+}
+
+#[cfg(feature = "opencascade")]
+impl From<String> for StepLoaderError {
+    fn from(error: String) -> Self {
+        StepLoaderError::OcctError(error)
+    }
+}
@@ -0,0 +1,410 @@
+//! Writing the loaded scene out as glTF 2.0, so a STEP file can be converted once and loaded
+//! with Bevy's own glTF loader from then on.
+//!
+//! Each [`StepPart`](crate::StepPart) becomes a node holding a mesh with a primitive per
+//! material, and all the vertex data goes in one buffer. The [`ATTRIBUTE_STEP_FACE`](crate::ATTRIBUTE_STEP_FACE)
+//! ids go along as the `_STEP_FACE` attribute, in floats as glTF has no 32 bit integer
+//! attributes, see [`ATTRIBUTE_STEP_FACE_GLTF`] for getting them back. Floats hold face ids
+//! exactly up to 2^24, and exporting a mesh with a larger one fails.
+
+use std::path::Path;
+
+use bevy_asset::Assets;
+use bevy_mesh::{Mesh, MeshVertexAttribute, VertexAttributeValues, VertexFormat};
+use bevy_pbr::StandardMaterial;
+
+use crate::{ATTRIBUTE_STEP_FACE, StepAsset, StepLoaderError};
+
+/// The `_STEP_FACE` attribute of an exported file, as Bevy's glTF loader reads it. Register it
+/// with `GltfPlugin::default().add_custom_vertex_attribute("_STEP_FACE", ATTRIBUTE_STEP_FACE_GLTF)`
+/// and turn it back into [`ATTRIBUTE_STEP_FACE`] with [`restore_step_faces`].
+///
+/// Ids are only exact up to 2^24 (16 777 216) as floats, so the export refuses meshes with
+/// larger ones, other than the `u32::MAX` of faces that weren't matched.
+pub const ATTRIBUTE_STEP_FACE_GLTF: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_StepFaceGltf", 0x57e9_facf, VertexFormat::Float32);
+
+/// Replaces the [`ATTRIBUTE_STEP_FACE_GLTF`] of a mesh loaded from an exported file with the
+/// [`ATTRIBUTE_STEP_FACE`] it was written from, so [`step_face_index`](crate::step_face_index)
+/// works on it. Returns whether the mesh had face ids.
+pub fn restore_step_faces(mesh: &mut Mesh) -> bool {
+    let Some(VertexAttributeValues::Float32(ids)) = mesh.remove_attribute(ATTRIBUTE_STEP_FACE_GLTF) else {
+        return false;
+    };
+    // Unmatched faces were u32::MAX, which comes back just past it and saturates to it again
+    let ids: Vec<u32> = ids.into_iter().map(|id| id as u32).collect();
+    mesh.insert_attribute(ATTRIBUTE_STEP_FACE, ids);
+    true
+}
+
+impl StepAsset {
+    /// The scene as binary glTF (`.glb`), with the meshes and materials its parts use taken
+    /// from `meshes` and `materials`.
+    ///
+    /// glTF is in metres, so load with [`StepLoaderSettings::target_unit`](crate::StepLoaderSettings::target_unit)
+    /// set to `Some(LengthUnit::Metre)` for the model to come out at its real size.
+    pub fn to_glb_bytes(
+        &self,
+        meshes: &Assets<Mesh>,
+        materials: &Assets<StandardMaterial>,
+    ) -> Result<Vec<u8>, StepLoaderError> {
+        let (json, buffer) = write::document(self, meshes, materials, false)?;
+        Ok(write::glb(&json, &buffer))
+    }
+
+    /// Writes the scene to `path`, as JSON with the buffer embedded when it ends in `.gltf`
+    /// and as binary glTF otherwise, see [`StepAsset::to_glb_bytes`].
+    pub fn export_gltf(
+        &self,
+        path: impl AsRef<Path>,
+        meshes: &Assets<Mesh>,
+        materials: &Assets<StandardMaterial>,
+    ) -> Result<(), StepLoaderError> {
+        let path = path.as_ref();
+        let bytes = if path.extension().is_some_and(|e| e.eq_ignore_ascii_case("gltf")) {
+            write::document(self, meshes, materials, true)?.0
+        } else {
+            self.to_glb_bytes(meshes, materials)?
+        };
+        std::fs::write(path, bytes)?;
+        Ok(())
+    }
+}
+
+#[cfg(feature = "gltf")]
+mod write {
+    use std::collections::HashMap;
+
+    use bevy_asset::{AssetId, Assets, Handle};
+    use bevy_color::ColorToComponents;
+    use bevy_mesh::{Indices, Mesh, MeshVertexAttribute, VertexAttributeValues};
+    use bevy_pbr::StandardMaterial;
+    use bevy_render::alpha::AlphaMode;
+    use serde_json::{Map, Value, json};
+    use wgpu_types::PrimitiveTopology;
+
+    use crate::{ATTRIBUTE_STEP_FACE, StepAsset, StepLoaderError, StepPrimitive};
+
+    const UNSIGNED_SHORT: u32 = 5123;
+    const UNSIGNED_INT: u32 = 5125;
+    const FLOAT: u32 = 5126;
+    const ARRAY_BUFFER: u32 = 34962;
+    const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+    const TRIANGLES: u32 = 4;
+    /// The largest face id a float holds exactly.
+    const MAX_FACE_ID: u32 = 1 << 24;
+
+    /// The vertex attributes written, and their glTF names.
+    const ATTRIBUTES: [(MeshVertexAttribute, &str); 6] = [
+        (Mesh::ATTRIBUTE_POSITION, "POSITION"),
+        (Mesh::ATTRIBUTE_NORMAL, "NORMAL"),
+        (Mesh::ATTRIBUTE_TANGENT, "TANGENT"),
+        (Mesh::ATTRIBUTE_UV_0, "TEXCOORD_0"),
+        (Mesh::ATTRIBUTE_COLOR, "COLOR_0"),
+        (ATTRIBUTE_STEP_FACE, "_STEP_FACE"),
+    ];
+
+    /// The glTF JSON, and the buffer it refers to. With `embed` the buffer goes in the JSON as
+    /// a data URI, and the second half is empty.
+    pub(super) fn document(
+        asset: &StepAsset,
+        meshes: &Assets<Mesh>,
+        materials: &Assets<StandardMaterial>,
+        embed: bool,
+    ) -> Result<(Vec<u8>, Vec<u8>), StepLoaderError> {
+        let mut writer = Writer::default();
+        let mut nodes = Vec::with_capacity(asset.parts.len());
+        for part in &asset.parts {
+            let mut node = Map::new();
+            node.insert("name".into(), json!(part.name));
+            node.insert("translation".into(), json!(part.transform.translation.to_array()));
+            node.insert("rotation".into(), json!(part.transform.rotation.to_array()));
+            node.insert("scale".into(), json!(part.transform.scale.to_array()));
+            if let Some(mesh) = writer.mesh(&part.name, &part.primitives, meshes, materials)? {
+                node.insert("mesh".into(), json!(mesh));
+            }
+            let children: Vec<usize> = (0..asset.parts.len())
+                .filter(|&child| asset.parts[child].parent == Some(nodes.len()))
+                .collect();
+            if !children.is_empty() {
+                node.insert("children".into(), json!(children));
+            }
+            nodes.push(Value::Object(node));
+        }
+        let roots: Vec<usize> = (0..asset.parts.len()).filter(|&p| asset.parts[p].parent.is_none()).collect();
+
+        let mut buffer = json!({ "byteLength": writer.buffer.len() });
+        if embed {
+            buffer["uri"] = json!(format!("data:application/octet-stream;base64,{}", base64(&writer.buffer)));
+        }
+        let mut document = json!({
+            "asset": { "version": "2.0", "generator": "bevy_step_loader" },
+            "scene": 0,
+            "scenes": [{ "nodes": roots }],
+            "nodes": nodes,
+        });
+        // glTF doesn't allow empty arrays
+        for (key, items) in [
+            ("meshes", writer.meshes),
+            ("materials", writer.materials),
+            ("accessors", writer.accessors),
+            ("bufferViews", writer.views),
+        ] {
+            if !items.is_empty() {
+                document[key] = Value::Array(items);
+            }
+        }
+        if !writer.buffer.is_empty() {
+            document["buffers"] = json!([buffer]);
+        }
+
+        let json = serde_json::to_vec(&document).map_err(std::io::Error::other)?;
+        Ok((json, if embed { Vec::new() } else { writer.buffer }))
+    }
+
+    /// A GLB container around the JSON and buffer.
+    pub(super) fn glb(json: &[u8], buffer: &[u8]) -> Vec<u8> {
+        // Chunks are padded to four bytes, JSON with spaces and the buffer with zeros
+        let pad = |len: usize| (4 - len % 4) % 4;
+        let json_length = json.len() + pad(json.len());
+        let buffer_length = buffer.len() + pad(buffer.len());
+        let mut total = 12 + 8 + json_length;
+        if !buffer.is_empty() {
+            total += 8 + buffer_length;
+        }
+
+        let mut glb = Vec::with_capacity(total);
+        glb.extend_from_slice(b"glTF");
+        glb.extend_from_slice(&2u32.to_le_bytes());
+        glb.extend_from_slice(&(total as u32).to_le_bytes());
+        glb.extend_from_slice(&(json_length as u32).to_le_bytes());
+        glb.extend_from_slice(b"JSON");
+        glb.extend_from_slice(json);
+        glb.resize(glb.len() + pad(json.len()), b' ');
+        if !buffer.is_empty() {
+            glb.extend_from_slice(&(buffer_length as u32).to_le_bytes());
+            glb.extend_from_slice(b"BIN\0");
+            glb.extend_from_slice(buffer);
+            glb.resize(glb.len() + pad(buffer.len()), 0);
+        }
+        glb
+    }
+
+    /// Everything but the nodes, built up as the parts are written.
+    #[derive(Default)]
+    struct Writer {
+        buffer: Vec<u8>,
+        views: Vec<Value>,
+        accessors: Vec<Value>,
+        meshes: Vec<Value>,
+        materials: Vec<Value>,
+        /// Materials already written, shared by every primitive using them.
+        written: HashMap<AssetId<StandardMaterial>, usize>,
+    }
+
+    impl Writer {
+        /// Writes the primitives as one mesh, `None` if there's nothing to write.
+        fn mesh(
+            &mut self,
+            name: &str,
+            primitives: &[StepPrimitive],
+            meshes: &Assets<Mesh>,
+            materials: &Assets<StandardMaterial>,
+        ) -> Result<Option<usize>, StepLoaderError> {
+            let mut written = Vec::with_capacity(primitives.len());
+            for primitive in primitives {
+                let mesh = meshes
+                    .get(&primitive.mesh)
+                    .ok_or(StepLoaderError::InvalidMesh("a part's mesh isn't loaded"))?;
+                // Only the triangles, the loader doesn't put anything else in the scene
+                if mesh.primitive_topology() != PrimitiveTopology::TriangleList {
+                    continue;
+                }
+
+                let mut attributes = Map::new();
+                for (attribute, name) in ATTRIBUTES {
+                    if let Some(values) = mesh.attribute(attribute)
+                        && let Some(accessor) = self.attribute(values)?
+                    {
+                        attributes.insert(name.into(), json!(accessor));
+                    }
+                }
+                if !attributes.contains_key("POSITION") {
+                    return Err(StepLoaderError::InvalidMesh("no position attribute"));
+                }
+
+                let mut out = json!({
+                    "attributes": attributes,
+                    "mode": TRIANGLES,
+                    "material": self.material(&primitive.material, materials),
+                });
+                if let Some(indices) = mesh.indices() {
+                    out["indices"] = json!(self.indices(indices));
+                }
+                written.push(out);
+            }
+
+            if written.is_empty() {
+                return Ok(None);
+            }
+            self.meshes.push(json!({ "name": name, "primitives": written }));
+            Ok(Some(self.meshes.len() - 1))
+        }
+
+        fn material(&mut self, handle: &Handle<StandardMaterial>, materials: &Assets<StandardMaterial>) -> usize {
+            if let Some(index) = self.written.get(&handle.id()) {
+                return *index;
+            }
+            let material = materials.get(handle).cloned().unwrap_or_default();
+            let mut out = json!({
+                "pbrMetallicRoughness": {
+                    "baseColorFactor": material.base_color.to_linear().to_f32_array(),
+                    "metallicFactor": material.metallic,
+                    "roughnessFactor": material.perceptual_roughness,
+                },
+                "doubleSided": material.double_sided,
+            });
+            if matches!(material.alpha_mode, AlphaMode::Blend | AlphaMode::Premultiplied | AlphaMode::Add) {
+                out["alphaMode"] = json!("BLEND");
+            }
+            // The label, `Material0` or `DefaultMaterial`
+            if let Some(label) = handle.path().and_then(|p| p.label()) {
+                out["name"] = json!(label);
+            }
+
+            self.materials.push(out);
+            self.written.insert(handle.id(), self.materials.len() - 1);
+            self.materials.len() - 1
+        }
+
+        /// Writes the attribute's values, `None` for formats glTF has no place for.
+        fn attribute(&mut self, values: &VertexAttributeValues) -> Result<Option<usize>, StepLoaderError> {
+            // Vertex attributes can't be 32 bit integers in glTF and face ids outgrow the
+            // smaller ones, so they go as floats, exact up to 2^24. Unmatched faces are
+            // u32::MAX, which comes back as itself anyway
+            let converted;
+            let values = match values {
+                VertexAttributeValues::Uint32(ids) => {
+                    if ids.iter().any(|&id| id > MAX_FACE_ID && id != u32::MAX) {
+                        return Err(StepLoaderError::InvalidMesh("face ids above 2^24 can't be written exactly"));
+                    }
+                    converted = VertexAttributeValues::Float32(ids.iter().map(|&id| id as f32).collect());
+                    &converted
+                }
+                values => values,
+            };
+            let (component, kind) = match values {
+                VertexAttributeValues::Float32(_) => (FLOAT, "SCALAR"),
+                VertexAttributeValues::Float32x2(_) => (FLOAT, "VEC2"),
+                VertexAttributeValues::Float32x3(_) => (FLOAT, "VEC3"),
+                VertexAttributeValues::Float32x4(_) => (FLOAT, "VEC4"),
+                _ => return Ok(None),
+            };
+            let view = self.view(values.get_bytes(), ARRAY_BUFFER);
+            let mut accessor = json!({
+                "bufferView": view,
+                "componentType": component,
+                "count": values.len(),
+                "type": kind,
+            });
+            // Required for positions, and no harm for the other vectors
+            if let VertexAttributeValues::Float32x3(points) = values
+                && !points.is_empty()
+            {
+                let (min, max) = points.iter().fold(([f32::INFINITY; 3], [f32::NEG_INFINITY; 3]), |(min, max), p| {
+                    ([0, 1, 2].map(|i| min[i].min(p[i])), [0, 1, 2].map(|i| max[i].max(p[i])))
+                });
+                accessor["min"] = json!(min);
+                accessor["max"] = json!(max);
+            }
+            self.accessors.push(accessor);
+            Ok(Some(self.accessors.len() - 1))
+        }
+
+        fn indices(&mut self, indices: &Indices) -> usize {
+            let (component, bytes): (u32, Vec<u8>) = match indices {
+                Indices::U16(i) => (UNSIGNED_SHORT, i.iter().flat_map(|v| v.to_le_bytes()).collect()),
+                Indices::U32(i) => (UNSIGNED_INT, i.iter().flat_map(|v| v.to_le_bytes()).collect()),
+            };
+            let view = self.view(&bytes, ELEMENT_ARRAY_BUFFER);
+            self.accessors.push(json!({
+                "bufferView": view,
+                "componentType": component,
+                "count": indices.len(),
+                "type": "SCALAR",
+            }));
+            self.accessors.len() - 1
+        }
+
+        /// Appends `bytes` to the buffer as a view of their own, four byte aligned.
+        fn view(&mut self, bytes: &[u8], target: u32) -> usize {
+            self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+            self.views.push(json!({
+                "buffer": 0,
+                "byteOffset": self.buffer.len(),
+                "byteLength": bytes.len(),
+                "target": target,
+            }));
+            self.buffer.extend_from_slice(bytes);
+            self.views.len() - 1
+        }
+    }
+
+    fn base64(bytes: &[u8]) -> String {
+        const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+        let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+        for chunk in bytes.chunks(3) {
+            let n = chunk.iter().enumerate().fold(0u32, |n, (i, b)| n | (u32::from(*b) << (16 - 8 * i)));
+            for i in 0..4 {
+                if i <= chunk.len() {
+                    out.push(ALPHABET[((n >> (18 - 6 * i)) & 63) as usize] as char);
+                } else {
+                    out.push('=');
+                }
+            }
+        }
+        out
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn face_ids_must_fit_a_float() {
+            let mut writer = Writer::default();
+            let exact = VertexAttributeValues::Uint32(vec![0, MAX_FACE_ID, u32::MAX]);
+            assert_eq!(writer.attribute(&exact).unwrap(), Some(0));
+            let read: Vec<u32> = writer.buffer[..12]
+                .chunks(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()) as u32)
+                .collect();
+            assert_eq!(read, [0, MAX_FACE_ID, u32::MAX]);
+
+            let past = VertexAttributeValues::Uint32(vec![MAX_FACE_ID + 1]);
+            assert!(matches!(writer.attribute(&past), Err(StepLoaderError::InvalidMesh(_))));
+        }
+    }
+}
+
+#[cfg(not(feature = "gltf"))]
+mod write {
+    use bevy_asset::Assets;
+    use bevy_mesh::Mesh;
+    use bevy_pbr::StandardMaterial;
+
+    use crate::{StepAsset, StepLoaderError};
+
+    pub(super) fn document(
+        _asset: &StepAsset,
+        _meshes: &Assets<Mesh>,
+        _materials: &Assets<StandardMaterial>,
+        _embed: bool,
+    ) -> Result<(Vec<u8>, Vec<u8>), StepLoaderError> {
+        Err(StepLoaderError::FeatureDisabled("gltf"))
+    }
+
+    pub(super) fn glb(_json: &[u8], _buffer: &[u8]) -> Vec<u8> {
+        Vec::new()
+    }
+}
@@ -0,0 +1,114 @@
+//! The HEADER section: who wrote the file, with what, and against which schema.
+
+use crate::part21::{StepData, Value};
+
+/// The application protocol a file was written against, from `FILE_SCHEMA`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum StepSchema {
+    /// AP203, `CONFIG_CONTROL_DESIGN` or its second edition.
+    Ap203,
+    /// AP214, `AUTOMOTIVE_DESIGN`.
+    Ap214,
+    /// AP242, `AP242_MANAGED_MODEL_BASED_3D_ENGINEERING_MIM_LF`.
+    Ap242,
+    /// Something else, with the schema name as written.
+    Other(String),
+    /// The header had no `FILE_SCHEMA`.
+    #[default]
+    Unknown,
+}
+
+impl StepSchema {
+    fn detect(schema: &str) -> StepSchema {
+        // Names can carry an object identifier, `AUTOMOTIVE_DESIGN { 1 0 10303 214 1 1 1 1 }`
+        let (name, oid) = match schema.split_once('{') {
+            Some((name, oid)) => (name.trim(), oid),
+            None => (schema.trim(), ""),
+        };
+        let name = name.to_ascii_uppercase();
+        let part = oid.split_whitespace().nth(3);
+
+        if name == "CONFIG_CONTROL_DESIGN" || name.starts_with("AP203") || part == Some("203") {
+            StepSchema::Ap203
+        } else if name == "AUTOMOTIVE_DESIGN" || name.starts_with("AP214") || part == Some("214") {
+            StepSchema::Ap214
+        } else if name.starts_with("AP242") || part == Some("442") || part == Some("242") {
+            StepSchema::Ap242
+        } else {
+            StepSchema::Other(schema.trim().to_string())
+        }
+    }
+}
+
+/// The contents of a STEP file's HEADER section.
+///
+/// Exporters fill these in very unevenly, so any of the strings can be empty.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct StepHeader {
+    /// `FILE_DESCRIPTION.description`.
+    pub description: Vec<String>,
+    /// `FILE_DESCRIPTION.implementation_level`, e.g. `2;1`.
+    pub implementation_level: String,
+    /// `FILE_NAME.name`, the name the file was exported under.
+    pub name: String,
+    /// `FILE_NAME.time_stamp`, as written (ISO 8601, in well behaved files).
+    pub time_stamp: String,
+    pub author: Vec<String>,
+    pub organization: Vec<String>,
+    /// The STEP processor (translator) that wrote the file.
+    pub preprocessor_version: String,
+    /// The CAD system the model came from.
+    pub originating_system: String,
+    pub authorization: String,
+    /// `FILE_SCHEMA`, as written.
+    pub schemas: Vec<String>,
+    /// The application protocol detected from the first of `schemas`.
+    pub schema: StepSchema,
+}
+
+impl StepHeader {
+    pub(crate) fn read(data: &StepData) -> StepHeader {
+        let mut header = StepHeader::default();
+
+        if let Some(description) = data.header_record("FILE_DESCRIPTION") {
+            header.description = strings(description.param(0));
+            header.implementation_level = string(description.param(1));
+        }
+
+        if let Some(name) = data.header_record("FILE_NAME") {
+            header.name = string(name.param(0));
+            header.time_stamp = string(name.param(1));
+            header.author = strings(name.param(2));
+            header.organization = strings(name.param(3));
+            header.preprocessor_version = string(name.param(4));
+            header.originating_system = string(name.param(5));
+            header.authorization = string(name.param(6));
+        }
+
+        if let Some(schema) = data.header_record("FILE_SCHEMA") {
+            header.schemas = strings(schema.param(0));
+        }
+        header.schema = header
+            .schemas
+            .first()
+            .map_or(StepSchema::Unknown, |s| StepSchema::detect(s));
+
+        header
+    }
+}
+
+fn string(value: &Value) -> String {
+    value.as_str().unwrap_or_default().to_string()
+}
+
+/// A list of strings, leaving out the empty ones exporters pad them with.
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_list()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(Value::as_str)
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
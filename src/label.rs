@@ -0,0 +1,136 @@
+use bevy_asset::AssetPath;
+
+/// Labels that can be used to load the sub-assets of a STEP file.
+///
+/// ```rust,ignore
+/// let scene: Handle<Scene> = asset_server.load(StepAssetLabel::Scene.from_asset("part.step"));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum StepAssetLabel {
+    /// `Scene`: the assembly as a `Scene`, one entity per placed part.
+    Scene,
+    /// `Part{}/Primitive{}`: the triangles of the nth node of the assembly that share a
+    /// material, in that node's local frame.
+    Primitive { part: usize, primitive: usize },
+    /// `Part{}/Primitive{}/Lod{}`: a simplified version of a primitive, the nth of
+    /// [`StepLoaderSettings::lods`](crate::StepLoaderSettings::lods) counting from 1.
+    Lod { part: usize, primitive: usize, level: usize },
+    /// `Material{}`: one of the distinct colours in the file.
+    Material(usize),
+    /// `DefaultMaterial`: the material given to faces the file doesn't colour.
+    DefaultMaterial,
+    /// The mesh of one solid, in the frame of the product it belongs to. The label is the
+    /// solid's name (`Body1`) when that's unique in the file, and the name with its entity id
+    /// (`Body1-42`) when it isn't; [`StepBody::label`](crate::StepBody::label) has it either way.
+    Body(String),
+    /// `Edges`: every edge of the B-rep as a `LineList` mesh, in the same frame as
+    /// [`StepAsset::mesh`](crate::StepAsset::mesh).
+    Edges,
+}
+
+impl core::fmt::Display for StepAssetLabel {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            StepAssetLabel::Scene => f.write_str("Scene"),
+            StepAssetLabel::Primitive { part, primitive } => write!(f, "Part{}/Primitive{}", part, primitive),
+            StepAssetLabel::Lod { part, primitive, level } => {
+                write!(f, "Part{}/Primitive{}/Lod{}", part, primitive, level)
+            }
+            StepAssetLabel::Material(index) => write!(f, "Material{}", index),
+            StepAssetLabel::DefaultMaterial => f.write_str("DefaultMaterial"),
+            StepAssetLabel::Body(label) => f.write_str(label),
+            StepAssetLabel::Edges => f.write_str("Edges"),
+        }
+    }
+}
+
+impl core::str::FromStr for StepAssetLabel {
+    type Err = core::convert::Infallible;
+
+    /// The label for a sub-asset name, the inverse of `Display`. Anything that isn't one of the
+    /// fixed labels is a body.
+    fn from_str(label: &str) -> Result<Self, Self::Err> {
+        // Digits only, `parse` alone would take `Part+1`
+        let numbered = |s: &str, prefix: &str| {
+            s.strip_prefix(prefix)
+                .filter(|n| n.bytes().all(|b| b.is_ascii_digit()))
+                .and_then(|n| n.parse::<usize>().ok())
+        };
+
+        let mut parts = label.split('/');
+        let parsed = match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some("Scene"), None, ..) => Some(StepAssetLabel::Scene),
+            (Some("DefaultMaterial"), None, ..) => Some(StepAssetLabel::DefaultMaterial),
+            (Some("Edges"), None, ..) => Some(StepAssetLabel::Edges),
+            (Some(material), None, ..) => numbered(material, "Material").map(StepAssetLabel::Material),
+            (Some(part), Some(primitive), None, _) => numbered(part, "Part")
+                .zip(numbered(primitive, "Primitive"))
+                .map(|(part, primitive)| StepAssetLabel::Primitive { part, primitive }),
+            (Some(part), Some(primitive), Some(level), None) => numbered(part, "Part")
+                .zip(numbered(primitive, "Primitive"))
+                .zip(numbered(level, "Lod"))
+                .map(|((part, primitive), level)| StepAssetLabel::Lod { part, primitive, level }),
+            _ => None,
+        };
+        Ok(parsed.unwrap_or_else(|| StepAssetLabel::Body(label.to_string())))
+    }
+}
+
+impl StepAssetLabel {
+    /// Add this label to an asset path.
+    pub fn from_asset(&self, path: impl Into<AssetPath<'static>>) -> AssetPath<'static> {
+        path.into().with_label(self.to_string())
+    }
+
+    /// Whether a body named `name` would collide with one of the fixed labels.
+    pub(crate) fn is_reserved(name: &str) -> bool {
+        let numbered = |prefix: &str| {
+            name.strip_prefix(prefix)
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+        };
+        matches!(name, "Scene" | "DefaultMaterial" | "Edges") || numbered("Material")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn labels_round_trip() {
+        let labels = [
+            StepAssetLabel::Scene,
+            StepAssetLabel::Primitive { part: 3, primitive: 0 },
+            StepAssetLabel::Lod { part: 0, primitive: 12, level: 2 },
+            StepAssetLabel::Material(7),
+            StepAssetLabel::DefaultMaterial,
+            StepAssetLabel::Body("Body1".to_string()),
+            StepAssetLabel::Body("Body1-42".to_string()),
+            StepAssetLabel::Edges,
+        ];
+        for label in labels {
+            assert_eq!(label.to_string().parse::<StepAssetLabel>(), Ok(label.clone()), "{}", label);
+        }
+    }
+
+    #[test]
+    fn anything_else_is_a_body() {
+        for name in ["Part1", "Part1/Primitive", "Part+1/Primitive2", "Material", "Scene/Extra"] {
+            assert_eq!(name.parse(), Ok(StepAssetLabel::Body(name.to_string())));
+        }
+    }
+
+    #[test]
+    fn labels_go_on_the_asset_path() {
+        let path = StepAssetLabel::Lod { part: 1, primitive: 2, level: 3 }.from_asset("models/part.step");
+        assert_eq!(path.to_string(), "models/part.step#Part1/Primitive2/Lod3");
+    }
+
+    #[test]
+    fn reserved_names() {
+        assert!(StepAssetLabel::is_reserved("Scene"));
+        assert!(StepAssetLabel::is_reserved("Material3"));
+        assert!(!StepAssetLabel::is_reserved("Material"));
+        assert!(!StepAssetLabel::is_reserved("Body1"));
+    }
+}
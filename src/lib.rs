@@ -1,148 +1,284 @@
-use bevy_app::{Plugin, App};
-use bevy_asset::{Asset, AssetLoader, LoadContext, io::Reader, RenderAssetUsages, AssetApp};
+use bevy_app::{Plugin, App, PostUpdate};
+use bevy_asset::{Asset, AssetLoader, Handle, LoadContext, io::Reader, AssetApp};
+use bevy_log::warn;
+use bevy_platform::time::Instant;
 use bevy_reflect::TypePath;
-use bevy_mesh::{Mesh, Indices};
-use wgpu_types::PrimitiveTopology;
-
-#[cfg(feature = "meshopt")]
-use meshopt;
-#[cfg(feature = "meshopt")]
-use bytemuck;
+use bevy_mesh::Mesh;
+use bevy_scene::Scene;
+use bevy_ecs::schedule::IntoScheduleConfigs;
+use bevy_transform::TransformSystems;
+
+mod assembly;
+mod backend;
+mod body;
+mod bounds;
+mod collider;
+mod brep;
+mod edges;
+mod error;
+mod gltf;
+mod header;
+mod label;
+mod lod;
+mod mass;
+mod normals;
+mod part21;
+mod picking;
+mod refine;
+mod report;
+mod scene;
+mod segment;
+mod settings;
+mod style;
+mod topology;
+mod units;
+mod uv;
+mod validation;
+
+pub use backend::triangulate_step_file;
+pub use bounds::{OrientedBox, StepBounds};
+pub use collider::{StepCollider, StepColliders};
+pub use error::{EntityRef, SourceLocation, StepLoaderError, SyntaxError};
+pub use gltf::{ATTRIBUTE_STEP_FACE_GLTF, restore_step_faces};
+pub use header::{StepHeader, StepSchema};
+pub use label::StepAssetLabel;
+pub use lod::{StepLod, StepLodLevel, update_step_lods};
+pub use mass::MassProperties;
+pub use normals::compute_crease_normals;
+pub use picking::{ATTRIBUTE_STEP_FACE, StepFace, step_face_index};
+pub use report::{FailedFace, FoxtrotStats, StepLoadReport};
+pub use scene::{StepPart, StepPrimitive};
+pub use settings::{
+    ColliderSettings, LodLevel, MeshoptPasses, NormalMode, StepLoaderSettings, TriangulationBackend, UpAxis,
+};
+pub use topology::{
+    StepTopology, TopologyEdge, TopologyFace, TopologyLoop, TopologyOrientedEdge, TopologyShell, TopologySolid,
+    TopologyVertex,
+};
+pub use units::LengthUnit;
+pub use validation::{ValidationCheck, ValidationProperties};
 
+pub struct StepPlugin;
 
+impl Plugin for StepPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<StepAsset>()
+            .register_asset_loader(StepLoader)
+            .register_type::<StepLod>()
+            .add_systems(PostUpdate, update_step_lods.after(TransformSystems::Propagate));
+    }
+}
 
-#[derive(Debug)]
-pub enum StepLoaderError {
-    IoError(std::io::Error),
-    #[cfg(feature = "opencascade")]
-    OcctError(String),
-    FoxtrotError(String),
-    ParseError(String),
+// The asset representing a STEP file
+#[derive(Asset, TypePath, Debug, Clone)]
+pub struct StepAsset {
+    /// Every triangle in the file as one mesh, coloured per vertex (`Mesh::ATTRIBUTE_COLOR`)
+    /// when the file has colours.
+    pub mesh: Mesh,
+    /// The bounding volumes of `mesh`.
+    pub bounds: StepBounds,
+    /// The file's product structure, see [`StepAssetLabel::Scene`].
+    pub scene: Handle<Scene>,
+    /// The scene's parts, parents before their children.
+    pub parts: Vec<StepPart>,
+    /// The file's HEADER section, empty if the file couldn't be read past the triangles.
+    pub header: StepHeader,
+    /// The length unit the file declares. Positions are in this unit (times
+    /// [`StepLoaderSettings::scale`]) unless [`StepLoaderSettings::target_unit`] is set.
+    pub length_unit: Option<LengthUnit>,
+    /// The B-rep's edges as lines, see [`StepAssetLabel::Edges`].
+    pub edges: Handle<Mesh>,
+    /// The B-rep faces under the meshes' triangles, indexed by [`ATTRIBUTE_STEP_FACE`]. Empty
+    /// if the file's structure couldn't be read.
+    pub faces: Vec<StepFace>,
+    /// The B-rep's solids, shells, faces, loops, edges and vertices, when
+    /// [`StepLoaderSettings::topology`] is set.
+    pub topology: Option<StepTopology>,
+    /// Each solid in the file as its own mesh, see [`StepAssetLabel::Body`]. Coloured per
+    /// vertex like `mesh`.
+    pub bodies: Vec<StepBody>,
+    /// The backend that produced `mesh`, which can differ from the requested one
+    /// when using [`TriangulationBackend::FoxtrotThenOcct`].
+    pub backend: TriangulationBackend,
+    /// How the load went, including any faces that went missing.
+    pub report: StepLoadReport,
 }
 
-impl std::fmt::Display for StepLoaderError {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            StepLoaderError::IoError(e) => write!(f, "IO error: {}", e),
-            #[cfg(feature = "opencascade")]
-            StepLoaderError::OcctError(e) => write!(f, "OpenCASCADE error: {}", e),
-            StepLoaderError::FoxtrotError(e) => write!(f, "Foxtrot triangulation error: {}", e),
-            StepLoaderError::ParseError(e) => write!(f, "Parse error: {}", e),
-        }
-    }
+/// A solid from a STEP file, loaded as its own mesh.
+#[derive(Debug, Clone)]
+pub struct StepBody {
+    /// The name given in the file, can be empty.
+    pub name: String,
+    /// The entity id of the solid, `42` for `#42=MANIFOLD_SOLID_BREP(...)`.
+    pub id: u64,
+    /// The label the mesh was added under.
+    pub label: String,
+    pub mesh: Handle<Mesh>,
+    /// The bounding volumes of the mesh, in its frame.
+    pub bounds: StepBounds,
+    /// Volume, area, centre of mass and inertia, in the mesh's frame and units, with the
+    /// density from [`StepLoaderSettings::densities`] or [`StepLoaderSettings::density`].
+    pub mass: MassProperties,
+    /// Collision shapes, when [`StepLoaderSettings::colliders`] asks for them.
+    pub colliders: Option<StepColliders>,
 }
 
-impl std::error::Error for StepLoaderError {}
+impl StepAsset {
+    /// The face under `triangle` of `mesh`, which should be one of this asset's meshes. See
+    /// [`step_face_index`] for where `triangle` comes from.
+    pub fn face(&self, mesh: &Mesh, triangle: usize) -> Option<&StepFace> {
+        self.faces.get(step_face_index(mesh, triangle)?)
+    }
 
-impl From<std::io::Error> for StepLoaderError {
-    fn from(error: std::io::Error) -> Self {
-        StepLoaderError::IoError(error)
+    /// Simplify the mesh using meshopt decimation, see [`simplify_mesh`].
+    pub fn simplify_mesh(&mut self, ratio: f32, error_threshold: f32) -> Result<SimplifyReport, StepLoaderError> {
+        simplify_mesh(&mut self.mesh, ratio, error_threshold)
     }
 }
 
-#[cfg(feature = "opencascade")]
-impl From<String> for StepLoaderError {
-    fn from(error: String) -> Self {
-        StepLoaderError::OcctError(error)
-    }
+/// What [`simplify_mesh`] did.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct SimplifyReport {
+    pub vertices_before: usize,
+    pub vertices_after: usize,
+    pub triangles_before: usize,
+    pub triangles_after: usize,
+    /// The error meshopt achieved, relative to the mesh's size.
+    pub error: f32,
 }
 
-pub struct StepPlugin;
+/// Simplify a mesh using meshopt decimation
+/// 
+/// The vertex buffer is compacted to the vertices the simplified triangles still use, with
+/// every attribute kept in step, and normals are recomputed for the new triangles if the mesh
+/// had any.
+///
+/// # Arguments
+/// * `ratio` - Target reduction ratio (0.0 to 1.0, where 1.0 means no reduction and 0.5 means 50% reduction)
+/// * `error_threshold` - Maximum allowed error for the simplification
+/// 
+/// # Returns
+/// * `Ok(SimplifyReport)` with the counts before and after if simplification was successful
+/// * `Err(StepLoaderError)` if simplification failed or meshopt feature is not enabled
+#[cfg(feature = "meshopt")]
+pub fn simplify_mesh(mesh: &mut Mesh, ratio: f32, error_threshold: f32) -> Result<SimplifyReport, StepLoaderError> {
+    simplify(mesh, ratio, error_threshold, true)
+}
 
-impl Plugin for StepPlugin {
-    fn build(&self, app: &mut App) {
-        app.init_asset::<StepAsset>()
-            .register_asset_loader(StepLoader);
+/// [`simplify_mesh`], optionally keeping the normals the surviving vertices already have.
+/// Those are still right when they came from the B-rep surfaces rather than the triangles.
+#[cfg(feature = "meshopt")]
+pub(crate) fn simplify(
+    mesh: &mut Mesh,
+    ratio: f32,
+    error_threshold: f32,
+    recompute_normals: bool,
+) -> Result<SimplifyReport, StepLoaderError> {
+    use bevy_mesh::Indices;
+
+    let vertices_before = mesh.count_vertices();
+    let triangles_before = mesh.indices().map_or(0, |i| i.len() / 3);
+    let (simplified_indices, error) = simplify_indices(mesh, ratio, error_threshold)?;
+
+    // Keep only the vertices still in use, in the order they're first used. `order` maps new
+    // vertices to old ones, and duplicate_vertices applies that to every attribute for us
+    let mut remap = vec![u32::MAX; vertices_before];
+    let mut order: Vec<u32> = Vec::new();
+    let indices: Vec<u32> = simplified_indices
+        .iter()
+        .map(|&v| {
+            let slot = &mut remap[v as usize];
+            if *slot == u32::MAX {
+                *slot = order.len() as u32;
+                order.push(v);
+            }
+            *slot
+        })
+        .collect();
+    let vertices_after = order.len();
+    mesh.insert_indices(Indices::U32(order));
+    mesh.duplicate_vertices();
+    mesh.insert_indices(Indices::U32(indices));
+
+    if recompute_normals && mesh.contains_attribute(Mesh::ATTRIBUTE_NORMAL) {
+        mesh.compute_smooth_normals();
     }
-}
 
-// The asset representing a STEP file
-#[derive(Asset, TypePath, Debug, Clone)]
-pub struct StepAsset {
-    pub mesh: Mesh,
+    Ok(SimplifyReport {
+        vertices_before,
+        vertices_after,
+        triangles_before,
+        triangles_after: simplified_indices.len() / 3,
+        error,
+    })
 }
 
-impl StepAsset {
-    /// Simplify the mesh using meshopt decimation
-    /// 
-    /// # Arguments
-    /// * `ratio` - Target reduction ratio (0.0 to 1.0, where 1.0 means no reduction and 0.5 means 50% reduction)
-    /// * `error_threshold` - Maximum allowed error for the simplification
-    /// 
-    /// # Returns
-    /// * `Ok(())` if simplification was successful
-    /// * `Err(StepLoaderError)` if simplification failed or meshopt feature is not enabled
-    #[cfg(feature = "meshopt")]
-    pub fn simplify_mesh(&mut self, ratio: f32, error_threshold: f32) -> Result<(), StepLoaderError> {
-        use std::mem;
-
-        // Extract vertex positions
-        let positions = match self.mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
-            Some(positions) => match positions {
-                bevy_mesh::VertexAttributeValues::Float32x3(pos) => pos.clone(),
-                _ => return Err(StepLoaderError::ParseError("Expected Float32x3 positions".to_string())),
-            },
-            None => return Err(StepLoaderError::ParseError("No position attribute found".to_string())),
-        };
+/// The indices of a simplified `mesh`, and the error meshopt reports for them (relative to the
+/// mesh's size).
+#[cfg(feature = "meshopt")]
+fn simplify_indices(mesh: &Mesh, ratio: f32, error_threshold: f32) -> Result<(Vec<u32>, f32), StepLoaderError> {
+    use bevy_mesh::Indices;
+    use std::mem;
 
-        // Extract indices
-        let original_indices: Vec<u32> = match self.mesh.indices() {
-            Some(indices) => match indices {
-                Indices::U32(indices) => indices.clone(),
-                Indices::U16(indices) => indices.iter().map(|&i| i as u32).collect(),
-            },
-            None => return Err(StepLoaderError::ParseError("No indices found".to_string())),
-        };
+    // Extract vertex positions
+    let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(positions) => match positions {
+            bevy_mesh::VertexAttributeValues::Float32x3(pos) => pos,
+            _ => return Err(StepLoaderError::InvalidMesh("expected Float32x3 positions")),
+        },
+        None => return Err(StepLoaderError::InvalidMesh("no position attribute")),
+    };
 
-        // Prepare data for meshopt
-        let vertices: Vec<f32> = positions
-            .iter()
-            .flat_map(|&[x, y, z]| [x, y, z])
-            .collect();
-
-        let target_index_count = (original_indices.len() as f32 * ratio) as usize;
-        let target_error = error_threshold;
-
-        // Create vertex adapter
-        let vertex_size = 3 * mem::size_of::<f32>();
-        let vertex_adapter = match meshopt::VertexDataAdapter::new(
-            bytemuck::cast_slice(&vertices),
-            vertex_size,
-            0,
-        ) {
-            Ok(adapter) => adapter,
-            Err(_) => return Err(StepLoaderError::ParseError("Failed to create vertex adapter".to_string())),
-        };
+    // Extract indices
+    let original_indices: Vec<u32> = match mesh.indices() {
+        Some(indices) => match indices {
+            Indices::U32(indices) => indices.clone(),
+            Indices::U16(indices) => indices.iter().map(|&i| i as u32).collect(),
+        },
+        None => return Err(StepLoaderError::InvalidMesh("no indices")),
+    };
 
-        // Perform simplification
-        let mut error_result: f32 = 0.0;
-        let simplified_indices = meshopt::simplify(
-            &original_indices,
-            &vertex_adapter,
-            target_index_count,
-            target_error,
-            meshopt::SimplifyOptions::LockBorder,
-            Some(&mut error_result),
-        );
+    // Prepare data for meshopt
+    let vertices: Vec<f32> = positions
+        .iter()
+        .flat_map(|&[x, y, z]| [x, y, z])
+        .collect();
 
-        // Update the mesh with simplified indices (mutable borrow only when needed)
-        if let Some(indices) = self.mesh.indices_mut() {
-            *indices = Indices::U32(simplified_indices.clone());
-        }
+    let target_index_count = (original_indices.len() as f32 * ratio) as usize;
+    let target_error = error_threshold;
+
+    // Create vertex adapter
+    let vertex_size = 3 * mem::size_of::<f32>();
+    let vertex_adapter = match meshopt::VertexDataAdapter::new(
+        bytemuck::cast_slice(&vertices),
+        vertex_size,
+        0,
+    ) {
+        Ok(adapter) => adapter,
+        Err(_) => return Err(StepLoaderError::InvalidMesh("positions can't be read by meshopt")),
+    };
 
-        println!("Mesh simplified: {} -> {} indices (error: {})", original_indices.len(), simplified_indices.len(), error_result);
-        
-        Ok(())
-    }
+    // Perform simplification
+    let mut error_result: f32 = 0.0;
+    let simplified_indices = meshopt::simplify(
+        &original_indices,
+        &vertex_adapter,
+        target_index_count,
+        target_error,
+        meshopt::SimplifyOptions::LockBorder,
+        Some(&mut error_result),
+    );
 
-    /// Simplify the mesh using meshopt decimation
-    /// 
-    /// This method is only available when the `meshopt` feature is enabled.
-    /// If the feature is not enabled, this method will always return an error.
-    #[cfg(not(feature = "meshopt"))]
-    pub fn simplify_mesh(&mut self, _ratio: f32, _error_threshold: f32) -> Result<(), StepLoaderError> {
-        Err(StepLoaderError::ParseError("Mesh simplification requires the 'meshopt' feature to be enabled".to_string()))
-    }
+    Ok((simplified_indices, error_result))
+}
+
+/// Simplify a mesh using meshopt decimation
+/// 
+/// This function is only available when the `meshopt` feature is enabled.
+/// If the feature is not enabled, this function will always return an error.
+#[cfg(not(feature = "meshopt"))]
+pub fn simplify_mesh(_mesh: &mut Mesh, _ratio: f32, _error_threshold: f32) -> Result<SimplifyReport, StepLoaderError> {
+    Err(StepLoaderError::FeatureDisabled("meshopt"))
 }
 
 // The loader for STEP files
@@ -151,7 +287,7 @@ pub struct StepLoader;
 
 impl AssetLoader for StepLoader {
     type Asset = StepAsset;
-    type Settings = ();
+    type Settings = StepLoaderSettings;
     type Error = StepLoaderError;
 
     fn extensions(&self) -> &[&str] {
@@ -161,135 +297,89 @@ impl AssetLoader for StepLoader {
     async fn load(
         &self,
         reader: &mut dyn Reader,
-        _settings: &Self::Settings,
-        _load_context: &mut LoadContext<'_>,
+        settings: &Self::Settings,
+        load_context: &mut LoadContext<'_>,
     ) -> Result<Self::Asset, Self::Error> {
         let mut bytes = Vec::new();
         reader.read_to_end(&mut bytes).await?;
 
-        let mesh = triangulate_step_file(&bytes)?;
-
-        Ok(StepAsset { mesh })
-    }
-}
-
-/// Triangulate the STEP file data into a Bevy Mesh.
-/// Depending on the feature flag, it uses either OpenCASCADE (opencascade) or Foxtrot library.
-///
-/// The 'opencascade' feature, means you'll build it via the wrapper, some cmake etc deps and fanalging may be required
-/// however, it is SIGNIFICANTLY more robust and can handle a wider variety of STEP files, and their miscellaneous shitfuckery.
-fn triangulate_step_file(step_data: &[u8]) -> Result<Mesh, StepLoaderError> {
-    #[cfg(feature = "opencascade")]
-    {
-        triangulate_with_occt(step_data)
-    }
-    #[cfg(not(feature = "opencascade"))]
-    {
-        triangulate_with_foxtrot(step_data)
-    }
-}
-
-#[cfg(feature = "opencascade")]
-fn triangulate_with_occt(step_data: &[u8]) -> Result<Mesh, StepLoaderError> {
-    use opencascade::primitives::Shape;
-    use opencascade::mesh::Mesher;
-
-    let temp_path = std::env::temp_dir().join("temp_step_file.step");
-    std::fs::write(&temp_path, step_data)?;
-
-    let shape_to_mesh = Shape::read_step(temp_path.to_str().unwrap())
-        .map_err(|e| StepLoaderError::OcctError(format!("OCCT failed to read STEP file: {:?}", e)))?;
-
-    let occt_mesh = Mesher::new(&shape_to_mesh).mesh();
-
-    let vertices: Vec<[f32; 3]> = occt_mesh
-        .vertices
-        .iter()
-        .map(|v| [v.x as f32, v.y as f32, v.z as f32])
-        .collect();
-
-    let indices: Vec<u32> = occt_mesh.indices.iter().map(|&i| i as u32).collect();
-
-    let mut bevy_mesh = Mesh::new(
-        PrimitiveTopology::TriangleList,
-        RenderAssetUsages::all(), // Using the asset API directly
-    );
-    bevy_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-    bevy_mesh.insert_indices(Indices::U32(indices));
-    
-    // Compute normals for proper lighting
-    bevy_mesh.compute_normals();
-
-    #[cfg(feature = "meshopt")]
-    {
-        optimize_mesh(&mut bevy_mesh)?;
-    }
-
-    Ok(bevy_mesh)
-}
-
-#[allow(dead_code)]
-fn triangulate_with_foxtrot(step_data: &[u8]) -> Result<Mesh, StepLoaderError> {
-    use step::step_file::StepFile;
-    use triangulate::triangulate::triangulate4 as triangulate;
-
-    let flat = StepFile::strip_flatten(step_data);
-    let step = StepFile::parse(&flat);
-    let (triangulated_mesh, _stats) = triangulate(&step);
+        let (mut raw, mut report) = backend::triangulate(&bytes, settings)?;
+        let start = Instant::now();
+
+        // The backends only give us triangles, the structure comes from reading the file ourselves.
+        // If that fails we can still hand back the triangles, just without any structure
+        let data = match part21::parse(&bytes) {
+            Ok(data) => data,
+            // With no triangles either, the syntax error is the best explanation we have
+            Err(e) if raw.is_empty() => return Err(e.into()),
+            Err(e) => {
+                warn!("{}: couldn't read the STEP structure, loading it as one part: {}", load_context.path().display(), e);
+                Default::default()
+            }
+        };
+        let header = StepHeader::read(&data);
+        let length_unit = units::length_unit(&data);
+        if length_unit.is_none() && settings.target_unit.is_some() && settings.assumed_unit.is_some() {
+            warn!("{}: no length unit declared, assuming {:?}", load_context.path().display(), settings.assumed_unit);
+        }
+        // From here on everything is built in the target unit
+        let settings = &units::rescaled(settings, length_unit)?;
+        let brep = brep::Brep::read(&data);
+        let assembly = assembly::Assembly::read(&data, &brep);
+        if raw.is_empty() {
+            report.check_faces(&data, &brep, &assembly, &[]);
+            return Err(brep::explain_empty(&data, &brep, &report));
+        }
+        let mut hits = segment::classify(&raw, &brep, &assembly);
+        refine::refine(&mut raw, &mut hits, &brep, &assembly, settings);
+        report.check_faces(&data, &brep, &assembly, &hits);
+        report.triangles = raw.indices.len() / 3;
+        report.vertices = raw.positions.len();
+        report.validation = validation::check(&data, &raw, &assembly, &hits, length_unit, settings);
+        if !report.failed_faces.is_empty() {
+            warn!(
+                "{}: {} of {} faces are missing from the mesh, see StepAsset::report",
+                load_context.path().display(),
+                report.failed_faces.len(),
+                report.faces
+            );
+        }
 
-    let vertices: Vec<[f32; 3]> = triangulated_mesh
-        .verts
-        .iter()
-        .map(|v| [v.pos.x as f32, v.pos.y as f32, v.pos.z as f32])
-        .collect();
+        let faces = picking::assign_faces(&mut raw, &hits, &brep);
+        if settings.normals == NormalMode::Surface {
+            normals::surface_normals(&mut raw, &hits, &brep, &assembly);
+        }
+        if settings.uv_scale.is_some() {
+            uv::surface_uvs(&mut raw, &hits, &brep, &assembly);
+        }
 
-    let indices: Vec<u32> = triangulated_mesh
-        .triangles
-        .iter()
-        .flat_map(|t| [t.verts.x, t.verts.y, t.verts.z])
-        .collect();
+        let styles = style::Styles::read(&data);
+        let triangle_styles: Vec<Option<usize>> = hits.iter().map(|h| h.and_then(|h| styles.of(&h))).collect();
+        style::paint(&mut raw, &triangle_styles, &styles);
 
-    let mut bevy_mesh = Mesh::new(
-        PrimitiveTopology::TriangleList,
-        RenderAssetUsages::all(), // Using the asset API directly
-    );
-    bevy_mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-    bevy_mesh.insert_indices(Indices::U32(indices));
-    
-    // Compute normals for proper lighting
-    bevy_mesh.compute_normals();
-
-    #[cfg(feature = "meshopt")]
-    {
-        optimize_mesh(&mut bevy_mesh)?;
+        let (scene, parts) = scene::build(load_context, &raw, &assembly, &hits, &styles, &triangle_styles, settings)?;
+        let bodies = body::build(load_context, &raw, &brep, &assembly, &hits, settings, &mut report)?;
+        let edges = load_context.add_labeled_asset(
+            StepAssetLabel::Edges.to_string(),
+            edges::build(&data, &brep, &assembly, settings),
+        );
+        let mesh = backend::build_mesh(raw, settings)?;
+        report.build_time = start.elapsed();
+
+        Ok(StepAsset {
+            bounds: StepBounds::from_mesh(&mesh),
+            mesh,
+            scene,
+            parts,
+            header,
+            length_unit,
+            edges,
+            faces,
+            topology: settings.topology.then(|| StepTopology::read(&data)),
+            bodies,
+            backend: report.backend,
+            report,
+        })
     }
-
-    Ok(bevy_mesh)
 }
 
-#[cfg(feature = "meshopt")]
-fn optimize_mesh(mesh: &mut Mesh) -> Result<(), StepLoaderError> {
-    let positions: Vec<[f32; 3]> = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
-        Some(positions) => match positions {
-            bevy_mesh::VertexAttributeValues::Float32x3(pos) => pos.to_vec(),
-            _ => return Err(StepLoaderError::ParseError("Expected Float32x3 positions".to_string())),
-        },
-        None => return Err(StepLoaderError::ParseError("No position attribute found".to_string())),
-    };
-
-    let mut indices: Vec<u32> = match mesh.indices() {
-        Some(indices) => match indices {
-            Indices::U32(idx) => idx.to_vec(),
-            Indices::U16(idx) => idx.iter().map(|&i| i as u32).collect(),
-        },
-        None => return Err(StepLoaderError::ParseError("No indices found".to_string())),
-    };
-
-    if !indices.is_empty() && !positions.is_empty() {
-        meshopt::optimize_vertex_cache_in_place(&mut indices, positions.len());
-        
-        *mesh.indices_mut().unwrap() = Indices::U32(indices);
-    }
-
-    Ok(())
-}
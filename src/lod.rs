@@ -0,0 +1,97 @@
+//! Levels of detail: simplified copies of each scene mesh, and a system swapping between them
+//! by distance to the camera.
+
+use bevy_asset::Handle;
+use bevy_camera::{Camera, primitives::Aabb};
+use bevy_ecs::prelude::*;
+use bevy_mesh::{Mesh, Mesh3d};
+use bevy_reflect::Reflect;
+use bevy_transform::components::GlobalTransform;
+
+use crate::{StepLoaderError, StepLoaderSettings};
+
+/// The levels of detail of a mesh entity in a STEP scene, added when
+/// [`StepLoaderSettings::lods`] isn't empty. [`update_step_lods`] keeps the entity's `Mesh3d`
+/// on the level matching its distance to the camera.
+#[derive(Component, Reflect, Debug, Clone, Default)]
+#[reflect(Component)]
+pub struct StepLod {
+    /// Ordered by distance, the first is the full mesh at distance zero.
+    pub levels: Vec<StepLodLevel>,
+}
+
+/// One level of a [`StepLod`].
+#[derive(Reflect, Debug, Clone, Default)]
+pub struct StepLodLevel {
+    pub mesh: Handle<Mesh>,
+    /// Camera distance from which this level is used, in world units.
+    pub distance: f32,
+    /// The share of the full mesh's triangles that was asked for.
+    pub ratio: f32,
+    /// The simplification error meshopt reports, relative to the mesh's size.
+    pub error: f32,
+}
+
+impl StepLod {
+    /// The level to show at `distance`.
+    pub fn level_at(&self, distance: f32) -> Option<&StepLodLevel> {
+        self.levels
+            .iter()
+            .rev()
+            .find(|l| distance >= l.distance)
+            .or(self.levels.first())
+    }
+}
+
+/// Switches every [`StepLod`] entity to the level for its distance to the nearest active camera.
+pub fn update_step_lods(
+    cameras: Query<(&Camera, &GlobalTransform)>,
+    mut meshes: Query<(&StepLod, &GlobalTransform, Option<&Aabb>, &mut Mesh3d)>,
+) {
+    let eyes: Vec<_> = cameras
+        .iter()
+        .filter(|(camera, _)| camera.is_active)
+        .map(|(_, transform)| transform.translation())
+        .collect();
+    if eyes.is_empty() {
+        return;
+    }
+
+    for (lod, transform, aabb, mut mesh) in &mut meshes {
+        // The middle of the geometry, parts aren't always modelled around their origin
+        let centre = transform.transform_point(aabb.map_or(Default::default(), |a| a.center.into()));
+        let distance = eyes.iter().map(|eye| eye.distance(centre)).fold(f32::INFINITY, f32::min);
+        if let Some(level) = lod.level_at(distance)
+            && mesh.0 != level.mesh
+        {
+            mesh.0 = level.mesh.clone();
+        }
+    }
+}
+
+/// Simplified copies of an indexed mesh, one per entry of [`StepLoaderSettings::lods`], with
+/// normals added, and the error of each.
+#[cfg(feature = "meshopt")]
+pub(crate) fn build_chain(mesh: &Mesh, settings: &StepLoaderSettings) -> Result<Vec<(Mesh, f32)>, StepLoaderError> {
+    settings
+        .lods
+        .iter()
+        .map(|level| {
+            let mut simplified = mesh.clone();
+            // Surface normals are exact wherever the vertex ended up, so only the others are redone
+            let recompute_normals = settings.normals != crate::NormalMode::Surface;
+            let report = crate::simplify(&mut simplified, level.ratio, settings.lod_max_error, recompute_normals)?;
+            crate::backend::add_normals(&mut simplified, settings)?;
+            Ok((simplified, report.error))
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "meshopt"))]
+pub(crate) fn build_chain(_mesh: &Mesh, settings: &StepLoaderSettings) -> Result<Vec<(Mesh, f32)>, StepLoaderError> {
+    if settings.lods.is_empty() {
+        Ok(Vec::new())
+    } else {
+        Err(StepLoaderError::FeatureDisabled("meshopt"))
+    }
+}
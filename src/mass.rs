@@ -0,0 +1,183 @@
+//! Volume, area, centre of mass and inertia of a solid, from its triangles.
+//!
+//! The volume integrals run over the tetrahedra each triangle makes with a reference point,
+//! which adds up to the enclosed volume for any closed mesh and stays close for the small gaps
+//! tessellating faces independently leaves.
+
+use bevy_math::{DMat3, DVec3};
+use bevy_mesh::{Mesh, VertexAttributeValues};
+
+use crate::StepLoaderError;
+
+/// The physical properties of a solid, in the units of its mesh.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct MassProperties {
+    pub volume: f64,
+    pub area: f64,
+    /// The centre of mass, assuming uniform density, in the mesh's frame.
+    pub centroid: DVec3,
+    /// Mass per unit volume, whatever units suit, `mass` and `inertia` follow.
+    pub density: f64,
+    pub mass: f64,
+    /// The inertia tensor about `centroid`, along the mesh's axes.
+    pub inertia: DMat3,
+    /// The same at a density of 1, the solid's shape alone, which `inertia` is `density`
+    /// times.
+    pub unit_inertia: DMat3,
+}
+
+impl MassProperties {
+    /// The properties of the solid `mesh` encloses. Triangles wound the wrong way round (all of
+    /// them) are fine, the volume comes out positive either way.
+    pub fn from_mesh(mesh: &Mesh, density: f64) -> Result<MassProperties, StepLoaderError> {
+        let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(VertexAttributeValues::Float32x3(positions)) => positions,
+            Some(_) => return Err(StepLoaderError::InvalidMesh("expected Float32x3 positions")),
+            None => return Err(StepLoaderError::InvalidMesh("no position attribute")),
+        };
+        let indices: Vec<usize> = match mesh.indices() {
+            Some(indices) => indices.iter().collect(),
+            None => (0..positions.len()).collect(),
+        };
+        let positions: Vec<DVec3> = positions.iter().map(|p| DVec3::from(p.map(f64::from))).collect();
+        Ok(Self::from_triangles(&positions, &indices, density))
+    }
+
+    pub(crate) fn from_triangles(positions: &[DVec3], indices: &[usize], density: f64) -> MassProperties {
+        // Integrate about a point inside the mesh's bounds, far from the origin the products
+        // below would lose precision
+        let (min, max) = positions
+            .iter()
+            .fold((DVec3::INFINITY, DVec3::NEG_INFINITY), |(min, max), p| (min.min(*p), max.max(*p)));
+        let reference = if positions.is_empty() { DVec3::ZERO } else { (min + max) / 2.0 };
+
+        let mut volume = 0.0;
+        let mut area = 0.0;
+        let mut first_moment = DVec3::ZERO;
+        // Integral of p p^T over the volume, about `reference`
+        let mut second_moment = DMat3::ZERO;
+        let outer = |p: DVec3| DMat3::from_cols(p * p.x, p * p.y, p * p.z);
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i]] - reference);
+            area += (b - a).cross(c - a).length() / 2.0;
+
+            // Six times the signed volume of the tetrahedron (reference, a, b, c)
+            let det = a.dot(b.cross(c));
+            volume += det / 6.0;
+            let sum = a + b + c;
+            first_moment += sum * (det / 24.0);
+            second_moment += (outer(a) + outer(b) + outer(c) + outer(sum)) * (det / 120.0);
+        }
+
+        // Inside out, every integral has the wrong sign
+        if volume < 0.0 {
+            volume = -volume;
+            first_moment = -first_moment;
+            second_moment = -second_moment;
+        }
+        if volume <= f64::EPSILON {
+            return MassProperties {
+                area,
+                density,
+                centroid: reference,
+                ..Default::default()
+            };
+        }
+
+        let centroid = first_moment / volume;
+        // Move the second moment to the centroid, then turn it into an inertia tensor
+        let central = second_moment - outer(centroid) * volume;
+        let trace = central.x_axis.x + central.y_axis.y + central.z_axis.z;
+        let unit_inertia = DMat3::from_diagonal(DVec3::splat(trace)) - central;
+
+        MassProperties {
+            volume,
+            area,
+            centroid: centroid + reference,
+            density,
+            mass: volume * density,
+            inertia: unit_inertia * density,
+            unit_inertia,
+        }
+    }
+
+    /// The same solid made of something else.
+    pub fn with_density(&self, density: f64) -> MassProperties {
+        MassProperties {
+            density,
+            mass: self.volume * density,
+            inertia: self.unit_inertia * density,
+            ..*self
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A unit cube with its low corner at `origin`, wound outwards.
+    fn cube(origin: DVec3) -> (Vec<DVec3>, Vec<usize>) {
+        let positions = (0..8)
+            .map(|i| origin + DVec3::new((i & 1) as f64, (i >> 1 & 1) as f64, (i >> 2 & 1) as f64))
+            .collect();
+        #[rustfmt::skip]
+        let indices = vec![
+            0, 2, 1, 1, 2, 3, // -z
+            4, 5, 6, 5, 7, 6, // +z
+            0, 1, 4, 1, 5, 4, // -y
+            2, 6, 3, 3, 6, 7, // +y
+            0, 4, 2, 2, 4, 6, // -x
+            1, 3, 5, 3, 7, 5, // +x
+        ];
+        (positions, indices)
+    }
+
+    #[test]
+    fn unit_cube() {
+        let (positions, indices) = cube(DVec3::new(1.0, 2.0, 3.0));
+        let mass = MassProperties::from_triangles(&positions, &indices, 2.0);
+        assert!((mass.volume - 1.0).abs() < 1e-12);
+        assert!((mass.area - 6.0).abs() < 1e-12);
+        assert!(mass.centroid.distance(DVec3::new(1.5, 2.5, 3.5)) < 1e-12);
+        assert!((mass.mass - 2.0).abs() < 1e-12);
+        // m (a² + a²) / 12 about every axis, and no products of inertia
+        assert!(mass.inertia.abs_diff_eq(DMat3::from_diagonal(DVec3::splat(2.0 / 6.0)), 1e-12));
+
+        let lighter = mass.with_density(1.0);
+        assert!(lighter.inertia.abs_diff_eq(DMat3::from_diagonal(DVec3::splat(1.0 / 6.0)), 1e-12));
+        assert_eq!(lighter.mass, 1.0);
+        assert_eq!(lighter.unit_inertia, mass.unit_inertia);
+    }
+
+    #[test]
+    fn density_from_nothing() {
+        let (positions, indices) = cube(DVec3::ZERO);
+        let weightless = MassProperties::from_triangles(&positions, &indices, 0.0);
+        assert_eq!(weightless.mass, 0.0);
+        assert_eq!(weightless.inertia, DMat3::ZERO);
+
+        let steel = weightless.with_density(7.8);
+        assert!((steel.mass - 7.8).abs() < 1e-12);
+        assert!(steel.inertia.abs_diff_eq(DMat3::from_diagonal(DVec3::splat(7.8 / 6.0)), 1e-12));
+        assert!(steel.with_density(0.0).with_density(1.0).inertia.abs_diff_eq(weightless.unit_inertia, 1e-12));
+    }
+
+    #[test]
+    fn inside_out_and_flat() {
+        let (positions, mut indices) = cube(DVec3::ZERO);
+        let outwards = MassProperties::from_triangles(&positions, &indices, 1.0);
+        for triangle in indices.chunks_exact_mut(3) {
+            triangle.swap(1, 2);
+        }
+        let inwards = MassProperties::from_triangles(&positions, &indices, 1.0);
+        assert!((inwards.volume - outwards.volume).abs() < 1e-12);
+        assert!(inwards.centroid.distance(outwards.centroid) < 1e-12);
+        assert!(inwards.inertia.abs_diff_eq(outwards.inertia, 1e-12));
+
+        // One face on its own encloses nothing
+        let flat = MassProperties::from_triangles(&positions[..4], &indices[..6], 1.0);
+        assert_eq!(flat.volume, 0.0);
+        assert!((flat.area - 1.0).abs() < 1e-12);
+    }
+}
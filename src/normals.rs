@@ -0,0 +1,148 @@
+//! Normals that keep a CAD model's edges sharp.
+//!
+//! Averaging triangle normals smooths across the edges between faces and only approximates
+//! curved ones. Splitting the vertices along face boundaries and asking each face's surface
+//! for its normal gives the shading the CAD tool shows. Without the B-rep, splitting wherever
+//! the triangles meet at a steep enough angle comes close.
+
+use std::collections::HashMap;
+
+use bevy_math::{DVec3, Vec3};
+use bevy_mesh::{Indices, Mesh, VertexAttributeValues};
+
+use crate::StepLoaderError;
+use crate::assembly::Assembly;
+use crate::backend::RawMesh;
+use crate::brep::Brep;
+use crate::segment::FaceHit;
+
+/// Gives every vertex of `raw` the normal of the surface under it, after splitting vertices
+/// shared by triangles of different faces.
+///
+/// Surfaces we can evaluate (planes, cylinders, cones, spheres and tori) are evaluated here.
+/// For the rest (B-splines and friends) the backend's normal is kept, as both backends take it
+/// from the surface too, and failing that the face's own triangles are averaged. Which side of
+/// the surface the normal points to follows the backend, or the triangles' winding.
+pub(crate) fn surface_normals(raw: &mut RawMesh, hits: &[Option<FaceHit>], brep: &Brep, assembly: &Assembly) {
+    let owners = split_faces(raw, hits);
+
+    let positions: Vec<DVec3> = raw.positions.iter().map(|p| DVec3::from(p.map(f64::from))).collect();
+    let mut geometric = vec![DVec3::ZERO; positions.len()];
+    for triangle in raw.indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] as usize);
+        // Area weighted, by not normalising
+        let n = (positions[b] - positions[a]).cross(positions[c] - positions[a]);
+        for v in [a, b, c] {
+            geometric[v] += n;
+        }
+    }
+
+    let inverses: Vec<_> = assembly.nodes.iter().map(|n| n.world.inverse()).collect();
+    let normals = (0..positions.len())
+        .map(|v| {
+            let backend = raw.normals.get(v).and_then(|n| DVec3::from(n.map(f64::from)).try_normalize());
+            let reference = backend.or_else(|| geometric[v].try_normalize()).unwrap_or(DVec3::Y);
+            let analytic = owners[v].and_then(|hit| {
+                let surface = &brep.faces.get(&hit.face)?.surface;
+                let local = surface.normal(inverses[hit.node].transform_point3(positions[v]))?;
+                assembly.nodes[hit.node].world.transform_vector3(local).try_normalize()
+            });
+            let n = match analytic {
+                Some(n) if n.dot(reference) < 0.0 => -n,
+                Some(n) => n,
+                None => reference,
+            };
+            n.as_vec3().to_array()
+        })
+        .collect();
+    raw.normals = normals;
+}
+
+/// Gives each face its own copy of the vertices it shares with other faces, so normals can
+/// differ across the edge. Returns the face owning each vertex afterwards.
+pub(crate) fn split_faces(raw: &mut RawMesh, hits: &[Option<FaceHit>]) -> Vec<Option<FaceHit>> {
+    let mut owners: Vec<Option<Option<FaceHit>>> = vec![None; raw.positions.len()];
+    let mut copies: HashMap<(u32, Option<FaceHit>), u32> = HashMap::new();
+
+    for corner in 0..raw.indices.len() / 3 * 3 {
+        let hit = hits.get(corner / 3).copied().flatten();
+        let v = raw.indices[corner];
+        match owners[v as usize] {
+            None => owners[v as usize] = Some(hit),
+            Some(owner) if owner == hit => {}
+            Some(_) => {
+                raw.indices[corner] = *copies.entry((v, hit)).or_insert_with(|| {
+                    owners.push(Some(hit));
+                    raw.duplicate_vertex(v)
+                });
+            }
+        }
+    }
+    owners.into_iter().map(Option::flatten).collect()
+}
+
+/// Gives `mesh` normals that are smooth across edges where its triangles meet at less than
+/// `angle_degrees`, and sharp where they meet at more, splitting vertices as needed.
+///
+/// Unlike [`NormalMode::Surface`](crate::NormalMode::Surface) this only looks at the triangles,
+/// so it works on any mesh: OCCT output, simplified meshes, meshes from elsewhere. Vertices at
+/// the same position are treated as one, so faces tessellated separately still smooth together.
+pub fn compute_crease_normals(mesh: &mut Mesh, angle_degrees: f32) -> Result<(), StepLoaderError> {
+    let positions: Vec<Vec3> = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+        Some(VertexAttributeValues::Float32x3(positions)) => positions.iter().map(|p| Vec3::from(*p)).collect(),
+        Some(_) => return Err(StepLoaderError::InvalidMesh("expected Float32x3 positions")),
+        None => return Err(StepLoaderError::InvalidMesh("no position attribute")),
+    };
+    let mut indices: Vec<u32> = match mesh.indices() {
+        Some(indices) => indices.iter().map(|i| i as u32).collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    indices.truncate(indices.len() / 3 * 3);
+
+    // Area weighted, by not normalising
+    let face_normals: Vec<Vec3> = indices
+        .chunks_exact(3)
+        .map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[t[i] as usize]);
+            (b - a).cross(c - a)
+        })
+        .collect();
+
+    // Every triangle corner at each position
+    let key = |corner: usize| positions[indices[corner] as usize].to_array().map(f32::to_bits);
+    let mut corners: HashMap<[u32; 3], Vec<usize>> = HashMap::new();
+    for corner in 0..indices.len() {
+        corners.entry(key(corner)).or_default().push(corner);
+    }
+
+    // A corner's normal is the sum of the faces around its position within the angle of its
+    // own face. Corners of the same vertex that end up with the same normal share it again
+    let cos_threshold = angle_degrees.to_radians().cos();
+    let mut order: Vec<u32> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+    let mut vertices: HashMap<(u32, [u32; 3]), u32> = HashMap::new();
+    let mut new_indices = vec![0; indices.len()];
+    for (corner, new_index) in new_indices.iter_mut().enumerate() {
+        let own = face_normals[corner / 3].normalize_or_zero();
+        let sum: Vec3 = corners[&key(corner)]
+            .iter()
+            .map(|&other| face_normals[other / 3])
+            .filter(|n| n.normalize_or_zero().dot(own) >= cos_threshold)
+            .sum();
+        let normal = sum.try_normalize().unwrap_or(own).to_array();
+        let v = indices[corner];
+        *new_index = *vertices.entry((v, normal.map(f32::to_bits))).or_insert_with(|| {
+            order.push(v);
+            normals.push(normal);
+            (order.len() - 1) as u32
+        });
+    }
+
+    // Same trick as simplify_mesh, `order` maps the new vertices to the old ones and
+    // duplicate_vertices carries every attribute across
+    mesh.insert_indices(Indices::U32(order));
+    mesh.duplicate_vertices();
+    mesh.insert_indices(Indices::U32(new_indices));
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
+    Ok(())
+}
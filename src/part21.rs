@@ -0,0 +1,697 @@
+//! A small reader for the ISO 10303-21 exchange structure (the text format of a `.step` file).
+//!
+//! Foxtrot and OCCT only hand back triangles, everything else the loader wants from the file
+//! (product structure, styles, units, the header...) is read from the entity graph this builds.
+//! It's deliberately untyped: each instance is a list of records, each record a keyword and
+//! its parameters, and callers pick out what they understand.
+
+use std::collections::HashMap;
+
+use crate::{EntityRef, SourceLocation, SyntaxError};
+
+/// A parameter value inside a record.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    /// `#123`
+    Ref(u64),
+    Integer(i64),
+    Real(f64),
+    String(String),
+    /// `.NAME.`, this includes the booleans `.T.` and `.F.`
+    Enum(String),
+    Binary(String),
+    List(Vec<Value>),
+    /// A typed parameter such as `LENGTH_MEASURE(0.01)`.
+    Typed(String, Box<Value>),
+    /// `$`
+    Null,
+    /// `*`
+    Derived,
+}
+
+impl Value {
+    pub(crate) fn as_id(&self) -> Option<u64> {
+        match self {
+            Value::Ref(id) => Some(*id),
+            Value::Typed(_, v) => v.as_id(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_f64(&self) -> Option<f64> {
+        match self {
+            Value::Real(v) => Some(*v),
+            Value::Integer(v) => Some(*v as f64),
+            Value::Typed(_, v) => v.as_f64(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            Value::Typed(_, v) => v.as_str(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_enum(&self) -> Option<&str> {
+        match self {
+            Value::Enum(s) => Some(s),
+            Value::Typed(_, v) => v.as_enum(),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// The ids referenced by a list parameter, skipping anything that isn't a reference.
+    pub(crate) fn refs(&self) -> impl Iterator<Item = u64> + '_ {
+        self.as_list().unwrap_or_default().iter().filter_map(Value::as_id)
+    }
+}
+
+/// `KEYWORD(param, param, ...)`
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Record {
+    pub keyword: String,
+    pub params: Vec<Value>,
+}
+
+impl Record {
+    pub(crate) fn param(&self, index: usize) -> &Value {
+        self.params.get(index).unwrap_or(&Value::Null)
+    }
+}
+
+/// One `#id = ...;` instance from the DATA section.
+///
+/// Simple instances have a single record, complex ones (`#id = (A() B() C());`) have one per
+/// partial type.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Instance {
+    pub records: Vec<Record>,
+    /// Byte offset of the instance's `#id` in the file.
+    pub offset: usize,
+}
+
+impl Instance {
+    /// The record with the given keyword, which for a simple instance is the whole instance.
+    pub(crate) fn record(&self, keyword: &str) -> Option<&Record> {
+        self.records.iter().find(|r| r.keyword == keyword)
+    }
+
+    pub(crate) fn is(&self, keyword: &str) -> bool {
+        self.record(keyword).is_some()
+    }
+}
+
+/// A parsed exchange structure.
+#[derive(Debug, Default)]
+pub(crate) struct StepData {
+    /// Records from the HEADER section, in file order.
+    pub header: Vec<Record>,
+    pub instances: HashMap<u64, Instance>,
+    /// Byte offset of the start of each line, for turning offsets into line numbers.
+    pub line_starts: Vec<usize>,
+}
+
+impl StepData {
+    pub(crate) fn get(&self, id: u64) -> Option<&Instance> {
+        self.instances.get(&id)
+    }
+
+    /// The record `keyword` of instance `id`, if the instance exists and has one.
+    pub(crate) fn record(&self, id: u64, keyword: &str) -> Option<&Record> {
+        self.get(id)?.record(keyword)
+    }
+
+    /// Instance `id` and where it's defined, for error messages.
+    pub(crate) fn entity(&self, id: u64) -> EntityRef {
+        EntityRef {
+            id,
+            location: self.get(id).map(|i| SourceLocation::new(&self.line_starts, i.offset)),
+        }
+    }
+
+    /// The HEADER record `keyword`, if the file has one.
+    pub(crate) fn header_record(&self, keyword: &str) -> Option<&Record> {
+        self.header.iter().find(|r| r.keyword == keyword)
+    }
+
+    /// Every instance with a record named `keyword`, in no particular order.
+    pub(crate) fn all<'a>(&'a self, keyword: &'a str) -> impl Iterator<Item = (u64, &'a Record)> + 'a {
+        self.instances
+            .iter()
+            .filter_map(move |(id, inst)| inst.record(keyword).map(|r| (*id, r)))
+    }
+}
+
+pub(crate) fn parse(input: &[u8]) -> Result<StepData, SyntaxError> {
+    let line_starts = line_starts(input);
+    let mut data = Parser {
+        input,
+        pos: 0,
+        line_starts: &line_starts,
+    }
+    .file()?;
+    data.line_starts = line_starts;
+    Ok(data)
+}
+
+fn line_starts(input: &[u8]) -> Vec<usize> {
+    std::iter::once(0)
+        .chain(input.iter().enumerate().filter(|(_, b)| **b == b'\n').map(|(i, _)| i + 1))
+        .collect()
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+    line_starts: &'a [usize],
+}
+
+impl Parser<'_> {
+    fn error<T>(&self, message: impl Into<String>) -> Result<T, SyntaxError> {
+        Err(SyntaxError {
+            location: SourceLocation::new(self.line_starts, self.pos),
+            message: message.into(),
+        })
+    }
+
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_whitespace();
+        self.input.get(self.pos).copied()
+    }
+
+    fn skip_whitespace(&mut self) {
+        loop {
+            match self.input.get(self.pos..self.pos + 2) {
+                Some(b"/*") => {
+                    self.pos = match find(&self.input[self.pos + 2..], b"*/") {
+                        Some(end) => self.pos + 2 + end + 2,
+                        None => self.input.len(),
+                    };
+                }
+                _ => match self.input.get(self.pos) {
+                    Some(c) if c.is_ascii_whitespace() => self.pos += 1,
+                    _ => return,
+                },
+            }
+        }
+    }
+
+    fn expect(&mut self, c: u8) -> Result<(), SyntaxError> {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            self.error(format!("expected '{}'", c as char))
+        }
+    }
+
+    fn eat(&mut self, c: u8) -> bool {
+        let found = self.peek() == Some(c);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    /// A keyword, which may include `-` for the section markers such as `ISO-10303-21`.
+    fn keyword(&mut self) -> Result<String, SyntaxError> {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self
+            .input
+            .get(self.pos)
+            .is_some_and(|c| c.is_ascii_alphanumeric() || *c == b'_' || *c == b'-' || *c == b'!')
+        {
+            self.pos += 1;
+        }
+        if start == self.pos {
+            return self.error("expected a keyword");
+        }
+        Ok(String::from_utf8_lossy(&self.input[start..self.pos]).into_owned())
+    }
+
+    fn file(mut self) -> Result<StepData, SyntaxError> {
+        let mut data = StepData::default();
+        if self.keyword()? != "ISO-10303-21" {
+            return self.error("missing ISO-10303-21 marker");
+        }
+        self.expect(b';')?;
+
+        loop {
+            let section = self.keyword()?;
+            match section.as_str() {
+                "HEADER" => {
+                    self.expect(b';')?;
+                    while !self.end_of_section()? {
+                        data.header.push(self.record()?);
+                        self.expect(b';')?;
+                    }
+                }
+                "DATA" => {
+                    // DATA may carry a name and schema in later editions of the standard
+                    if self.peek() == Some(b'(') {
+                        self.parameters()?;
+                    }
+                    self.expect(b';')?;
+                    while !self.end_of_section()? {
+                        let (id, instance) = self.instance()?;
+                        data.instances.insert(id, instance);
+                    }
+                }
+                "ANCHOR" | "REFERENCE" | "SIGNATURE" => {
+                    // Nothing here is useful to us, skip to the end of the section
+                    match find(&self.input[self.pos..], b"ENDSEC") {
+                        Some(end) => self.pos += end,
+                        None => return self.error("unterminated section"),
+                    }
+                    self.end_of_section()?;
+                }
+                "END-ISO-10303-21" => {
+                    self.expect(b';')?;
+                    return Ok(data);
+                }
+                other => return self.error(format!("unexpected section {}", other)),
+            }
+        }
+    }
+
+    /// Consumes `ENDSEC;` if it's next.
+    fn end_of_section(&mut self) -> Result<bool, SyntaxError> {
+        self.skip_whitespace();
+        if self.input[self.pos..].starts_with(b"ENDSEC") {
+            self.pos += b"ENDSEC".len();
+            self.expect(b';')?;
+            Ok(true)
+        } else if self.pos >= self.input.len() {
+            self.error("unexpected end of file")
+        } else {
+            Ok(false)
+        }
+    }
+
+    fn instance(&mut self) -> Result<(u64, Instance), SyntaxError> {
+        self.skip_whitespace();
+        let offset = self.pos;
+        self.expect(b'#')?;
+        let id = self.id()?;
+        self.expect(b'=')?;
+        let records = if self.eat(b'(') {
+            let mut records = Vec::new();
+            while !self.eat(b')') {
+                records.push(self.record()?);
+            }
+            records
+        } else {
+            vec![self.record()?]
+        };
+        self.expect(b';')?;
+        Ok((id, Instance { records, offset }))
+    }
+
+    fn id(&mut self) -> Result<u64, SyntaxError> {
+        let start = self.pos;
+        while self.input.get(self.pos).is_some_and(u8::is_ascii_digit) {
+            self.pos += 1;
+        }
+        std::str::from_utf8(&self.input[start..self.pos])
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .map_or_else(|| self.error("expected an instance id"), Ok)
+    }
+
+    fn record(&mut self) -> Result<Record, SyntaxError> {
+        let keyword = self.keyword()?;
+        let params = self.parameters()?;
+        Ok(Record { keyword, params })
+    }
+
+    fn parameters(&mut self) -> Result<Vec<Value>, SyntaxError> {
+        self.expect(b'(')?;
+        let mut params = Vec::new();
+        if self.eat(b')') {
+            return Ok(params);
+        }
+        loop {
+            params.push(self.value()?);
+            if self.eat(b')') {
+                return Ok(params);
+            }
+            self.expect(b',')?;
+        }
+    }
+
+    fn value(&mut self) -> Result<Value, SyntaxError> {
+        match self.peek() {
+            Some(b'#') => {
+                self.pos += 1;
+                Ok(Value::Ref(self.id()?))
+            }
+            Some(b'$') => {
+                self.pos += 1;
+                Ok(Value::Null)
+            }
+            Some(b'*') => {
+                self.pos += 1;
+                Ok(Value::Derived)
+            }
+            Some(b'\'') => self.string().map(Value::String),
+            Some(b'"') => {
+                self.pos += 1;
+                let start = self.pos;
+                match find(&self.input[start..], b"\"") {
+                    Some(end) => {
+                        self.pos = start + end + 1;
+                        Ok(Value::Binary(String::from_utf8_lossy(&self.input[start..start + end]).into_owned()))
+                    }
+                    None => self.error("unterminated binary"),
+                }
+            }
+            Some(b'.') if self.input.get(self.pos + 1).is_some_and(u8::is_ascii_alphabetic) => {
+                self.pos += 1;
+                let name = self.keyword()?;
+                self.expect(b'.')?;
+                Ok(Value::Enum(name))
+            }
+            Some(b'(') => self.parameters().map(Value::List),
+            Some(c) if c.is_ascii_digit() || c == b'-' || c == b'+' || c == b'.' => self.number(),
+            Some(c) if c.is_ascii_alphabetic() => {
+                let name = self.keyword()?;
+                self.expect(b'(')?;
+                let inner = if self.peek() == Some(b')') { Value::Null } else { self.value()? };
+                self.expect(b')')?;
+                Ok(Value::Typed(name, Box::new(inner)))
+            }
+            _ => self.error("expected a parameter"),
+        }
+    }
+
+    fn number(&mut self) -> Result<Value, SyntaxError> {
+        let start = self.pos;
+        let mut real = false;
+        while let Some(&c) = self.input.get(self.pos) {
+            match c {
+                b'0'..=b'9' | b'+' | b'-' => {}
+                b'.' | b'E' | b'e' => real = true,
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let text = std::str::from_utf8(&self.input[start..self.pos]).unwrap_or_default();
+        let value = if real {
+            // Part 21 allows `1.` and `1.E3`, which Rust's parser is happy with
+            text.parse().ok().map(Value::Real)
+        } else {
+            text.parse().ok().map(Value::Integer)
+        };
+        value.map_or_else(|| self.error(format!("bad number '{}'", text)), Ok)
+    }
+
+    fn string(&mut self) -> Result<String, SyntaxError> {
+        self.pos += 1;
+        let mut raw = Vec::new();
+        loop {
+            match self.input.get(self.pos) {
+                Some(b'\'') if self.input.get(self.pos + 1) == Some(&b'\'') => {
+                    raw.push(b'\'');
+                    self.pos += 2;
+                }
+                Some(b'\'') => {
+                    self.pos += 1;
+                    return Ok(decode_string(&raw));
+                }
+                // Line breaks inside strings are just the exporter wrapping lines
+                Some(b'\r' | b'\n') => self.pos += 1,
+                Some(&c) => {
+                    raw.push(c);
+                    self.pos += 1;
+                }
+                None => return self.error("unterminated string"),
+            }
+        }
+    }
+}
+
+/// Decodes the `\S\`, `\P?\`, `\X\`, `\X2\` and `\X4\` control directives and `\\` escapes.
+fn decode_string(raw: &[u8]) -> String {
+    let text = String::from_utf8_lossy(raw);
+    if !text.contains('\\') {
+        return text.into_owned();
+    }
+
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text.as_ref();
+    // The ISO 8859 part `\S\` characters come from, every string starts on 8859-1
+    let mut page = b'A';
+    while let Some(i) = rest.find('\\') {
+        out.push_str(&rest[..i]);
+        rest = &rest[i..];
+        if let Some(tail) = rest.strip_prefix("\\\\") {
+            out.push('\\');
+            rest = tail;
+        } else if let Some(tail) = rest.strip_prefix("\\S\\") {
+            // The upper half of the code page, as the character 0x80 below
+            let mut chars = tail.chars();
+            match chars.next() {
+                Some(c) => {
+                    out.push(match u8::try_from(c) {
+                        Ok(byte) if byte < 0x80 => code_page(page, byte + 0x80),
+                        _ => char::REPLACEMENT_CHARACTER,
+                    });
+                    rest = chars.as_str();
+                }
+                None => {
+                    out.push('\\');
+                    rest = &rest[1..];
+                }
+            }
+        } else if let Some(&[b'\\', b'P', letter @ b'A'..=b'I', b'\\', ..]) = rest.as_bytes().get(..4) {
+            // Switches the code page, and prints nothing
+            page = letter;
+            rest = &rest[4..];
+        } else if let Some(tail) = rest.strip_prefix("\\X\\") {
+            // A single ISO 8859-1 character as two hex digits
+            match tail.get(..2).and_then(|h| u8::from_str_radix(h, 16).ok()) {
+                Some(b) => {
+                    out.push(b as char);
+                    rest = &tail[2..];
+                }
+                None => {
+                    out.push('\\');
+                    rest = &rest[1..];
+                }
+            }
+        } else if let Some((digits, tail)) = rest
+            .strip_prefix("\\X2\\")
+            .map(|t| (4, t))
+            .or_else(|| rest.strip_prefix("\\X4\\").map(|t| (8, t)))
+        {
+            // Runs of UTF-16/UTF-32 code units in hex, terminated by \X0\
+            let end = tail.find("\\X0\\").unwrap_or(tail.len());
+            let hex = &tail[..end];
+            // The run is whatever the file holds, so work on bytes: a unit that isn't all hex
+            // digits, non-ASCII included, is a replacement character rather than a panic
+            let units = hex.as_bytes().chunks(digits).map(|unit| {
+                std::str::from_utf8(unit)
+                    .ok()
+                    .filter(|h| h.len() == digits && h.bytes().all(|b| b.is_ascii_hexdigit()))
+                    .and_then(|h| u32::from_str_radix(h, 16).ok())
+            });
+            if digits == 4 {
+                let units: Vec<u16> = units.map(|u| u.map_or(0xfffd, |u| u as u16)).collect();
+                out.extend(char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+            } else {
+                out.extend(units.map(|u| u.and_then(char::from_u32).unwrap_or(char::REPLACEMENT_CHARACTER)));
+            }
+            rest = tail.get(end + 4..).unwrap_or_default();
+        } else {
+            out.push('\\');
+            rest = &rest[1..];
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// `byte` in ISO 8859-1 to 8859-9, named `A` to `I` as by `\P?\`. Bytes a part leaves
+/// undefined are replacement characters.
+fn code_page(page: u8, byte: u8) -> char {
+    const UNDEFINED: char = char::REPLACEMENT_CHARACTER;
+    // 8859-2 to -4 from 0xa0, they move characters about too much for anything but a table
+    const LATIN_2: &str = "\u{a0}Ą˘Ł¤ĽŚ§¨ŠŞŤŹ\u{ad}ŽŻ°ą˛ł´ľśˇ¸šşťź˝žżŔÁÂĂÄĹĆÇČÉĘËĚÍÎĎĐŃŇÓÔŐÖ×ŘŮÚŰÜÝŢßŕáâăäĺćçčéęëěíîďđńňóôőö÷řůúűüýţ˙";
+    const LATIN_3: &str = "\u{a0}Ħ˘£¤\u{fffd}Ĥ§¨İŞĞĴ\u{ad}\u{fffd}Ż°ħ²³´µĥ·¸ışğĵ½\u{fffd}żÀÁÂ\u{fffd}ÄĊĈÇÈÉÊËÌÍÎÏ\u{fffd}ÑÒÓÔĠÖ×ĜÙÚÛÜŬŜßàáâ\u{fffd}äċĉçèéêëìíîï\u{fffd}ñòóôġö÷ĝùúûüŭŝ˙";
+    const LATIN_4: &str = "\u{a0}ĄĸŖ¤ĨĻ§¨ŠĒĢŦ\u{ad}Ž¯°ą˛ŗ´ĩļˇ¸šēģŧŊžŋĀÁÂÃÄÅÆĮČÉĘËĖÍÎĪĐŅŌĶÔÕÖ×ØŲÚÛÜŨŪßāáâãäåæįčéęëėíîīđņōķôõö÷øųúûüũū˙";
+
+    let latin_1 = char::from(byte);
+    if byte < 0xa0 {
+        // ASCII and the C1 controls are the same in every part
+        return latin_1;
+    }
+    let table = |table: &str| table.chars().nth(usize::from(byte - 0xa0)).unwrap_or(UNDEFINED);
+    let offset = |base: u32, first: u8| char::from_u32(base + u32::from(byte - first)).unwrap_or(UNDEFINED);
+    match page {
+        b'B' => table(LATIN_2),
+        b'C' => table(LATIN_3),
+        b'D' => table(LATIN_4),
+        // Cyrillic
+        b'E' => match byte {
+            0xa0 | 0xad => latin_1,
+            0xf0 => '№',
+            0xfd => '§',
+            _ => offset(0x0400, 0xa0),
+        },
+        // Arabic
+        b'F' => match byte {
+            0xa0 | 0xa4 | 0xad => latin_1,
+            0xac => '\u{060c}',
+            0xbb => '\u{061b}',
+            0xbf => '\u{061f}',
+            0xc1..=0xda => offset(0x0621, 0xc1),
+            0xe0..=0xf2 => offset(0x0640, 0xe0),
+            _ => UNDEFINED,
+        },
+        // Greek
+        b'G' => match byte {
+            0xa1 => '\u{2018}',
+            0xa2 => '\u{2019}',
+            0xa4 => '€',
+            0xa5 => '\u{20af}',
+            0xaa => '\u{037a}',
+            0xaf => '\u{2015}',
+            0xae | 0xd2 | 0xff => UNDEFINED,
+            0xb4..=0xb6 | 0xb8..=0xba | 0xbc | 0xbe..=0xfe => offset(0x0384, 0xb4),
+            _ => latin_1,
+        },
+        // Hebrew
+        b'H' => match byte {
+            0xaa => '×',
+            0xba => '÷',
+            0xdf => '\u{2017}',
+            0xe0..=0xfa => offset(0x05d0, 0xe0),
+            0xfd => '\u{200e}',
+            0xfe => '\u{200f}',
+            0xa1 | 0xbf..=0xde | 0xfb | 0xfc | 0xff => UNDEFINED,
+            _ => latin_1,
+        },
+        // Turkish, 8859-1 but for six letters
+        b'I' => match byte {
+            0xd0 => 'Ğ',
+            0xdd => 'İ',
+            0xde => 'Ş',
+            0xf0 => 'ğ',
+            0xfd => 'ı',
+            0xfe => 'ş',
+            _ => latin_1,
+        },
+        _ => latin_1,
+    }
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE: &str = "ISO-10303-21;
+HEADER;
+FILE_DESCRIPTION(('a part'),'2;1');
+FILE_NAME('part.step','2024-01-01T00:00:00',('someone'),(''),'','','');
+FILE_SCHEMA(('AUTOMOTIVE_DESIGN'));
+ENDSEC;
+DATA;
+/* a comment */
+#1=CARTESIAN_POINT('origin',(0.,1.5,-2.E1));
+#2=PRODUCT('Bracket','',$,(#3));
+#3=( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) );
+#4=MEASURE_REPRESENTATION_ITEM('volume',VOLUME_MEASURE(12),#3);
+ENDSEC;
+END-ISO-10303-21;
+";
+
+    #[test]
+    fn parses_simple_and_complex_instances() {
+        let data = parse(FILE.as_bytes()).unwrap();
+        assert_eq!(data.header_record("FILE_SCHEMA").unwrap().keyword, "FILE_SCHEMA");
+
+        let point = data.record(1, "CARTESIAN_POINT").unwrap();
+        let coords: Vec<f64> = point.param(1).as_list().unwrap().iter().filter_map(Value::as_f64).collect();
+        assert_eq!(coords, [0.0, 1.5, -20.0]);
+
+        assert_eq!(data.record(2, "PRODUCT").unwrap().param(0).as_str(), Some("Bracket"));
+        assert_eq!(data.record(2, "PRODUCT").unwrap().param(2), &Value::Null);
+        assert_eq!(data.record(2, "PRODUCT").unwrap().param(3).refs().collect::<Vec<_>>(), [3]);
+
+        let unit = data.get(3).unwrap();
+        assert_eq!(unit.records.len(), 3);
+        assert_eq!(unit.record("SI_UNIT").unwrap().param(1).as_enum(), Some("METRE"));
+        assert_eq!(unit.record("NAMED_UNIT").unwrap().param(0), &Value::Derived);
+
+        let measure = data.record(4, "MEASURE_REPRESENTATION_ITEM").unwrap().param(1);
+        assert!(matches!(measure, Value::Typed(keyword, _) if keyword == "VOLUME_MEASURE"));
+        assert_eq!(measure.as_f64(), Some(12.0));
+    }
+
+    #[test]
+    fn reports_where_syntax_errors_are() {
+        let error = parse(b"ISO-10303-21;\nHEADER;\nENDSEC;\nDATA;\n#1=POINT(1.,;\nENDSEC;\n").unwrap_err();
+        assert_eq!(error.location.line, 5);
+    }
+
+    #[test]
+    fn decodes_control_directives() {
+        assert_eq!(decode_string(b"plain"), "plain");
+        assert_eq!(decode_string(b"back\\\\slash"), "back\\slash");
+        assert_eq!(decode_string(b"caf\\X\\E9"), "caf\u{e9}");
+        assert_eq!(decode_string(b"\\X2\\00C400D6\\X0\\-Teil"), "\u{c4}\u{d6}-Teil");
+        assert_eq!(decode_string(b"\\X2\\D83DDE00\\X0\\"), "\u{1f600}");
+        assert_eq!(decode_string(b"\\X4\\0001F600\\X0\\"), "\u{1f600}");
+    }
+
+    #[test]
+    fn decodes_code_page_characters() {
+        // 0x44 + 0x80 is an umlaut in 8859-1, which every string starts on
+        assert_eq!(decode_string(b"M\\S\\vller-\\S\\D"), "M\u{f6}ller-\u{c4}");
+        assert_eq!(decode_string(b"\\PE\\\\S\\?\\S\\^"), "\u{41f}\u{43e}");
+        assert_eq!(decode_string(b"\\PB\\\\S\\)koda \\PA\\\\S\\i"), "\u{160}koda \u{e9}");
+        assert_eq!(decode_string(b"\\PI\\\\S\\]stanbul"), "\u{130}stanbul");
+        // A code page switch on its own prints nothing, and unknown ones are left as they are
+        assert_eq!(decode_string(b"\\PA\\plain"), "plain");
+        assert_eq!(decode_string(b"\\PZ\\"), "\\PZ\\");
+        assert_eq!(decode_string(b"end\\S\\"), "end\\S\\");
+    }
+
+    #[test]
+    fn code_page_tables_line_up() {
+        // The last of each table lines up with 0xff
+        assert_eq!(code_page(b'B', 0xff), '\u{2d9}');
+        assert_eq!(code_page(b'C', 0xff), '\u{2d9}');
+        assert_eq!(code_page(b'D', 0xff), '\u{2d9}');
+        assert_eq!(code_page(b'G', 0xfe), '\u{3ce}');
+        assert_eq!(code_page(b'H', 0xfa), '\u{5ea}');
+    }
+
+    #[test]
+    fn malformed_runs_become_replacement_characters() {
+        // Non-ASCII inside the run used to be sliced through the middle of a character
+        let decoded = decode_string("\\X2\\\u{e9}\u{e9}0041\\X0\\".as_bytes());
+        assert!(decoded.contains(char::REPLACEMENT_CHARACTER));
+        assert!(decoded.ends_with('A'));
+
+        assert_eq!(decode_string(b"\\X2\\00G1\\X0\\"), "\u{fffd}");
+        assert_eq!(decode_string(b"\\X4\\FFFFFFFF\\X0\\"), "\u{fffd}");
+        // An unterminated run takes the rest of the string
+        assert_eq!(decode_string(b"\\X2\\0041"), "A");
+    }
+}
@@ -0,0 +1,73 @@
+//! Which B-rep face each triangle came from, kept on the meshes for picking.
+//!
+//! Every vertex carries the index of its face in [`StepAsset::faces`](crate::StepAsset::faces)
+//! as [`ATTRIBUTE_STEP_FACE`]. Vertices are split along face boundaries, so the first vertex
+//! of a triangle is enough to know its face, and unlike a per-triangle table the attribute
+//! survives meshopt reordering, simplification and flat shading.
+
+use std::collections::BTreeMap;
+
+use bevy_mesh::{Indices, Mesh, MeshVertexAttribute, VertexAttributeValues, VertexFormat};
+
+use crate::backend::RawMesh;
+use crate::brep::Brep;
+use crate::segment::FaceHit;
+
+/// The index into [`StepAsset::faces`](crate::StepAsset::faces) of the face each vertex lies
+/// on, `u32::MAX` for triangles that couldn't be matched to a face.
+pub const ATTRIBUTE_STEP_FACE: MeshVertexAttribute =
+    MeshVertexAttribute::new("Vertex_StepFace", 0x57e9_face, VertexFormat::Uint32);
+
+/// A face of the B-rep, as found under a triangle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StepFace {
+    /// The entity id of the face, `42` for `#42=ADVANCED_FACE(...)`.
+    pub id: u64,
+    /// The keyword of the face's surface, `PLANE`, `CYLINDRICAL_SURFACE`,
+    /// `B_SPLINE_SURFACE_WITH_KNOTS`...
+    pub surface_type: String,
+    /// The entity id of the solid owning the face, see [`StepBody::id`](crate::StepBody::id).
+    pub solid: u64,
+}
+
+/// The index of the face under `triangle` of `mesh`, for meshes loaded from a STEP file.
+///
+/// `triangle` is the `triangle_index` of a `bevy_picking` `MeshRayCast` hit.
+pub fn step_face_index(mesh: &Mesh, triangle: usize) -> Option<usize> {
+    let Some(VertexAttributeValues::Uint32(faces)) = mesh.attribute(ATTRIBUTE_STEP_FACE) else {
+        return None;
+    };
+    let vertex = match mesh.indices() {
+        Some(Indices::U32(indices)) => *indices.get(triangle * 3)? as usize,
+        Some(Indices::U16(indices)) => *indices.get(triangle * 3)? as usize,
+        None => triangle * 3,
+    };
+    match *faces.get(vertex)? {
+        u32::MAX => None,
+        face => Some(face as usize),
+    }
+}
+
+/// Tags every vertex of `raw` with its face, splitting vertices shared between faces, and
+/// returns the faces in the order the tags refer to them.
+pub(crate) fn assign_faces(raw: &mut RawMesh, hits: &[Option<FaceHit>], brep: &Brep) -> Vec<StepFace> {
+    let owners = crate::normals::split_faces(raw, hits);
+
+    // The same face placed twice by the assembly is still one face
+    let mut indices: BTreeMap<(u64, u64), u32> = owners.iter().flatten().map(|h| ((h.solid, h.face), 0)).collect();
+    let mut faces = Vec::with_capacity(indices.len());
+    for (i, ((solid, face), index)) in indices.iter_mut().enumerate() {
+        *index = i as u32;
+        faces.push(StepFace {
+            id: *face,
+            surface_type: brep.faces.get(face).map(|f| f.surface_type.clone()).unwrap_or_default(),
+            solid: *solid,
+        });
+    }
+
+    raw.faces = owners
+        .iter()
+        .map(|owner| owner.map_or(u32::MAX, |h| indices[&(h.solid, h.face)]))
+        .collect();
+    faces
+}
@@ -0,0 +1,150 @@
+//! Refining the backends' triangles down to the chord and angular tolerances.
+//!
+//! Foxtrot tessellates at a density of its own, and the OCCT bindings only take a chord
+//! tolerance. Once the triangles are matched to their faces, every edge inside a face whose
+//! midpoint strays further than [`chord_tolerance`](crate::StepLoaderSettings::chord_tolerance)
+//! from the face's surface, or whose ends' surface normals are further apart than
+//! [`angular_tolerance`](crate::StepLoaderSettings::angular_tolerance), is split with the new
+//! vertex on the surface. Edges along face boundaries keep the backend's sampling so that
+//! neighbouring faces still meet, and faces on surfaces we can't evaluate keep their triangles.
+
+use std::collections::HashMap;
+
+use bevy_math::{DAffine3, DVec3};
+
+use crate::StepLoaderSettings;
+use crate::assembly::Assembly;
+use crate::backend::RawMesh;
+use crate::brep::Brep;
+use crate::segment::FaceHit;
+
+/// Each pass halves the edges it splits, plenty to reach any tolerance the backend's own
+/// sampling leaves room for.
+const MAX_PASSES: usize = 8;
+/// Refining stops once the mesh has this many times the backend's triangles, for tolerances
+/// far finer than the model needs.
+const MAX_GROWTH: usize = 64;
+
+/// Splits the triangles of `raw` until their edges are within the settings' tolerances,
+/// keeping `hits` in step with them.
+pub(crate) fn refine(
+    raw: &mut RawMesh,
+    hits: &mut Vec<Option<FaceHit>>,
+    brep: &Brep,
+    assembly: &Assembly,
+    settings: &StepLoaderSettings,
+) {
+    let chord = settings.chord_tolerance.filter(|t| *t > 0.0);
+    let angle = settings.angular_tolerance.filter(|t| *t > 0.0);
+    if chord.is_none() && angle.is_none() {
+        return;
+    }
+
+    let inverses: Vec<DAffine3> = assembly.nodes.iter().map(|n| n.world.inverse()).collect();
+    let budget = raw.indices.len() / 3 * MAX_GROWTH;
+    for _ in 0..MAX_PASSES {
+        if raw.indices.len() / 3 >= budget {
+            break;
+        }
+        let midpoints = midpoints(raw, hits, brep, assembly, &inverses, chord, angle);
+        if midpoints.is_empty() {
+            break;
+        }
+        split(raw, hits, &midpoints);
+    }
+}
+
+/// Adds a vertex on the surface for every edge that's out of tolerance, keyed by the edge.
+fn midpoints(
+    raw: &mut RawMesh,
+    hits: &[Option<FaceHit>],
+    brep: &Brep,
+    assembly: &Assembly,
+    inverses: &[DAffine3],
+    chord: Option<f64>,
+    angle: Option<f64>,
+) -> HashMap<(u32, u32), u32> {
+    let mut edges: HashMap<(u32, u32), Vec<usize>> = HashMap::new();
+    for (t, triangle) in raw.indices.chunks_exact(3).enumerate() {
+        for k in 0..3 {
+            edges.entry(key(triangle[k], triangle[(k + 1) % 3])).or_default().push(t);
+        }
+    }
+
+    let mut flagged: Vec<((u32, u32), DVec3)> = edges
+        .into_iter()
+        .filter_map(|(edge, triangles)| {
+            // Only edges inside a face, splitting a boundary would open a crack to the next one
+            let &[first, second] = triangles.as_slice() else {
+                return None;
+            };
+            let hit = hits.get(first).copied().flatten()?;
+            if hits.get(second).copied().flatten() != Some(hit) {
+                return None;
+            }
+            let surface = &brep.faces.get(&hit.face)?.surface;
+            let [a, b] = [edge.0, edge.1]
+                .map(|v| inverses[hit.node].transform_point3(DVec3::from(raw.positions[v as usize].map(f64::from))));
+            let middle = (a + b) / 2.0;
+            let on_surface = surface.project(middle)?;
+
+            let too_far = chord.is_some_and(|chord| on_surface.distance(middle) > chord);
+            let too_bent = angle.is_some_and(|angle| {
+                surface
+                    .normal(a)
+                    .zip(surface.normal(b))
+                    .is_some_and(|(na, nb)| na.angle_between(nb) > angle)
+            });
+            (too_far || too_bent).then(|| (edge, assembly.nodes[hit.node].world.transform_point3(on_surface)))
+        })
+        .collect();
+
+    // Sorted so the new vertices come out in the same order every load
+    flagged.sort_unstable_by_key(|(edge, _)| *edge);
+    flagged
+        .into_iter()
+        .map(|(edge, p)| (edge, raw.midpoint_vertex(edge.0, edge.1, p.as_vec3().to_array())))
+        .collect()
+}
+
+/// Replaces every triangle with an edge in `midpoints` by the triangles it splits into.
+fn split(raw: &mut RawMesh, hits: &mut Vec<Option<FaceHit>>, midpoints: &HashMap<(u32, u32), u32>) {
+    let indices = std::mem::take(&mut raw.indices);
+    let mut split_hits = Vec::with_capacity(hits.len());
+    for (t, triangle) in indices.chunks_exact(3).enumerate() {
+        let v = [triangle[0], triangle[1], triangle[2]];
+        let m = [0, 1, 2].map(|k| midpoints.get(&key(v[k], v[(k + 1) % 3])).copied());
+        for piece in subdivide(v, m) {
+            raw.indices.extend_from_slice(&piece);
+            split_hits.push(hits.get(t).copied().flatten());
+        }
+    }
+    *hits = split_hits;
+}
+
+/// The triangles `v` splits into, given the midpoints `m[k]` of its edges from `v[k]` to
+/// `v[k + 1]`, wound the same way as `v`.
+fn subdivide(v: [u32; 3], m: [Option<u32>; 3]) -> Vec<[u32; 3]> {
+    let split_edges = m.iter().flatten().count();
+    if split_edges == 0 {
+        return vec![v];
+    }
+    if let [Some(a), Some(b), Some(c)] = m {
+        return vec![[v[0], a, c], [a, v[1], b], [c, b, v[2]], [a, b, c]];
+    }
+
+    // Turn the triangle so that its first edge is split, and with two split its second too
+    let r = (0..3)
+        .find(|&r| m[r].is_some() && (split_edges == 1 || m[(r + 1) % 3].is_some()))
+        .unwrap_or(0);
+    let v = [0, 1, 2].map(|k| v[(k + r) % 3]);
+    match [0, 1, 2].map(|k| m[(k + r) % 3]) {
+        [Some(a), Some(b), None] => vec![[a, v[1], b], [v[0], a, b], [v[0], b, v[2]]],
+        [Some(a), None, None] => vec![[v[0], a, v[2]], [a, v[1], v[2]]],
+        _ => vec![v],
+    }
+}
+
+fn key(a: u32, b: u32) -> (u32, u32) {
+    (a.min(b), a.max(b))
+}
@@ -0,0 +1,115 @@
+//! What happened while loading, for spotting files whose geometry quietly went missing.
+
+use std::collections::{BTreeMap, HashSet};
+use std::time::Duration;
+
+use crate::assembly::Assembly;
+use crate::brep::Brep;
+use crate::part21::StepData;
+use crate::segment::FaceHit;
+use crate::{EntityRef, TriangulationBackend, ValidationCheck};
+
+/// How a STEP file's load went, see [`StepAsset::report`](crate::StepAsset::report).
+#[derive(Debug, Clone, PartialEq)]
+pub struct StepLoadReport {
+    /// The backend that produced the triangles.
+    pub backend: TriangulationBackend,
+    /// Whether [`TriangulationBackend::FoxtrotThenOcct`] had to retry with OCCT.
+    pub fell_back: bool,
+    /// What Foxtrot says about its run, `None` when it didn't produce the triangles.
+    pub foxtrot: Option<FoxtrotStats>,
+    /// Faces in the file's solids.
+    pub faces: usize,
+    /// Faces that no triangles could be traced back to, which usually means the backend
+    /// couldn't tessellate them and the part will have holes.
+    pub failed_faces: Vec<FailedFace>,
+    /// How many faces failed for each surface type, e.g. `B_SPLINE_SURFACE_WITH_KNOTS`.
+    pub failed_surfaces: BTreeMap<String, usize>,
+    pub triangles: usize,
+    pub vertices: usize,
+    /// The products whose exporter wrote their volume, area or centroid into the file,
+    /// checked against the triangles. A few percent off in volume or area means faces went
+    /// missing or came out coarse.
+    pub validation: Vec<ValidationCheck>,
+    /// Time spent reading the file in the backend.
+    pub parse_time: Duration,
+    /// Time spent tessellating in the backend.
+    pub tessellate_time: Duration,
+    /// Time spent on everything after, the scene, materials, normals and so on.
+    pub build_time: Duration,
+    /// The part of `build_time` spent on the bodies' collision shapes, see
+    /// [`StepLoaderSettings::colliders`](crate::StepLoaderSettings::colliders).
+    pub collider_time: Duration,
+}
+
+impl StepLoadReport {
+    pub(crate) fn new(backend: TriangulationBackend) -> StepLoadReport {
+        StepLoadReport {
+            backend,
+            fell_back: false,
+            foxtrot: None,
+            faces: 0,
+            failed_faces: Vec::new(),
+            failed_surfaces: BTreeMap::new(),
+            triangles: 0,
+            vertices: 0,
+            validation: Vec::new(),
+            parse_time: Duration::ZERO,
+            tessellate_time: Duration::ZERO,
+            build_time: Duration::ZERO,
+            collider_time: Duration::ZERO,
+        }
+    }
+
+    /// Whether every face made it into the mesh and the backend reported no errors.
+    pub fn is_complete(&self) -> bool {
+        self.failed_faces.is_empty() && self.foxtrot.is_none_or(|s| s.errors == 0 && s.panics == 0)
+    }
+
+    /// Fills in the faces that didn't come out of the backend.
+    pub(crate) fn check_faces(&mut self, data: &StepData, brep: &Brep, assembly: &Assembly, hits: &[Option<FaceHit>]) {
+        let hit: HashSet<u64> = hits.iter().flatten().map(|h| h.face).collect();
+        let mut faces: Vec<u64> = assembly
+            .nodes
+            .iter()
+            .flat_map(|n| &n.solids)
+            .filter_map(|s| brep.solids.get(s))
+            .flat_map(|s| s.faces.iter().copied())
+            .collect();
+        faces.sort_unstable();
+        faces.dedup();
+
+        self.faces = faces.len();
+        self.failed_faces = faces
+            .into_iter()
+            .filter(|f| !hit.contains(f))
+            .map(|f| FailedFace {
+                entity: data.entity(f),
+                surface: brep.faces[&f].surface_type.clone(),
+            })
+            .collect();
+        self.failed_surfaces.clear();
+        for face in &self.failed_faces {
+            *self.failed_surfaces.entry(face.surface.clone()).or_default() += 1;
+        }
+    }
+}
+
+/// The statistics Foxtrot's triangulator keeps.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct FoxtrotStats {
+    pub shells: usize,
+    pub faces: usize,
+    /// Faces Foxtrot gave up on.
+    pub errors: usize,
+    /// Faces Foxtrot panicked on, and skipped.
+    pub panics: usize,
+}
+
+/// A face that didn't make it into the mesh.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FailedFace {
+    pub entity: EntityRef,
+    /// The keyword of the face's surface, e.g. `B_SPLINE_SURFACE_WITH_KNOTS`.
+    pub surface: String,
+}
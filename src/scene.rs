@@ -0,0 +1,193 @@
+//! Builds the labeled `Scene` mirroring the file's assembly tree.
+
+use std::collections::BTreeMap;
+
+use bevy_asset::{Handle, LoadContext};
+use bevy_camera::visibility::Visibility;
+use bevy_color::Color;
+use bevy_ecs::{hierarchy::ChildOf, name::Name, world::World};
+use bevy_math::{DAffine3, DMat4};
+use bevy_mesh::{Mesh, Mesh3d};
+use bevy_pbr::{MeshMaterial3d, StandardMaterial};
+use bevy_render::alpha::AlphaMode;
+use bevy_scene::Scene;
+use bevy_transform::components::Transform;
+
+use crate::assembly::{Assembly, Node};
+use crate::backend::{RawMesh, add_normals, build_indexed_mesh};
+use crate::lod::{self, StepLod, StepLodLevel};
+use crate::segment::FaceHit;
+use crate::style::{Style, Styles};
+use crate::{StepAssetLabel, StepLoaderError, StepLoaderSettings};
+
+/// One entity per assembly node, with a child entity per material holding the node's triangles
+/// in that material, in the node's local frame, so parts can be moved, hidden or picked on
+/// their own. The same layout is returned as [`StepPart`]s, for going through the parts
+/// without the scene.
+///
+/// Triangles that couldn't be traced back to a part go on the first root, so nothing the
+/// backend produced goes missing.
+pub(crate) fn build(
+    load_context: &mut LoadContext<'_>,
+    raw: &RawMesh,
+    assembly: &Assembly,
+    hits: &[Option<FaceHit>],
+    styles: &Styles,
+    triangle_styles: &[Option<usize>],
+    settings: &StepLoaderSettings,
+) -> Result<(Handle<Scene>, Vec<StepPart>), StepLoaderError> {
+    let fallback = [Node {
+        name: String::new(),
+        definition: None,
+        parent: None,
+        local: DAffine3::IDENTITY,
+        world: DAffine3::IDENTITY,
+        solids: Vec::new(),
+    }];
+    let nodes = if assembly.nodes.is_empty() { &fallback[..] } else { &assembly.nodes[..] };
+    let unassigned_owner = nodes.iter().position(|n| n.parent.is_none()).unwrap_or(0);
+
+    // Node -> material -> triangles, `None` being the default material
+    let mut triangles: Vec<BTreeMap<Option<usize>, Vec<usize>>> = vec![BTreeMap::new(); nodes.len()];
+    for (t, hit) in hits.iter().enumerate() {
+        let owner = hit.map_or(unassigned_owner, |h| h.node);
+        triangles[owner].entry(triangle_styles[t]).or_default().push(t);
+    }
+
+    let default_material = load_context.add_labeled_asset(
+        StepAssetLabel::DefaultMaterial.to_string(),
+        StandardMaterial {
+            base_color: Color::srgb(0.7, 0.7, 0.7),
+            metallic: 0.1,
+            perceptual_roughness: 0.5,
+            ..Default::default()
+        },
+    );
+    let materials: Vec<Handle<StandardMaterial>> = styles
+        .styles
+        .iter()
+        .enumerate()
+        .map(|(index, style)| {
+            load_context.add_labeled_asset(StepAssetLabel::Material(index).to_string(), material(style))
+        })
+        .collect();
+
+    let file_name = load_context
+        .path()
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+
+    let mut world = World::default();
+    let mut entities = Vec::with_capacity(nodes.len());
+    let mut parts = Vec::with_capacity(nodes.len());
+    for (index, node) in nodes.iter().enumerate() {
+        let name = match node.name.as_str() {
+            "" if node.parent.is_none() => file_name.clone(),
+            "" => format!("Part{}", index),
+            name => name.to_string(),
+        };
+
+        let placement = transform(&node.local, settings);
+        let mut entity = world.spawn((Name::new(name.clone()), placement, Visibility::default()));
+        if let Some(parent) = node.parent {
+            entity.insert(ChildOf(entities[parent]));
+        }
+        let id = entity.id();
+        entities.push(id);
+
+        let mut part = StepPart {
+            name: name.clone(),
+            parent: node.parent,
+            transform: placement,
+            primitives: Vec::new(),
+        };
+        let inverse = node.world.inverse();
+        for (primitive, (style, tris)) in triangles[index].iter().enumerate() {
+            let mut subset = raw.subset(tris.iter().copied(), &inverse);
+            // The material carries the colour here, vertex colours would tint it a second time
+            subset.colors.clear();
+            let mut mesh = build_indexed_mesh(subset, settings)?;
+            let lods = lod::build_chain(&mesh, settings)?;
+            add_normals(&mut mesh, settings)?;
+            let mesh = load_context.add_labeled_asset(StepAssetLabel::Primitive { part: index, primitive }.to_string(), mesh);
+
+            let material = style.map_or_else(|| default_material.clone(), |s| materials[s].clone());
+            part.primitives.push(StepPrimitive {
+                mesh: mesh.clone(),
+                material: material.clone(),
+            });
+            let mut entity = world.spawn((
+                Name::new(format!("{}.{}", name, primitive)),
+                Mesh3d(mesh.clone()),
+                MeshMaterial3d(material),
+                ChildOf(id),
+            ));
+
+            if !lods.is_empty() {
+                let full = StepLodLevel {
+                    mesh,
+                    distance: 0.0,
+                    ratio: 1.0,
+                    error: 0.0,
+                };
+                let mut levels = vec![full];
+                for (n, ((lod, error), level)) in lods.into_iter().zip(&settings.lods).enumerate() {
+                    let label = StepAssetLabel::Lod { part: index, primitive, level: n + 1 };
+                    levels.push(StepLodLevel {
+                        mesh: load_context.add_labeled_asset(label.to_string(), lod),
+                        distance: level.distance,
+                        ratio: level.ratio,
+                        error,
+                    });
+                }
+                levels.sort_by(|a, b| a.distance.total_cmp(&b.distance));
+                entity.insert(StepLod { levels });
+            }
+        }
+        parts.push(part);
+    }
+
+    let scene = load_context.add_labeled_asset(StepAssetLabel::Scene.to_string(), Scene::new(world));
+    Ok((scene, parts))
+}
+
+/// A node of the assembly as the scene lays it out, see [`StepAsset::parts`](crate::StepAsset::parts).
+#[derive(Debug, Clone)]
+pub struct StepPart {
+    /// The product's name, the file's name for a nameless root, or `Part{n}`.
+    pub name: String,
+    /// Index of the parent part, `None` for a root.
+    pub parent: Option<usize>,
+    /// The placement in the parent, in Bevy's axes, as on the scene's entity.
+    pub transform: Transform,
+    /// The part's own triangles, one primitive per material, see [`StepAssetLabel::Primitive`].
+    pub primitives: Vec<StepPrimitive>,
+}
+
+/// Triangles of a part sharing a material.
+#[derive(Debug, Clone)]
+pub struct StepPrimitive {
+    pub mesh: Handle<Mesh>,
+    pub material: Handle<StandardMaterial>,
+}
+
+fn material(style: &Style) -> StandardMaterial {
+    StandardMaterial {
+        base_color: style.color(),
+        metallic: 0.1,
+        perceptual_roughness: 0.5,
+        alpha_mode: if style.transparency > 0.0 { AlphaMode::Blend } else { AlphaMode::Opaque },
+        ..Default::default()
+    }
+}
+
+/// A node's placement as a Bevy transform, converted to Bevy's axes and with the loader's
+/// scale applied to its translation, the same way both are applied to the vertices.
+fn transform(local: &DAffine3, settings: &StepLoaderSettings) -> Transform {
+    let basis = DAffine3::from_mat3(settings.up_axis.basis());
+    let local = basis * *local * basis.inverse();
+    let mut transform = Transform::from_matrix(DMat4::from(local).as_mat4());
+    transform.translation *= settings.scale;
+    transform
+}
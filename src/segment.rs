@@ -0,0 +1,150 @@
+//! Working out which B-rep face each triangle came from.
+//!
+//! Neither backend tells us, both just hand back one big triangle soup. They do tessellate
+//! face by face without sharing vertices across faces though, so the connected patches of the
+//! output line up with the faces of the model. Each patch is matched to the face whose surface
+//! it lies on (and whose edges it lies within), trying every placed instance of every solid.
+
+use bevy_math::{DAffine3, DVec3};
+
+use crate::assembly::Assembly;
+use crate::backend::RawMesh;
+use crate::brep::{Bounds, Brep};
+
+/// Vertices sampled from each patch when scoring it against a face.
+const SAMPLES: usize = 32;
+
+/// Where a triangle came from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct FaceHit {
+    /// Index into [`Assembly::nodes`].
+    pub node: usize,
+    pub solid: u64,
+    pub face: u64,
+}
+
+struct Candidate {
+    hit: FaceHit,
+    /// The face's bounds in file space, padded by the matching tolerance.
+    bounds: Bounds,
+}
+
+/// One entry per triangle of `raw`, `None` where no face was a plausible source.
+pub(crate) fn classify(raw: &RawMesh, brep: &Brep, assembly: &Assembly) -> Vec<Option<FaceHit>> {
+    let triangle_count = raw.indices.len() / 3;
+    let mut hits = vec![None; triangle_count];
+    if triangle_count == 0 || brep.faces.is_empty() {
+        return hits;
+    }
+
+    let positions: Vec<DVec3> = raw.positions.iter().map(|p| DVec3::from(p.map(f64::from))).collect();
+    let mut extent = Bounds::EMPTY;
+    positions.iter().for_each(|p| extent.extend(*p));
+    // Tessellation sits within the chord tolerance of the surface, a thousandth of the model
+    // is comfortably more than that without letting neighbouring faces bleed into each other
+    let tolerance = (extent.diagonal() * 1e-3).max(1e-9);
+
+    let inverses: Vec<DAffine3> = assembly.nodes.iter().map(|n| n.world.inverse()).collect();
+    let candidates: Vec<Candidate> = assembly
+        .nodes
+        .iter()
+        .enumerate()
+        .flat_map(|(node, n)| n.solids.iter().map(move |solid| (node, n, *solid)))
+        .filter_map(|(node, n, solid)| Some((node, n, solid, brep.solids.get(&solid)?)))
+        .flat_map(|(node, n, solid, s)| {
+            s.faces.iter().filter_map(move |face| {
+                Some(Candidate {
+                    hit: FaceHit { node, solid, face: *face },
+                    bounds: brep.faces.get(face)?.bounds.transformed(&n.world).padded(tolerance),
+                })
+            })
+        })
+        .collect();
+
+    let score = |candidate: &Candidate, points: &[DVec3]| -> f64 {
+        let face = &brep.faces[&candidate.hit.face];
+        let inverse = &inverses[candidate.hit.node];
+        let total: f64 = points
+            .iter()
+            .map(|p| {
+                let local = inverse.transform_point3(*p);
+                // Surfaces we can't evaluate only have their bounds to go on, so lose ties to ones we can
+                face.surface.distance(local).unwrap_or(tolerance) + face.bounds.outside_distance(local)
+            })
+            .sum();
+        total / points.len() as f64
+    };
+
+    let best = |points: &[DVec3]| -> Option<(FaceHit, f64)> {
+        let mut bounds = Bounds::EMPTY;
+        points.iter().for_each(|p| bounds.extend(*p));
+        candidates
+            .iter()
+            .filter(|c| c.bounds.overlaps(&bounds))
+            .map(|c| (c.hit, score(c, points)))
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+    };
+
+    for patch in patches(raw) {
+        let mut vertices: Vec<u32> = patch.iter().flat_map(|t| triangle(raw, *t)).collect();
+        vertices.sort_unstable();
+        vertices.dedup();
+        let step = vertices.len().div_ceil(SAMPLES).max(1);
+        let samples: Vec<DVec3> = vertices.iter().step_by(step).map(|v| positions[*v as usize]).collect();
+
+        match best(&samples) {
+            Some((hit, error)) if error <= tolerance * 2.0 => {
+                for t in patch {
+                    hits[t] = Some(hit);
+                }
+            }
+            // A patch that doesn't sit on any one face is probably several faces sharing
+            // vertices, fall back to matching its triangles one at a time
+            _ => {
+                for t in patch {
+                    let corners = triangle(raw, t).map(|v| positions[v as usize]);
+                    let points = [corners[0], corners[1], corners[2], (corners[0] + corners[1] + corners[2]) / 3.0];
+                    hits[t] = best(&points).map(|(hit, _)| hit);
+                }
+            }
+        }
+    }
+
+    hits
+}
+
+fn triangle(raw: &RawMesh, t: usize) -> [u32; 3] {
+    [raw.indices[t * 3], raw.indices[t * 3 + 1], raw.indices[t * 3 + 2]]
+}
+
+/// Groups triangles that share vertices, returning the triangle indices of each group.
+fn patches(raw: &RawMesh) -> Vec<Vec<usize>> {
+    let mut parent: Vec<u32> = (0..raw.positions.len() as u32).collect();
+    fn find(parent: &mut [u32], mut v: u32) -> u32 {
+        while parent[v as usize] != v {
+            parent[v as usize] = parent[parent[v as usize] as usize];
+            v = parent[v as usize];
+        }
+        v
+    }
+
+    for t in 0..raw.indices.len() / 3 {
+        let [a, b, c] = triangle(raw, t);
+        for other in [b, c] {
+            let (ra, ro) = (find(&mut parent, a), find(&mut parent, other));
+            if ra != ro {
+                parent[ro as usize] = ra;
+            }
+        }
+    }
+
+    let mut groups: std::collections::HashMap<u32, Vec<usize>> = std::collections::HashMap::new();
+    for t in 0..raw.indices.len() / 3 {
+        let root = find(&mut parent, raw.indices[t * 3]);
+        groups.entry(root).or_default().push(t);
+    }
+    let mut groups: Vec<Vec<usize>> = groups.into_values().collect();
+    // Deterministic order, so labels built from this don't shuffle between loads
+    groups.sort_unstable_by_key(|g| g[0]);
+    groups
+}
@@ -0,0 +1,267 @@
+use std::collections::BTreeMap;
+
+use bevy_math::DMat3;
+use serde::{Deserialize, Serialize};
+
+use crate::LengthUnit;
+
+/// Per-asset settings for [`StepLoader`](crate::StepLoader).
+///
+/// These can be set from a `.meta` file next to the STEP file, or in code via
+/// `AssetServer::load_with_settings`:
+///
+/// ```rust,ignore
+/// let handle: Handle<StepAsset> = asset_server.load_with_settings(
+///     "part.step",
+///     |s: &mut StepLoaderSettings| {
+///         s.chord_tolerance = Some(0.05);
+///         s.normals = NormalMode::Flat;
+///     },
+/// );
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct StepLoaderSettings {
+    /// Which triangulator to run.
+    pub backend: TriangulationBackend,
+    /// Maximum distance between the tessellated surface and the true surface, in file units.
+    /// `None`, the default, keeps the backend's own tessellation.
+    ///
+    /// Neither backend takes it: Foxtrot's density is fixed and the OCCT bindings mesh at a
+    /// linear deflection of 0.01 file units. So after tessellation the triangles inside each
+    /// face are split until they're within it, see below. That only makes meshes finer, a
+    /// tolerance coarser than the backend's is already met. Also bounds the sampling of curved
+    /// edges in the edges mesh.
+    pub chord_tolerance: Option<f64>,
+    /// Maximum angle (radians) between the surface normals at the ends of a triangle edge, and
+    /// between adjacent segments when sampling curved edges for the edges mesh. `None`, the
+    /// default, keeps the backend's own tessellation and samples edges every half radian.
+    ///
+    /// Neither backend takes it either, so the edges inside each face that are out of either
+    /// tolerance are split, with the new vertices put on the face's surface. Only planes,
+    /// cylinders, cones, spheres and tori can be refined this way: the edges along face
+    /// boundaries, and faces on B-splines and other surfaces, keep the backend's sampling.
+    /// Each split can add triangles, up to 64 times the backend's.
+    pub angular_tolerance: Option<f64>,
+    /// How vertex normals are generated for the output mesh, [`NormalMode::Smooth`] by
+    /// default. [`NormalMode::Surface`] evaluates them on the B-rep instead.
+    pub normals: NormalMode,
+    /// Texture repeats per unit of length, for the UVs (and tangents) generated from each
+    /// face's surface parameters. UVs are measured after `scale` and `target_unit`, so with
+    /// metres and `1.0` a texture covers a square metre. `None`, the default, leaves the
+    /// meshes without UVs.
+    pub uv_scale: Option<f32>,
+    /// Run meshopt over the output meshes (requires the `meshopt` feature), see `meshopt_passes`.
+    pub meshopt: bool,
+    /// Which meshopt passes run when `meshopt` is on.
+    pub meshopt_passes: MeshoptPasses,
+    /// Uniform scale applied to every vertex position after tessellation, on top of any
+    /// conversion to `target_unit`.
+    pub scale: f32,
+    /// Convert the model into this unit, from whatever the file declares. `None` keeps the
+    /// file's own units.
+    pub target_unit: Option<LengthUnit>,
+    /// The unit to convert from to `target_unit` when the file doesn't declare one, usually
+    /// `Some(LengthUnit::Millimetre)` as that's what most CAD systems use. `None`, the default,
+    /// makes converting a file without a unit an error
+    /// ([`StepLoaderError::UnitMissing`](crate::StepLoaderError::UnitMissing)).
+    pub assumed_unit: Option<LengthUnit>,
+    /// Which way is up in the file. Positions, normals and scene transforms are all turned so
+    /// that it ends up along Bevy's +Y.
+    pub up_axis: UpAxis,
+    /// Simplified levels of detail to generate for every mesh in the scene, on top of the full
+    /// one (requires the `meshopt` feature). Each is added as a labeled asset, and the scene's
+    /// entities get a [`StepLod`](crate::StepLod) to switch between them.
+    pub lods: Vec<LodLevel>,
+    /// The most simplification error allowed for any level, relative to the mesh's size.
+    /// Levels stop short of their ratio rather than go past it.
+    pub lod_max_error: f32,
+    /// Keep the B-rep's topology on [`StepAsset::topology`](crate::StepAsset::topology).
+    pub topology: bool,
+    /// Density of every solid for [`StepBody::mass`](crate::StepBody::mass), in mass per cubic
+    /// unit of the output mesh, e.g. 7850 for steel in metres and kilograms.
+    pub density: f64,
+    /// Densities of particular solids, by [`StepBody::label`](crate::StepBody::label), in place
+    /// of `density`.
+    pub densities: BTreeMap<String, f64>,
+    /// Build collision shapes for every body into [`StepBody::colliders`](crate::StepBody::colliders)
+    /// (requires the `colliders` feature). `None` builds none.
+    pub colliders: Option<ColliderSettings>,
+}
+
+impl Default for StepLoaderSettings {
+    fn default() -> Self {
+        Self {
+            backend: TriangulationBackend::default(),
+            chord_tolerance: None,
+            angular_tolerance: None,
+            normals: NormalMode::default(),
+            uv_scale: None,
+            meshopt: true,
+            meshopt_passes: MeshoptPasses::default(),
+            scale: 1.0,
+            target_unit: None,
+            assumed_unit: None,
+            up_axis: UpAxis::default(),
+            lods: Vec::new(),
+            lod_max_error: 0.05,
+            topology: false,
+            density: 1.0,
+            densities: BTreeMap::new(),
+            colliders: None,
+        }
+    }
+}
+
+/// The meshopt passes to run over each mesh, in the order they run. Only the vertex cache
+/// optimisation is on by default.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeshoptPasses {
+    /// Round positions to this many bits per axis across the mesh's bounds. Bevy's position
+    /// attribute stays `f32`, but vertices that only differ below this precision can then be
+    /// welded, and the values match what a quantised export would store.
+    pub position_bits: Option<u8>,
+    /// Merge vertices that have the same position, colour, normal, UVs and B-rep face
+    /// (`generate_vertex_remap`), dropping the duplicates a backend leaves inside each face.
+    ///
+    /// Vertices on different faces never merge, so the edges between faces keep their own
+    /// vertices on each side and shading isn't smoothed across them, whatever the
+    /// [`NormalMode`]. Only [`triangulate_step_file`](crate::triangulate_step_file), which
+    /// doesn't tag vertices with their faces, merges across faces where the rest agrees.
+    pub weld: bool,
+    /// Reorder triangles for the GPU's vertex cache.
+    pub vertex_cache: bool,
+    /// Reorder triangles to cut overdraw, letting the vertex cache efficiency get this much
+    /// worse in exchange (meshopt suggests `1.05`).
+    pub overdraw: Option<f32>,
+    /// Reorder vertices into the order the triangles use them.
+    pub vertex_fetch: bool,
+    /// Round normals to this many bits per component, as a signed normalised integer would.
+    pub normal_bits: Option<u8>,
+}
+
+impl Default for MeshoptPasses {
+    fn default() -> Self {
+        Self {
+            position_bits: None,
+            weld: false,
+            vertex_cache: true,
+            overdraw: None,
+            vertex_fetch: false,
+            normal_bits: None,
+        }
+    }
+}
+
+/// How collision shapes are built, see [`StepLoaderSettings::colliders`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ColliderSettings {
+    /// Fit a box, cylinder or plane to solids whose faces make exactly one.
+    pub primitives: bool,
+    /// The most convex pieces a solid is split into.
+    pub max_pieces: usize,
+    /// Only split a piece when that shrinks the volume its hulls cover by at least this share,
+    /// `0.05` for 5%. Lower follows concave shapes more closely, with more pieces.
+    pub min_gain: f32,
+}
+
+impl Default for ColliderSettings {
+    fn default() -> Self {
+        Self {
+            primitives: true,
+            max_pieces: 16,
+            min_gain: 0.05,
+        }
+    }
+}
+
+/// A level of detail to generate, see [`StepLoaderSettings::lods`].
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct LodLevel {
+    /// The share of the full mesh's triangles to aim for, `0.25` for a quarter.
+    pub ratio: f32,
+    /// Camera distance from which this level is shown, in world units.
+    pub distance: f32,
+}
+
+/// The triangulator used to turn the B-rep into triangles.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TriangulationBackend {
+    /// The pure Rust triangulator from Foxtrot.
+    Foxtrot,
+    /// OpenCASCADE, only available with the `opencascade` feature.
+    Occt,
+    /// Try Foxtrot first, and retry with OCCT if it fails or produces no triangles.
+    ///
+    /// Without the `opencascade` feature this behaves like [`TriangulationBackend::Foxtrot`].
+    FoxtrotThenOcct,
+}
+
+impl Default for TriangulationBackend {
+    fn default() -> Self {
+        if cfg!(feature = "opencascade") {
+            TriangulationBackend::Occt
+        } else {
+            TriangulationBackend::Foxtrot
+        }
+    }
+}
+
+/// How vertex normals are generated.
+#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+pub enum NormalMode {
+    /// Evaluate each vertex's normal from the B-rep surface its face lies on, with vertices
+    /// split along face boundaries so edges between faces stay sharp. Faces whose surface isn't
+    /// known keep the backend's normals, which it also takes from the surface.
+    Surface,
+    /// Average the normals of every triangle sharing a vertex.
+    #[default]
+    Smooth,
+    /// Give every triangle its own vertices and a face normal.
+    Flat,
+    /// Smooth across edges where the triangles meet at less than this many degrees, and split
+    /// the vertices to keep the edge sharp where they meet at more. Needs nothing but the
+    /// triangles, see [`compute_crease_normals`](crate::compute_crease_normals).
+    CreaseAngle(f32),
+}
+
+/// The up axis and handedness of a file's coordinates, for converting into Bevy's right-handed Y-up.
+///
+/// STEP itself doesn't say, most CAD systems model Z-up.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum UpAxis {
+    /// Right-handed Y-up, the same as Bevy, coordinates are left alone.
+    #[default]
+    YUp,
+    /// Right-handed Z-up: `(x, y, z)` becomes `(x, z, -y)`.
+    ZUp,
+    /// Right-handed X-up: `(x, y, z)` becomes `(-y, x, z)`.
+    XUp,
+    /// Left-handed Y-up: `(x, y, z)` becomes `(x, y, -z)`.
+    YUpLeftHanded,
+    /// Left-handed Z-up: `(x, y, z)` becomes `(x, z, y)`.
+    ZUpLeftHanded,
+}
+
+impl UpAxis {
+    /// The matrix taking file coordinates into Bevy's.
+    pub fn basis(self) -> DMat3 {
+        use bevy_math::DVec3;
+        let [x, y, z] = match self {
+            UpAxis::YUp => [DVec3::X, DVec3::Y, DVec3::Z],
+            UpAxis::ZUp => [DVec3::X, DVec3::NEG_Z, DVec3::Y],
+            UpAxis::XUp => [DVec3::Y, DVec3::NEG_X, DVec3::Z],
+            UpAxis::YUpLeftHanded => [DVec3::X, DVec3::Y, DVec3::NEG_Z],
+            UpAxis::ZUpLeftHanded => [DVec3::X, DVec3::Z, DVec3::Y],
+        };
+        // The columns are where the file's axes end up
+        DMat3::from_cols(x, y, z)
+    }
+
+    /// Whether the conversion mirrors, which turns triangles inside out unless their winding is flipped too.
+    pub fn is_mirrored(self) -> bool {
+        matches!(self, UpAxis::YUpLeftHanded | UpAxis::ZUpLeftHanded)
+    }
+}
@@ -0,0 +1,218 @@
+//! Colours and transparency from the file's presentation styles.
+//!
+//! ```text
+//! STYLED_ITEM -> (PRESENTATION_STYLE_ASSIGNMENT) -> SURFACE_STYLE_USAGE -> SURFACE_SIDE_STYLE
+//!     -> SURFACE_STYLE_FILL_AREA -> FILL_AREA_STYLE -> FILL_AREA_STYLE_COLOUR -> COLOUR_RGB
+//!     -> SURFACE_STYLE_TRANSPARENT
+//! ```
+//!
+//! A style can be attached to a solid, a shell or a single face, the most specific one wins.
+
+use std::collections::HashMap;
+
+use bevy_color::{Color, ColorToComponents, LinearRgba};
+
+use crate::backend::RawMesh;
+use crate::part21::StepData;
+use crate::segment::FaceHit;
+
+/// How deep we follow style references before giving up, they're only ever a few levels.
+const MAX_DEPTH: usize = 8;
+
+/// The look of a surface, as far as STEP describes it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Style {
+    /// sRGB, 0..1.
+    pub color: [f32; 3],
+    /// 0 is opaque, 1 is invisible.
+    pub transparency: f32,
+}
+
+impl Style {
+    /// The colour with transparency turned into alpha.
+    pub(crate) fn color(&self) -> Color {
+        let [r, g, b] = self.color;
+        Color::srgba(r, g, b, 1.0 - self.transparency)
+    }
+}
+
+/// Every distinct style in the file, and what it's attached to.
+#[derive(Debug, Default)]
+pub(crate) struct Styles {
+    pub styles: Vec<Style>,
+    solids: HashMap<u64, usize>,
+    /// Faces styled directly, or through their shell.
+    faces: HashMap<u64, usize>,
+}
+
+impl Styles {
+    pub(crate) fn read(data: &StepData) -> Styles {
+        let mut styles = Styles::default();
+        let mut shells: HashMap<u64, usize> = HashMap::new();
+
+        let mut items: Vec<_> = data
+            .all("STYLED_ITEM")
+            .chain(data.all("OVER_RIDING_STYLED_ITEM"))
+            .collect();
+        // Later items override earlier ones, so go in file order
+        items.sort_unstable_by_key(|(id, _)| *id);
+
+        for (_, item) in items {
+            let Some(target) = item.param(2).as_id() else { continue };
+            let mut style = StyleBuilder::default();
+            for assignment in item.param(1).refs() {
+                style.read(data, assignment, 0);
+            }
+            let Some(style) = style.finish() else { continue };
+            let index = styles.intern(style);
+
+            if data.record(target, "CLOSED_SHELL").is_some() || data.record(target, "OPEN_SHELL").is_some() {
+                shells.insert(target, index);
+            } else if data.record(target, "ADVANCED_FACE").is_some() || data.record(target, "FACE_SURFACE").is_some() {
+                styles.faces.insert(target, index);
+            } else {
+                // Solids, and anything else that we might treat as one
+                styles.solids.insert(target, index);
+            }
+        }
+
+        for (shell, index) in shells {
+            let faces = data
+                .record(shell, "CLOSED_SHELL")
+                .or_else(|| data.record(shell, "OPEN_SHELL"))
+                .map(|s| s.param(1).refs().collect::<Vec<_>>())
+                .unwrap_or_default();
+            for face in faces {
+                styles.faces.entry(face).or_insert(index);
+            }
+        }
+
+        styles
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.styles.is_empty()
+    }
+
+    /// The style of the face a triangle came from, if it has one.
+    pub(crate) fn of(&self, hit: &FaceHit) -> Option<usize> {
+        self.faces.get(&hit.face).or_else(|| self.solids.get(&hit.solid)).copied()
+    }
+
+    fn intern(&mut self, style: Style) -> usize {
+        match self.styles.iter().position(|s| *s == style) {
+            Some(index) => index,
+            None => {
+                self.styles.push(style);
+                self.styles.len() - 1
+            }
+        }
+    }
+}
+
+/// Gives `raw` vertex colours from the style of each triangle, `triangle_styles` being indices
+/// into `styles`. Triangles without a style are left white so a material's colour shows through.
+pub(crate) fn paint(raw: &mut RawMesh, triangle_styles: &[Option<usize>], styles: &Styles) {
+    if styles.is_empty() {
+        return;
+    }
+    let colors: Vec<[f32; 4]> = styles.styles.iter().map(|s| LinearRgba::from(s.color()).to_f32_array()).collect();
+    raw.colors = vec![[1.0; 4]; raw.positions.len()];
+    for (t, style) in triangle_styles.iter().enumerate() {
+        let Some(style) = style else { continue };
+        for &v in &raw.indices[t * 3..t * 3 + 3] {
+            raw.colors[v as usize] = colors[*style];
+        }
+    }
+}
+
+#[derive(Default)]
+struct StyleBuilder {
+    color: Option<[f32; 3]>,
+    transparency: Option<f32>,
+}
+
+impl StyleBuilder {
+    /// Walks down from anything in the chain above, picking up colours and transparency.
+    fn read(&mut self, data: &StepData, id: u64, depth: usize) {
+        let Some(instance) = data.get(id) else { return };
+        if depth > MAX_DEPTH {
+            return;
+        }
+        for record in &instance.records {
+            match record.keyword.as_str() {
+                "PRESENTATION_STYLE_ASSIGNMENT" | "PRESENTATION_STYLE_BY_CONTEXT" => {
+                    for style in record.param(0).refs() {
+                        self.read(data, style, depth + 1);
+                    }
+                }
+                "SURFACE_STYLE_USAGE" => {
+                    if let Some(side) = record.param(1).as_id() {
+                        self.read(data, side, depth + 1);
+                    }
+                }
+                "SURFACE_SIDE_STYLE" | "FILL_AREA_STYLE" => {
+                    for element in record.param(1).refs() {
+                        self.read(data, element, depth + 1);
+                    }
+                }
+                "SURFACE_STYLE_FILL_AREA" => {
+                    if let Some(fill) = record.param(0).as_id() {
+                        self.read(data, fill, depth + 1);
+                    }
+                }
+                "FILL_AREA_STYLE_COLOUR" => {
+                    if let Some(colour) = record.param(1).as_id() {
+                        self.color = self.color.or_else(|| colour_rgb(data, colour));
+                    }
+                }
+                // SURFACE_STYLE_RENDERING(method, colour), the _WITH_PROPERTIES form adds the
+                // transparency among its properties
+                "SURFACE_STYLE_RENDERING" | "SURFACE_STYLE_RENDERING_WITH_PROPERTIES" => {
+                    if let Some(colour) = record.param(1).as_id() {
+                        self.color = self.color.or_else(|| colour_rgb(data, colour));
+                    }
+                    for property in record.param(2).refs() {
+                        self.read(data, property, depth + 1);
+                    }
+                }
+                "SURFACE_STYLE_TRANSPARENT" => {
+                    if let Some(t) = record.param(0).as_f64() {
+                        self.transparency = Some(t.clamp(0.0, 1.0) as f32);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn finish(self) -> Option<Style> {
+        if self.color.is_none() && self.transparency.is_none() {
+            return None;
+        }
+        Some(Style {
+            // A transparency with no colour still needs something to be transparent
+            color: self.color.unwrap_or([0.7, 0.7, 0.7]),
+            transparency: self.transparency.unwrap_or(0.0),
+        })
+    }
+}
+
+fn colour_rgb(data: &StepData, id: u64) -> Option<[f32; 3]> {
+    if let Some(rgb) = data.record(id, "COLOUR_RGB") {
+        let channel = |i| rgb.param(i).as_f64().map(|c| c.clamp(0.0, 1.0) as f32);
+        return Some([channel(1)?, channel(2)?, channel(3)?]);
+    }
+    let name = data.record(id, "DRAUGHTING_PRE_DEFINED_COLOUR")?.param(0).as_str()?;
+    Some(match name {
+        "red" => [1.0, 0.0, 0.0],
+        "green" => [0.0, 1.0, 0.0],
+        "blue" => [0.0, 0.0, 1.0],
+        "yellow" => [1.0, 1.0, 0.0],
+        "magenta" => [1.0, 0.0, 1.0],
+        "cyan" => [0.0, 1.0, 1.0],
+        "black" => [0.0, 0.0, 0.0],
+        "white" => [1.0, 1.0, 1.0],
+        _ => return None,
+    })
+}
@@ -0,0 +1,280 @@
+//! The B-rep's topology, kept around so tools can select, highlight and analyse without
+//! reading the file again.
+//!
+//! ```text
+//! solid -> shells -> faces -> loops -> oriented edges -> edges -> vertices
+//! ```
+//!
+//! Everything is keyed by its entity id in the file, and the links run both ways: edges know
+//! the faces they bound and vertices the edges they end.
+
+use std::collections::BTreeMap;
+
+use bevy_math::DVec3;
+
+use crate::brep::{SOLID_KEYWORDS, solid_shells, surface_type, vertex_point};
+use crate::part21::StepData;
+
+/// A solid (or surface model), `MANIFOLD_SOLID_BREP` and friends.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TopologySolid {
+    pub name: String,
+    /// The outer shell first, then any voids.
+    pub shells: Vec<u64>,
+}
+
+/// A `CLOSED_SHELL` or `OPEN_SHELL`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TopologyShell {
+    pub closed: bool,
+    pub faces: Vec<u64>,
+    /// The solids using this shell.
+    pub solids: Vec<u64>,
+}
+
+/// An `ADVANCED_FACE` (or `FACE_SURFACE`).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TopologyFace {
+    /// The entity id of the face's surface.
+    pub surface: Option<u64>,
+    /// The keyword of the face's surface, `PLANE`, `B_SPLINE_SURFACE_WITH_KNOTS`...
+    pub surface_type: String,
+    /// Whether the face's normal agrees with its surface's.
+    pub same_sense: bool,
+    /// The face's bounds, its `EDGE_LOOP`s, `VERTEX_LOOP`s and `POLY_LOOP`s.
+    pub loops: Vec<u64>,
+    /// The shells using this face.
+    pub shells: Vec<u64>,
+}
+
+/// One of a face's bounds.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TopologyLoop {
+    /// Whether this is the face's `FACE_OUTER_BOUND`.
+    pub outer: bool,
+    /// The edges around the loop in order, empty for vertex and poly loops.
+    pub edges: Vec<TopologyOrientedEdge>,
+    pub face: u64,
+}
+
+/// An edge as used by a loop, which may run against the edge's own direction.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TopologyOrientedEdge {
+    /// The `EDGE_CURVE`.
+    pub edge: u64,
+    /// Whether the loop runs from the edge's start to its end.
+    pub orientation: bool,
+}
+
+/// An `EDGE_CURVE`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TopologyEdge {
+    pub start: u64,
+    pub end: u64,
+    /// The entity id of the edge's curve.
+    pub curve: Option<u64>,
+    /// The keyword of the edge's curve, `LINE`, `CIRCLE`, `SURFACE_CURVE`...
+    pub curve_type: String,
+    /// The faces this edge bounds, two for an edge of a closed shell, one for a seam.
+    pub faces: Vec<u64>,
+}
+
+/// A `VERTEX_POINT`.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct TopologyVertex {
+    /// In file units, in the frame of the solid.
+    pub position: DVec3,
+    /// The edges starting or ending here.
+    pub edges: Vec<u64>,
+}
+
+/// The solids, shells, faces, loops, edges and vertices of a STEP file, see
+/// [`StepAsset::topology`](crate::StepAsset::topology).
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct StepTopology {
+    pub solids: BTreeMap<u64, TopologySolid>,
+    pub shells: BTreeMap<u64, TopologyShell>,
+    pub faces: BTreeMap<u64, TopologyFace>,
+    pub loops: BTreeMap<u64, TopologyLoop>,
+    pub edges: BTreeMap<u64, TopologyEdge>,
+    pub vertices: BTreeMap<u64, TopologyVertex>,
+}
+
+impl StepTopology {
+    /// The faces on either side of `edge`.
+    pub fn faces_of_edge(&self, edge: u64) -> &[u64] {
+        self.edges.get(&edge).map_or(&[], |e| &e.faces)
+    }
+
+    /// The edges bounding `face`, loop by loop, each once.
+    pub fn edges_of_face(&self, face: u64) -> Vec<u64> {
+        let mut edges: Vec<u64> = Vec::new();
+        for bound in self.faces.get(&face).map_or(&[][..], |f| &f.loops) {
+            for oriented in self.loops.get(bound).map_or(&[][..], |l| &l.edges) {
+                if !edges.contains(&oriented.edge) {
+                    edges.push(oriented.edge);
+                }
+            }
+        }
+        edges
+    }
+
+    /// The keyword of the surface under `face`.
+    pub fn surface_type(&self, face: u64) -> Option<&str> {
+        self.faces.get(&face).map(|f| f.surface_type.as_str())
+    }
+
+    /// The faces sharing an edge with `face`.
+    pub fn adjacent_faces(&self, face: u64) -> Vec<u64> {
+        let mut faces: Vec<u64> = self
+            .edges_of_face(face)
+            .into_iter()
+            .flat_map(|edge| self.faces_of_edge(edge).iter().copied())
+            .filter(|f| *f != face)
+            .collect();
+        faces.sort_unstable();
+        faces.dedup();
+        faces
+    }
+
+    pub(crate) fn read(data: &StepData) -> StepTopology {
+        let mut topology = StepTopology::default();
+        for keyword in SOLID_KEYWORDS {
+            for (id, record) in data.all(keyword) {
+                let shells = solid_shells(data, keyword, record)
+                    .into_iter()
+                    .filter_map(|s| topology.read_shell(data, s, id))
+                    .collect();
+                let name = record.param(0).as_str().unwrap_or_default().to_string();
+                topology.solids.insert(id, TopologySolid { name, shells });
+            }
+        }
+        topology
+    }
+
+    /// Adds `solid` to a shell, reading the shell the first time it's seen, and returns its id.
+    /// Voids come in already resolved to the shell they turn inside out, see [`solid_shells`].
+    fn read_shell(&mut self, data: &StepData, id: u64, solid: u64) -> Option<u64> {
+        if let Some(shell) = self.shells.get_mut(&id) {
+            shell.solids.push(solid);
+            return Some(id);
+        }
+        let (record, closed) = match (data.record(id, "CLOSED_SHELL"), data.record(id, "OPEN_SHELL")) {
+            (Some(record), _) => (record, true),
+            (None, Some(record)) => (record, false),
+            (None, None) => return None,
+        };
+        let faces: Vec<u64> = record.param(1).refs().collect();
+        for face in &faces {
+            self.read_face(data, *face, id);
+        }
+        self.shells.insert(id, TopologyShell { closed, faces, solids: vec![solid] });
+        Some(id)
+    }
+
+    fn read_face(&mut self, data: &StepData, id: u64, shell: u64) {
+        if let Some(face) = self.faces.get_mut(&id) {
+            face.shells.push(shell);
+            return;
+        }
+        let Some(record) = data
+            .record(id, "ADVANCED_FACE")
+            .or_else(|| data.record(id, "FACE_SURFACE"))
+        else {
+            return;
+        };
+
+        let mut loops = Vec::new();
+        for bound in record.param(1).refs() {
+            let (bound_record, outer) = match data.record(bound, "FACE_OUTER_BOUND") {
+                Some(record) => (record, true),
+                None => match data.record(bound, "FACE_BOUND") {
+                    Some(record) => (record, false),
+                    None => continue,
+                },
+            };
+            let Some(loop_id) = bound_record.param(1).as_id() else { continue };
+            self.read_loop(data, loop_id, id, outer);
+            loops.push(loop_id);
+        }
+
+        let surface = record.param(2).as_id();
+        self.faces.insert(
+            id,
+            TopologyFace {
+                surface,
+                surface_type: surface.map(|s| surface_type(data, s)).unwrap_or_default(),
+                same_sense: record.param(3).as_enum() != Some("F"),
+                loops,
+                shells: vec![shell],
+            },
+        );
+    }
+
+    fn read_loop(&mut self, data: &StepData, id: u64, face: u64, outer: bool) {
+        let mut edges = Vec::new();
+        if let Some(edge_loop) = data.record(id, "EDGE_LOOP") {
+            for oriented in edge_loop.param(1).refs() {
+                let Some(record) = data.record(oriented, "ORIENTED_EDGE") else { continue };
+                let Some(edge) = record.param(3).as_id() else { continue };
+                if !self.read_edge(data, edge, face) {
+                    continue;
+                }
+                edges.push(TopologyOrientedEdge {
+                    edge,
+                    orientation: record.param(4).as_enum() != Some("F"),
+                });
+            }
+        } else if let Some(vertex) = data.record(id, "VERTEX_LOOP").and_then(|l| l.param(1).as_id()) {
+            self.read_vertex(data, vertex, None);
+        }
+        self.loops.insert(id, TopologyLoop { outer, edges, face });
+    }
+
+    /// Adds `face` to an edge, reading the edge the first time it's seen. False if `id`
+    /// isn't an edge.
+    fn read_edge(&mut self, data: &StepData, id: u64, face: u64) -> bool {
+        if let Some(edge) = self.edges.get_mut(&id) {
+            // Seams are used twice by the same face
+            if !edge.faces.contains(&face) {
+                edge.faces.push(face);
+            }
+            return true;
+        }
+        let Some(record) = data.record(id, "EDGE_CURVE") else { return false };
+        let (Some(start), Some(end)) = (record.param(1).as_id(), record.param(2).as_id()) else {
+            return false;
+        };
+        self.read_vertex(data, start, Some(id));
+        if end != start {
+            self.read_vertex(data, end, Some(id));
+        }
+        let curve = record.param(3).as_id();
+        let curve_type = curve
+            .and_then(|c| data.get(c))
+            .and_then(|inst| inst.records.first())
+            .map(|r| r.keyword.clone())
+            .unwrap_or_default();
+        self.edges.insert(
+            id,
+            TopologyEdge {
+                start,
+                end,
+                curve,
+                curve_type,
+                faces: vec![face],
+            },
+        );
+        true
+    }
+
+    fn read_vertex(&mut self, data: &StepData, id: u64, edge: Option<u64>) {
+        let vertex = self.vertices.entry(id).or_insert_with(|| TopologyVertex {
+            position: vertex_point(data, id).unwrap_or_default(),
+            edges: Vec::new(),
+        });
+        if let Some(edge) = edge {
+            vertex.edges.push(edge);
+        }
+    }
+}
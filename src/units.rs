@@ -0,0 +1,308 @@
+//! The file's length unit, and converting out of it.
+//!
+//! Units hang off the geometric context every representation refers to:
+//!
+//! ```text
+//! ( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#mm,#rad,#sr)) ... )
+//! #mm = ( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) )
+//! #in = ( CONVERSION_BASED_UNIT('INCH',#m) LENGTH_UNIT() NAMED_UNIT(#dim) )
+//! #m = LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(25.4),#mm)
+//! ```
+
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{StepLoaderError, StepLoaderSettings};
+use crate::part21::{StepData, Value};
+
+/// Conversion based units can be defined in terms of each other, but not very deeply.
+const MAX_DEPTH: usize = 4;
+
+/// A unit of length.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum LengthUnit {
+    Micrometre,
+    Millimetre,
+    Centimetre,
+    Metre,
+    Kilometre,
+    Inch,
+    Foot,
+    /// Anything else, as the number of metres in one unit.
+    Custom(f64),
+}
+
+impl LengthUnit {
+    /// The number of metres in one of this unit.
+    pub fn metres(self) -> f64 {
+        match self {
+            LengthUnit::Micrometre => 1e-6,
+            LengthUnit::Millimetre => 1e-3,
+            LengthUnit::Centimetre => 1e-2,
+            LengthUnit::Metre => 1.0,
+            LengthUnit::Kilometre => 1e3,
+            LengthUnit::Inch => 0.0254,
+            LengthUnit::Foot => 0.3048,
+            LengthUnit::Custom(metres) => metres,
+        }
+    }
+
+    /// The named unit of this size, if there is one.
+    pub fn from_metres(metres: f64) -> LengthUnit {
+        [
+            LengthUnit::Micrometre,
+            LengthUnit::Millimetre,
+            LengthUnit::Centimetre,
+            LengthUnit::Metre,
+            LengthUnit::Kilometre,
+            LengthUnit::Inch,
+            LengthUnit::Foot,
+        ]
+        .into_iter()
+        .find(|unit| ((unit.metres() - metres) / metres).abs() < 1e-9)
+        .unwrap_or(LengthUnit::Custom(metres))
+    }
+
+    /// The factor taking lengths in this unit to lengths in `target`.
+    pub fn to(self, target: LengthUnit) -> f64 {
+        self.metres() / target.metres()
+    }
+}
+
+/// The length unit of the file's geometry, `None` if it doesn't declare one.
+pub(crate) fn length_unit(data: &StepData) -> Option<LengthUnit> {
+    // Every representation context assigns units, and a file can have several. They almost
+    // always agree, when they don't go with the one most contexts use
+    let mut votes: HashMap<u64, usize> = HashMap::new();
+    for (_, context) in data.all("GLOBAL_UNIT_ASSIGNED_CONTEXT") {
+        for unit in context.param(0).refs() {
+            if data.record(unit, "LENGTH_UNIT").is_some() {
+                *votes.entry(unit).or_default() += 1;
+            }
+        }
+    }
+    // Failing that, the unit of the distance uncertainty, which is nearly always the same
+    if votes.is_empty() {
+        for (_, uncertainty) in data.all("UNCERTAINTY_MEASURE_WITH_UNIT") {
+            if let Some(unit) = uncertainty.param(1).as_id() {
+                *votes.entry(unit).or_default() += 1;
+            }
+        }
+    }
+
+    let mut candidates: Vec<(u64, usize)> = votes.into_iter().collect();
+    candidates.sort_unstable_by_key(|(id, count)| (std::cmp::Reverse(*count), *id));
+    candidates
+        .into_iter()
+        .find_map(|(id, _)| unit_metres(data, id, 0))
+        .map(LengthUnit::from_metres)
+}
+
+/// The size of a length unit instance in metres.
+fn unit_metres(data: &StepData, id: u64, depth: usize) -> Option<f64> {
+    if depth > MAX_DEPTH {
+        return None;
+    }
+    if let Some(si) = data.record(id, "SI_UNIT") {
+        if si.param(1).as_enum() != Some("METRE") {
+            return None;
+        }
+        return si_prefix(si.param(0));
+    }
+
+    let conversion = data.record(id, "CONVERSION_BASED_UNIT")?;
+    let measure = data.get(conversion.param(1).as_id()?)?;
+    // LENGTH_MEASURE_WITH_UNIT, or a complex instance with a MEASURE_WITH_UNIT record
+    let measure = measure
+        .record("LENGTH_MEASURE_WITH_UNIT")
+        .or_else(|| measure.record("MEASURE_WITH_UNIT"))?;
+    let value = measure.param(0).as_f64()?;
+    let base = unit_metres(data, measure.param(1).as_id()?, depth + 1)?;
+    // A unit of no size, or a negative one, would collapse or mirror the model, so it counts
+    // as missing and the assumed unit takes over
+    Some(value * base).filter(|metres| metres.is_finite() && *metres > 0.0)
+}
+
+/// The factor taking a measure in `unit` of length to the power `dimension`, 2 for an area or
+/// 3 for a volume, to the file's length unit to that power. Units we can't make sense of are
+/// taken to be the file's already, which is what exporters nearly always write.
+pub(crate) fn measure_factor(data: &StepData, unit: Option<u64>, dimension: i32, file_unit: Option<LengthUnit>) -> f64 {
+    match (unit.and_then(|unit| power_metres(data, unit, dimension)), file_unit) {
+        (Some(size), Some(file_unit)) => size / file_unit.metres().powi(dimension),
+        _ => 1.0,
+    }
+}
+
+/// The size of an area or volume unit instance, in square or cubic metres.
+fn power_metres(data: &StepData, id: u64, dimension: i32) -> Option<f64> {
+    // DERIVED_UNIT((#e1,...)) with #e = DERIVED_UNIT_ELEMENT(#length_unit,3.)
+    if let Some(derived) = data.record(id, "DERIVED_UNIT") {
+        let mut size = 1.0;
+        let mut exponents = 0.0;
+        for element in derived.param(0).refs() {
+            let element = data.record(element, "DERIVED_UNIT_ELEMENT")?;
+            let exponent = element.param(1).as_f64()?;
+            size *= unit_metres(data, element.param(0).as_id()?, 0)?.powf(exponent);
+            exponents += exponent;
+        }
+        return (exponents == f64::from(dimension)).then_some(size);
+    }
+
+    let si = data.record(id, "SI_UNIT")?;
+    let name = match dimension {
+        2 => "SQUARE_METRE",
+        3 => "CUBIC_METRE",
+        _ => return None,
+    };
+    if si.param(1).as_enum() != Some(name) {
+        return None;
+    }
+    // Exporters write `.MILLI.,.CUBIC_METRE.` for cubic millimetres, so the prefix is taken to
+    // apply to the metre
+    Some(si_prefix(si.param(0))?.powi(dimension))
+}
+
+/// The factor an `SI_UNIT` prefix stands for, 1 for none.
+fn si_prefix(prefix: &Value) -> Option<f64> {
+    Some(match prefix.as_enum() {
+        None => 1.0,
+        Some("EXA") => 1e18,
+        Some("PETA") => 1e15,
+        Some("TERA") => 1e12,
+        Some("GIGA") => 1e9,
+        Some("MEGA") => 1e6,
+        Some("KILO") => 1e3,
+        Some("HECTO") => 1e2,
+        Some("DECA") => 1e1,
+        Some("DECI") => 1e-1,
+        Some("CENTI") => 1e-2,
+        Some("MILLI") => 1e-3,
+        Some("MICRO") => 1e-6,
+        Some("NANO") => 1e-9,
+        Some("PICO") => 1e-12,
+        Some("FEMTO") => 1e-15,
+        Some("ATTO") => 1e-18,
+        Some(_) => return None,
+    })
+}
+
+/// `settings` with the unit conversion folded into its scale.
+pub(crate) fn rescaled(
+    settings: &StepLoaderSettings,
+    file_unit: Option<LengthUnit>,
+) -> Result<StepLoaderSettings, StepLoaderError> {
+    let factor = match settings.target_unit {
+        Some(target) => file_unit
+            .or(settings.assumed_unit)
+            .ok_or(StepLoaderError::UnitMissing)?
+            .to(target),
+        None => 1.0,
+    };
+    Ok(StepLoaderSettings {
+        scale: settings.scale * factor as f32,
+        ..settings.clone()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part21::parse;
+
+    fn file(data: &str) -> StepData {
+        parse(format!("ISO-10303-21;\nHEADER;\nENDSEC;\nDATA;\n{}\nENDSEC;\nEND-ISO-10303-21;\n", data).as_bytes())
+            .unwrap()
+    }
+
+    #[test]
+    fn si_units() {
+        let data = file(
+            "#1=( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#2,#3)) REPRESENTATION_CONTEXT('','') );
+             #2=( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) );
+             #3=( NAMED_UNIT(*) PLANE_ANGLE_UNIT() SI_UNIT($,.RADIAN.) );",
+        );
+        assert_eq!(length_unit(&data), Some(LengthUnit::Millimetre));
+
+        let data = file(
+            "#1=( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#2)) REPRESENTATION_CONTEXT('','') );
+             #2=( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT($,.METRE.) );",
+        );
+        assert_eq!(length_unit(&data), Some(LengthUnit::Metre));
+    }
+
+    #[test]
+    fn conversion_based_units() {
+        let data = file(
+            "#1=( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#2)) REPRESENTATION_CONTEXT('','') );
+             #2=( CONVERSION_BASED_UNIT('INCH',#3) LENGTH_UNIT() NAMED_UNIT(#4) );
+             #3=LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(25.4),#5);
+             #4=DIMENSIONAL_EXPONENTS(1.,0.,0.,0.,0.,0.,0.);
+             #5=( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) );",
+        );
+        assert_eq!(length_unit(&data), Some(LengthUnit::Inch));
+
+        // Something with no name of its own, in terms of inches
+        let data = file(
+            "#1=( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#2)) REPRESENTATION_CONTEXT('','') );
+             #2=( CONVERSION_BASED_UNIT('HAND',#3) LENGTH_UNIT() NAMED_UNIT(*) );
+             #3=LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(4.),#4);
+             #4=( CONVERSION_BASED_UNIT('INCH',#5) LENGTH_UNIT() NAMED_UNIT(*) );
+             #5=LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(25.4),#6);
+             #6=( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) );",
+        );
+        let Some(LengthUnit::Custom(metres)) = length_unit(&data) else {
+            panic!("expected a custom unit");
+        };
+        assert!((metres - 0.1016).abs() < 1e-12);
+    }
+
+    #[test]
+    fn units_of_no_size_are_missing() {
+        for factor in ["0.", "-25.4"] {
+            let data = file(&format!(
+                "#1=( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#2)) REPRESENTATION_CONTEXT('','') );
+                 #2=( CONVERSION_BASED_UNIT('BROKEN',#3) LENGTH_UNIT() NAMED_UNIT(*) );
+                 #3=LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE({}),#4);
+                 #4=( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) );",
+                factor
+            ));
+            assert_eq!(length_unit(&data), None, "{}", factor);
+        }
+    }
+
+    #[test]
+    fn contexts_vote() {
+        // Two contexts in millimetres outvote one in inches, and the uncertainty isn't asked
+        let data = file(
+            "#1=( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#4)) REPRESENTATION_CONTEXT('','') );
+             #2=( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#4)) REPRESENTATION_CONTEXT('','') );
+             #3=( GEOMETRIC_REPRESENTATION_CONTEXT(3) GLOBAL_UNIT_ASSIGNED_CONTEXT((#5)) REPRESENTATION_CONTEXT('','') );
+             #4=( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) );
+             #5=( CONVERSION_BASED_UNIT('INCH',#6) LENGTH_UNIT() NAMED_UNIT(*) );
+             #6=LENGTH_MEASURE_WITH_UNIT(LENGTH_MEASURE(25.4),#4);
+             #7=UNCERTAINTY_MEASURE_WITH_UNIT(LENGTH_MEASURE(1.E-07),#5,'distance_accuracy_value','');",
+        );
+        assert_eq!(length_unit(&data), Some(LengthUnit::Millimetre));
+    }
+
+    #[test]
+    fn conversions() {
+        assert_eq!(LengthUnit::from_metres(0.0254), LengthUnit::Inch);
+        assert!((LengthUnit::Inch.to(LengthUnit::Millimetre) - 25.4).abs() < 1e-12);
+        assert!((LengthUnit::Millimetre.to(LengthUnit::Metre) - 1e-3).abs() < 1e-15);
+
+        let settings = StepLoaderSettings {
+            target_unit: Some(LengthUnit::Metre),
+            ..Default::default()
+        };
+        assert!((rescaled(&settings, Some(LengthUnit::Centimetre)).unwrap().scale - 0.01).abs() < 1e-9);
+        // Undeclared, which can't be converted unless a unit is assumed
+        assert!(matches!(rescaled(&settings, None), Err(StepLoaderError::UnitMissing)));
+        let assuming = StepLoaderSettings {
+            assumed_unit: Some(LengthUnit::Millimetre),
+            ..settings
+        };
+        assert!((rescaled(&assuming, None).unwrap().scale - 0.001).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,185 @@
+//! Texture coordinates from the faces' surface parameters.
+//!
+//! Each face is unrolled along its own (u, v): planes in their own axes, cylinders and cones
+//! around and along their axis and so on, measured in lengths so a texture keeps its aspect.
+//! Faces whose surface we can't evaluate get the backend's parameters when it has them (OCCT
+//! does), scaled to lengths, and are box projected along the axis they face most otherwise.
+
+use std::collections::HashMap;
+
+use bevy_math::{DVec2, DVec3};
+
+use crate::assembly::Assembly;
+use crate::backend::RawMesh;
+use crate::brep::Brep;
+use crate::segment::FaceHit;
+
+/// Gives every vertex of `raw` a UV, splitting vertices wherever the triangles using them
+/// disagree, along seams and between faces.
+pub(crate) fn surface_uvs(raw: &mut RawMesh, hits: &[Option<FaceHit>], brep: &Brep, assembly: &Assembly) {
+    let positions: Vec<DVec3> = raw.positions.iter().map(|p| DVec3::from(p.map(f64::from))).collect();
+    let backend_uvs = std::mem::take(&mut raw.uvs);
+    let triangle_count = raw.indices.len() / 3;
+
+    // Area weighted, by not normalising. Summed over a face they say which way it faces as a
+    // whole, so a box projected face gets a single axis rather than one per triangle
+    let triangle_normals: Vec<DVec3> = raw
+        .indices
+        .chunks_exact(3)
+        .map(|t| {
+            let [a, b, c] = [0, 1, 2].map(|i| positions[t[i] as usize]);
+            (b - a).cross(c - a)
+        })
+        .collect();
+    let mut facing: HashMap<FaceHit, DVec3> = HashMap::new();
+    for (t, n) in triangle_normals.iter().enumerate() {
+        if let Some(hit) = hits.get(t).copied().flatten() {
+            *facing.entry(hit).or_default() += *n;
+        }
+    }
+
+    let backend_scales = if backend_uvs.len() == positions.len() {
+        parameter_scales(&raw.indices, &positions, &backend_uvs, hits)
+    } else {
+        HashMap::new()
+    };
+
+    let inverses: Vec<_> = assembly.nodes.iter().map(|n| n.world.inverse()).collect();
+    let mut corner_uvs: Vec<[f32; 2]> = Vec::with_capacity(triangle_count * 3);
+    for (t, triangle_normal) in triangle_normals.iter().enumerate() {
+        let corners = [0, 1, 2].map(|k| raw.indices[t * 3 + k] as usize);
+        let hit = hits.get(t).copied().flatten();
+
+        let analytic = hit.and_then(|hit| {
+            let surface = &brep.faces.get(&hit.face)?.surface;
+            let mut uvs = [DVec2::ZERO; 3];
+            let mut period = DVec2::ZERO;
+            for (uv, v) in uvs.iter_mut().zip(corners) {
+                (*uv, period) = surface.parameters(inverses[hit.node].transform_point3(positions[v]))?;
+            }
+            Some(unwrap(uvs, period))
+        });
+        let uvs = analytic
+            .or_else(|| {
+                let scale = *backend_scales.get(&hit?)?;
+                Some(corners.map(|v| DVec2::from(backend_uvs[v].map(f64::from)) * scale))
+            })
+            .unwrap_or_else(|| match hit {
+                // In the part's own frame, so every placement of it gets the same UVs
+                Some(hit) => {
+                    let inverse = &inverses[hit.node];
+                    let axis = inverse.transform_vector3(facing.get(&hit).copied().unwrap_or(*triangle_normal));
+                    corners.map(|v| box_projection(inverse.transform_point3(positions[v]), axis))
+                }
+                None => corners.map(|v| box_projection(positions[v], *triangle_normal)),
+            });
+        corner_uvs.extend(uvs.map(|uv| uv.as_vec2().to_array()));
+    }
+
+    // Vertices keep the UV of the first triangle to use them, the others get copies
+    let mut uvs = vec![[0.0; 2]; positions.len()];
+    let mut assigned = vec![false; positions.len()];
+    let mut copies: HashMap<(u32, [u32; 2]), u32> = HashMap::new();
+    for (corner, uv) in corner_uvs.into_iter().enumerate() {
+        let v = raw.indices[corner];
+        let i = v as usize;
+        if !assigned[i] {
+            assigned[i] = true;
+            uvs[i] = uv;
+        } else if uvs[i] != uv {
+            raw.indices[corner] = *copies.entry((v, uv.map(f32::to_bits))).or_insert_with(|| {
+                uvs.push(uv);
+                raw.duplicate_vertex(v)
+            });
+        }
+    }
+    raw.uvs = uvs;
+}
+
+/// For each face, the scale taking the backend's parameters (radians, knot space...) to
+/// lengths along the surface. It's fitted to the face's triangle edges by least squares on
+/// `length² = (su du)² + (sv dv)²`, falling back to one scale for both when the edges don't
+/// pin the two apart.
+fn parameter_scales(
+    indices: &[u32],
+    positions: &[DVec3],
+    uvs: &[[f32; 2]],
+    hits: &[Option<FaceHit>],
+) -> HashMap<FaceHit, DVec2> {
+    #[derive(Default)]
+    struct Fit {
+        uu: f64,
+        uv: f64,
+        vv: f64,
+        lu: f64,
+        lv: f64,
+        lengths: f64,
+        parameters: f64,
+    }
+
+    let mut fits: HashMap<FaceHit, Fit> = HashMap::new();
+    for (t, triangle) in indices.chunks_exact(3).enumerate() {
+        let Some(hit) = hits.get(t).copied().flatten() else {
+            continue;
+        };
+        let fit = fits.entry(hit).or_default();
+        for k in 0..3 {
+            let (a, b) = (triangle[k] as usize, triangle[(k + 1) % 3] as usize);
+            let d = DVec2::from(uvs[b].map(f64::from)) - DVec2::from(uvs[a].map(f64::from));
+            let (du, dv) = (d.x * d.x, d.y * d.y);
+            let length = positions[b].distance_squared(positions[a]);
+            fit.uu += du * du;
+            fit.uv += du * dv;
+            fit.vv += dv * dv;
+            fit.lu += length * du;
+            fit.lv += length * dv;
+            fit.lengths += length;
+            fit.parameters += du + dv;
+        }
+    }
+
+    fits.into_iter()
+        .filter_map(|(hit, fit)| {
+            let det = fit.uu * fit.vv - fit.uv * fit.uv;
+            let separate = (det > 1e-9 * fit.uu * fit.vv).then(|| {
+                DVec2::new(
+                    (fit.lu * fit.vv - fit.lv * fit.uv) / det,
+                    (fit.uu * fit.lv - fit.uv * fit.lu) / det,
+                )
+            });
+            let squared = match separate {
+                Some(s) if s.x > 0.0 && s.y > 0.0 => s,
+                _ if fit.parameters > 0.0 => DVec2::splat(fit.lengths / fit.parameters),
+                _ => return None,
+            };
+            Some((hit, DVec2::new(squared.x.sqrt(), squared.y.sqrt())))
+        })
+        .collect()
+}
+
+/// Moves the corners of a triangle across the seam of any parameter that wraps, so they sit
+/// within half a period of the first and the triangle doesn't stretch round the whole surface.
+fn unwrap(mut uvs: [DVec2; 3], period: DVec2) -> [DVec2; 3] {
+    for axis in 0..2 {
+        if period[axis] <= 0.0 {
+            continue;
+        }
+        for k in 1..3 {
+            let turns = ((uvs[k][axis] - uvs[0][axis]) / period[axis]).round();
+            uvs[k][axis] -= turns * period[axis];
+        }
+    }
+    uvs
+}
+
+/// Projects `p` onto the plane of the axis `facing` is closest to.
+fn box_projection(p: DVec3, facing: DVec3) -> DVec2 {
+    let a = facing.abs();
+    if a.x >= a.y && a.x >= a.z {
+        DVec2::new(p.y, p.z)
+    } else if a.y >= a.z {
+        DVec2::new(p.x, p.z)
+    } else {
+        DVec2::new(p.x, p.y)
+    }
+}
@@ -0,0 +1,324 @@
+//! Geometric validation properties: the volume, surface area and centroid some exporters work
+//! out from their exact geometry and write alongside it, checked against our triangles.
+//!
+//! They follow the CAx-IF recommended practice, one property definition per value:
+//!
+//! ```text
+//! PROPERTY_DEFINITION('geometric validation property','volume of Part',#pds) -> PRODUCT_DEFINITION_SHAPE
+//!     <- PROPERTY_DEFINITION_REPRESENTATION -> REPRESENTATION('volume',(#item),#context)
+//! #item = MEASURE_REPRESENTATION_ITEM('volume measure',VOLUME_MEASURE(1234.5),#unit)
+//! #item = MEASURE_REPRESENTATION_ITEM('surface area measure',AREA_MEASURE(567.8),#unit)
+//! #item = CARTESIAN_POINT('centre point',(1.,2.,3.))
+//! ```
+//!
+//! Only properties of whole products are read, the ones attached to shape aspects or single
+//! solids are skipped.
+
+use std::collections::{BTreeMap, HashMap};
+
+use bevy_math::{DAffine3, DVec3};
+
+use crate::assembly::Assembly;
+use crate::backend::RawMesh;
+use crate::brep::point;
+use crate::part21::{StepData, Value};
+use crate::segment::FaceHit;
+use crate::units::{LengthUnit, measure_factor};
+use crate::{EntityRef, MassProperties, StepLoaderSettings};
+
+/// Volume, area and centroid of a product, whichever of them are known.
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct ValidationProperties {
+    pub volume: Option<f64>,
+    pub area: Option<f64>,
+    pub centroid: Option<DVec3>,
+}
+
+/// A product's validation properties next to the same measured on the mesh, see
+/// [`StepLoadReport::validation`](crate::StepLoadReport::validation).
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationCheck {
+    /// The name of the product.
+    pub product: String,
+    /// Its `PRODUCT_DEFINITION`.
+    pub entity: EntityRef,
+    /// What the file says, in the output mesh's units and axes, about the product's origin.
+    pub expected: ValidationProperties,
+    /// The same from the triangles of the product and its components.
+    pub measured: ValidationProperties,
+}
+
+impl ValidationCheck {
+    /// How far the mesh's volume is from the file's, in percent, negative when it's smaller.
+    pub fn volume_deviation(&self) -> Option<f64> {
+        percent(self.measured.volume?, self.expected.volume?)
+    }
+
+    /// How far the mesh's surface area is from the file's, in percent, negative when it's
+    /// smaller.
+    pub fn area_deviation(&self) -> Option<f64> {
+        percent(self.measured.area?, self.expected.area?)
+    }
+
+    /// How far the mesh's centroid is from the file's, as a percentage of the product's size
+    /// (the cube root of its volume).
+    pub fn centroid_deviation(&self) -> Option<f64> {
+        let distance = self.measured.centroid?.distance(self.expected.centroid?);
+        let size = self.expected.volume.or(self.measured.volume)?.cbrt();
+        (size != 0.0).then(|| distance / size * 100.0)
+    }
+
+    /// Whether every property the file gives is within `tolerance` percent of the mesh's.
+    pub fn passes(&self, tolerance: f64) -> bool {
+        [self.volume_deviation(), self.area_deviation(), self.centroid_deviation()]
+            .into_iter()
+            .flatten()
+            .all(|d| d.abs() <= tolerance)
+    }
+}
+
+fn percent(value: f64, reference: f64) -> Option<f64> {
+    (reference != 0.0).then(|| (value - reference) / reference.abs() * 100.0)
+}
+
+/// Checks every product the file gives validation properties for, in entity id order.
+pub(crate) fn check(
+    data: &StepData,
+    raw: &RawMesh,
+    assembly: &Assembly,
+    hits: &[Option<FaceHit>],
+    file_unit: Option<LengthUnit>,
+    settings: &StepLoaderSettings,
+) -> Vec<ValidationCheck> {
+    let output = DAffine3::from_mat3(settings.up_axis.basis() * f64::from(settings.scale));
+    let scale = f64::from(settings.scale);
+
+    read(data, file_unit)
+        .into_iter()
+        .filter_map(|(pd, expected)| {
+            // The product's first occurrence, the others are the same triangles moved
+            let node = assembly.nodes.iter().position(|n| n.definition == Some(pd))?;
+            Some(ValidationCheck {
+                product: assembly.nodes[node].name.clone(),
+                entity: data.entity(pd),
+                expected: ValidationProperties {
+                    volume: expected.volume.map(|v| v * scale.powi(3)),
+                    area: expected.area.map(|a| a * scale.powi(2)),
+                    centroid: expected.centroid.map(|c| output.transform_point3(c)),
+                },
+                measured: measure(raw, assembly, hits, node, &(output * assembly.nodes[node].world.inverse())),
+            })
+        })
+        .collect()
+}
+
+/// The properties of the triangles under `node`, moved into the product's frame by `transform`.
+fn measure(
+    raw: &RawMesh,
+    assembly: &Assembly,
+    hits: &[Option<FaceHit>],
+    node: usize,
+    transform: &DAffine3,
+) -> ValidationProperties {
+    // Parents come before their children, so one pass finds every descendant
+    let mut inside = vec![false; assembly.nodes.len()];
+    inside[node] = true;
+    for (i, n) in assembly.nodes.iter().enumerate().skip(node + 1) {
+        let within = n.parent.is_some_and(|p| inside[p]);
+        inside[i] = within;
+    }
+
+    let triangles = hits
+        .iter()
+        .enumerate()
+        .filter(|(_, hit)| hit.is_some_and(|h| inside[h.node]))
+        .map(|(t, _)| t);
+    let part = raw.subset(triangles, transform);
+    let positions: Vec<DVec3> = part.positions.iter().map(|p| DVec3::from(p.map(f64::from))).collect();
+    let indices: Vec<usize> = part.indices.iter().map(|&i| i as usize).collect();
+    let mass = MassProperties::from_triangles(&positions, &indices, 1.0);
+    ValidationProperties {
+        volume: Some(mass.volume),
+        area: Some(mass.area),
+        centroid: Some(mass.centroid),
+    }
+}
+
+/// Product definition -> its validation properties, in file units and the product's frame.
+fn read(data: &StepData, file_unit: Option<LengthUnit>) -> BTreeMap<u64, ValidationProperties> {
+    let representations: HashMap<u64, u64> = data
+        .all("PROPERTY_DEFINITION_REPRESENTATION")
+        .filter_map(|(_, pdr)| Some((pdr.param(0).as_id()?, pdr.param(1).as_id()?)))
+        .collect();
+
+    let mut properties: BTreeMap<u64, ValidationProperties> = BTreeMap::new();
+    for (id, definition) in data.all("PROPERTY_DEFINITION") {
+        if !definition
+            .param(0)
+            .as_str()
+            .is_some_and(|name| name.eq_ignore_ascii_case("geometric validation property"))
+        {
+            continue;
+        }
+        let Some(pd) = definition
+            .param(2)
+            .as_id()
+            .and_then(|pds| data.record(pds, "PRODUCT_DEFINITION_SHAPE"))
+            .and_then(|pds| pds.param(2).as_id())
+            .filter(|pd| data.record(*pd, "PRODUCT_DEFINITION").is_some())
+        else {
+            continue;
+        };
+        let Some(items) = representations
+            .get(&id)
+            .and_then(|rep| data.get(*rep))
+            .and_then(|i| i.records.iter().find(|r| r.keyword.ends_with("REPRESENTATION")))
+        else {
+            continue;
+        };
+
+        let product = properties.entry(pd).or_default();
+        for item in items.param(1).refs() {
+            if let Some(centroid) = point(data, item) {
+                product.centroid = Some(centroid);
+            } else if let Some((kind, value, unit)) = measure_item(data, item) {
+                match kind {
+                    Measure::Volume => product.volume = Some(value * measure_factor(data, unit, 3, file_unit)),
+                    Measure::Area => product.area = Some(value * measure_factor(data, unit, 2, file_unit)),
+                }
+            }
+        }
+    }
+    properties
+}
+
+enum Measure {
+    Volume,
+    Area,
+}
+
+/// What a `MEASURE_REPRESENTATION_ITEM` measures, its value and its unit.
+fn measure_item(data: &StepData, id: u64) -> Option<(Measure, f64, Option<u64>)> {
+    let instance = data.get(id)?;
+    let item = instance.record("MEASURE_REPRESENTATION_ITEM")?;
+    // The complex form puts the value in a MEASURE_WITH_UNIT record and the name in a
+    // REPRESENTATION_ITEM one
+    let (value, unit) = match instance.record("MEASURE_WITH_UNIT") {
+        Some(measure) => (measure.param(0), measure.param(1)),
+        None => (item.param(1), item.param(2)),
+    };
+    let name = instance
+        .record("REPRESENTATION_ITEM")
+        .unwrap_or(item)
+        .param(0)
+        .as_str()
+        .unwrap_or_default()
+        .to_ascii_lowercase();
+
+    // The measure's type says what it is, failing that the item's name does
+    let kind = match value {
+        Value::Typed(keyword, _) if keyword == "VOLUME_MEASURE" => Measure::Volume,
+        Value::Typed(keyword, _) if keyword == "AREA_MEASURE" => Measure::Area,
+        Value::Typed(..) => return None,
+        _ if name.contains("volume") => Measure::Volume,
+        _ if name.contains("area") => Measure::Area,
+        _ => return None,
+    };
+    Some((kind, value.as_f64()?, unit.as_id()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::part21::parse;
+
+    const FILE: &str = "ISO-10303-21;
+HEADER;
+ENDSEC;
+DATA;
+#1=PRODUCT('Bracket','Bracket','',(#2));
+#2=PRODUCT_CONTEXT('',#3,'mechanical');
+#3=APPLICATION_CONTEXT('');
+#4=PRODUCT_DEFINITION_FORMATION('','',#1);
+#5=PRODUCT_DEFINITION('design','',#4,#6);
+#6=PRODUCT_DEFINITION_CONTEXT('part definition',#3,'design');
+#7=PRODUCT_DEFINITION_SHAPE('','',#5);
+#10=PROPERTY_DEFINITION('geometric validation property','volume of Bracket',#7);
+#11=PROPERTY_DEFINITION_REPRESENTATION(#10,#12);
+#12=REPRESENTATION('volume',(#13),#40);
+#13=MEASURE_REPRESENTATION_ITEM('volume measure',VOLUME_MEASURE(2.5),#30);
+#14=PROPERTY_DEFINITION('Geometric Validation Property','area of Bracket',#7);
+#15=PROPERTY_DEFINITION_REPRESENTATION(#14,#16);
+#16=REPRESENTATION('surface area',(#17),#40);
+#17=MEASURE_REPRESENTATION_ITEM('surface area measure',1500.,#20);
+#18=PROPERTY_DEFINITION('geometric validation property','centroid of Bracket',#7);
+#19=PROPERTY_DEFINITION_REPRESENTATION(#18,#21);
+#21=REPRESENTATION('centroid',(#22),#40);
+#22=CARTESIAN_POINT('centre point',(1.,2.,3.));
+#20=( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.MILLI.,.METRE.) );
+#23=( LENGTH_UNIT() NAMED_UNIT(*) SI_UNIT(.CENTI.,.METRE.) );
+#30=DERIVED_UNIT((#31));
+#31=DERIVED_UNIT_ELEMENT(#23,3.);
+#50=SHAPE_ASPECT('hole','',#7,.F.);
+#51=PROPERTY_DEFINITION('geometric validation property','volume of hole',#52);
+#52=PRODUCT_DEFINITION_SHAPE('','',#50);
+#53=PROPERTY_DEFINITION_REPRESENTATION(#51,#54);
+#54=REPRESENTATION('volume',(#55),#40);
+#55=MEASURE_REPRESENTATION_ITEM('volume measure',VOLUME_MEASURE(9.),#30);
+ENDSEC;
+END-ISO-10303-21;
+";
+
+    #[test]
+    fn reads_product_properties_in_file_units() {
+        let data = parse(FILE.as_bytes()).unwrap();
+        let properties = read(&data, Some(LengthUnit::Millimetre));
+        // The shape aspect's volume is left out
+        assert_eq!(properties.keys().copied().collect::<Vec<_>>(), [5]);
+        let bracket = properties[&5];
+        // 2.5 cm³ in mm³, an area named rather than typed and with a unit of length
+        assert!((bracket.volume.unwrap() - 2500.0).abs() < 1e-9);
+        assert_eq!(bracket.area, Some(1500.0));
+        assert_eq!(bracket.centroid, Some(DVec3::new(1.0, 2.0, 3.0)));
+    }
+
+    #[test]
+    fn measure_items_by_type_and_name() {
+        let data = parse(FILE.as_bytes()).unwrap();
+        assert!(matches!(measure_item(&data, 13), Some((Measure::Volume, 2.5, Some(30)))));
+        assert!(matches!(measure_item(&data, 17), Some((Measure::Area, 1500.0, Some(20)))));
+        assert!(measure_item(&data, 22).is_none());
+    }
+
+    #[test]
+    fn deviations() {
+        let check = ValidationCheck {
+            product: "Bracket".to_string(),
+            entity: EntityRef { id: 5, location: None },
+            expected: ValidationProperties {
+                volume: Some(8.0),
+                area: Some(24.0),
+                centroid: Some(DVec3::ZERO),
+            },
+            measured: ValidationProperties {
+                volume: Some(7.6),
+                area: Some(24.6),
+                centroid: Some(DVec3::new(0.0, 0.04, 0.0)),
+            },
+        };
+        assert!((check.volume_deviation().unwrap() + 5.0).abs() < 1e-9);
+        assert!((check.area_deviation().unwrap() - 2.5).abs() < 1e-9);
+        // 0.04 off on a part 2 across
+        assert!((check.centroid_deviation().unwrap() - 2.0).abs() < 1e-9);
+        assert!(check.passes(5.5));
+        assert!(!check.passes(4.5));
+
+        let unknown = ValidationCheck {
+            expected: ValidationProperties::default(),
+            ..check
+        };
+        assert_eq!(unknown.volume_deviation(), None);
+        assert_eq!(unknown.centroid_deviation(), None);
+        assert!(unknown.passes(0.0));
+    }
+}